@@ -0,0 +1,448 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use super::error::HyperVError;
+use super::executor::{
+    ExecutorState, PowerShellExecutor, PowerShellProcess, RunOptions, TeeReader,
+};
+use super::script::ps_literal;
+use super::utils::now_ms;
+
+/// Journal file rotated once it grows past this size
+const MAX_JOURNAL_BYTES: u64 = 1024 * 1024;
+
+/// Number of journal files kept (`audit.jsonl`, `audit.1.jsonl`, ...)
+const MAX_JOURNAL_FILES: usize = 5;
+
+/// Longest stderr excerpt stored per entry
+const MAX_STDERR_BYTES: usize = 4096;
+
+/// Replacement text for redacted secrets
+pub const REDACTED: &str = "********";
+
+/// Secrets shorter than this are only redacted where bound as a whole
+/// literal; matching them anywhere would mangle unrelated text
+const MIN_FREE_TEXT_SECRET_LEN: usize = 6;
+
+/// Who is running a script: the command kind, the VM it targets and any
+/// secrets that must never reach the journal
+#[derive(Clone, Debug, Default)]
+pub struct AuditContext {
+    pub kind: String,
+    pub vm: Option<String>,
+    /// Remote host profile the scripts run on; `None` for this machine
+    pub host: Option<String>,
+    secrets: Vec<String>,
+}
+
+impl AuditContext {
+    pub fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn vm(mut self, name: &str) -> Self {
+        self.vm = Some(name.to_string());
+        self
+    }
+
+    pub fn secrets(&self) -> &[String] {
+        &self.secrets
+    }
+
+    /// Redact `secret` from every script recorded under this context
+    pub fn secret(mut self, secret: Option<&str>) -> Self {
+        if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+            self.secrets.push(secret.to_string());
+        }
+        self
+    }
+}
+
+/// One executed script
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub kind: String,
+    pub vm: Option<String>,
+    /// Remote host profile; absent for this machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// `run` for captured calls, `spawn` for streamed ones
+    pub mode: String,
+    /// Script text with secrets redacted
+    pub script: String,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+    pub ended_at: u64,
+    /// Process exit code; `None` when unknown (killed, timed out, failed to start)
+    pub exit_code: Option<i32>,
+    /// `HyperVError` code for failed `run` calls
+    pub error_code: Option<String>,
+    /// Truncated stderr
+    pub stderr: Option<String>,
+}
+
+/// Filter for `query_audit_log`
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditQuery {
+    pub vm: Option<String>,
+    /// Inclusive lower bound on `started_at` (Unix ms)
+    pub from: Option<u64>,
+    /// Inclusive upper bound on `started_at` (Unix ms)
+    pub to: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+/// Append-only JSON Lines journal, rotated by size
+pub struct AuditLog {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn journal(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.dir.join("audit.jsonl")
+        } else {
+            self.dir.join(format!("audit.{}.jsonl", index))
+        }
+    }
+
+    pub fn record(&self, entry: &AuditEntry) {
+        let _guard = self.lock.lock().unwrap();
+        if let Err(e) = self.append(entry) {
+            eprintln!("Failed to write audit log: {}", e);
+        }
+    }
+
+    fn append(&self, entry: &AuditEntry) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let current = self.journal(0);
+        if fs::metadata(&current).is_ok_and(|m| m.len() >= MAX_JOURNAL_BYTES) {
+            self.rotate()?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&current)?
+            .write_all(line.as_bytes())
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        let oldest = self.journal(MAX_JOURNAL_FILES - 1);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for index in (0..MAX_JOURNAL_FILES - 1).rev() {
+            let from = self.journal(index);
+            if from.exists() {
+                fs::rename(&from, self.journal(index + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Entries matching `query`, newest first
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, HyperVError> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = Vec::new();
+        for index in (0..MAX_JOURNAL_FILES).rev() {
+            let path = self.journal(index);
+            if path.exists() {
+                read_journal(&path, query, &mut entries)?;
+            }
+        }
+
+        entries.sort_by_key(|e| Reverse(e.started_at));
+        if let Some(limit) = query.limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+}
+
+fn read_journal(
+    path: &Path,
+    query: &AuditQuery,
+    entries: &mut Vec<AuditEntry>,
+) -> Result<(), HyperVError> {
+    let file =
+        fs::File::open(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        // Skip lines torn by a crash mid-write
+        let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+            continue;
+        };
+        let vm_matches = match &query.vm {
+            Some(vm) => entry
+                .vm
+                .as_deref()
+                .is_some_and(|v| v.eq_ignore_ascii_case(vm)),
+            None => true,
+        };
+        if vm_matches
+            && query.from.is_none_or(|from| entry.started_at >= from)
+            && query.to.is_none_or(|to| entry.started_at <= to)
+        {
+            entries.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Remove secrets from a script before it is journaled: every known secret
+/// bound as a literal, longer ones also anywhere else (raw and as escaped
+/// inside a literal), and any `$...Password... = ...` binding.
+pub fn redact(script: &str, secrets: &[String]) -> String {
+    let mut text = script.to_string();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        let quoted = ps_literal(secret);
+        text = text.replace(&quoted, &ps_literal(REDACTED));
+        if secret.chars().count() >= MIN_FREE_TEXT_SECRET_LEN {
+            // Inside a literal, quote characters in the secret appear doubled
+            text = text.replace(&quoted[1..quoted.len() - 1], REDACTED);
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+    }
+
+    text.lines()
+        .map(redact_binding)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn redact_binding(line: &str) -> String {
+    let trimmed = line.trim_start();
+    let binding = trimmed
+        .strip_prefix('$')
+        .and_then(|rest| rest.split_once('='))
+        .map(|(name, _)| name.trim())
+        .filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+    match binding {
+        Some(name) if name.to_ascii_lowercase().contains("password") => {
+            format!("${} = {}", name, ps_literal(REDACTED))
+        }
+        _ => line.to_string(),
+    }
+}
+
+fn truncate(text: &str) -> String {
+    let text = text.trim();
+    if text.len() <= MAX_STDERR_BYTES {
+        return text.to_string();
+    }
+    let mut end = MAX_STDERR_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
+
+/// Executor that journals every script it runs or spawns
+pub struct AuditedExecutor {
+    inner: Arc<dyn PowerShellExecutor>,
+    log: Arc<AuditLog>,
+    context: AuditContext,
+}
+
+impl AuditedExecutor {
+    pub fn new(
+        inner: Arc<dyn PowerShellExecutor>,
+        log: Arc<AuditLog>,
+        context: AuditContext,
+    ) -> Self {
+        Self {
+            inner,
+            log,
+            context,
+        }
+    }
+
+    fn entry(&self, mode: &str, script: &str) -> AuditEntry {
+        let started_at = now_ms();
+        AuditEntry {
+            kind: self.context.kind.clone(),
+            vm: self.context.vm.clone(),
+            host: self.context.host.clone(),
+            mode: mode.to_string(),
+            script: redact(script, &self.context.secrets),
+            started_at,
+            ended_at: started_at,
+            exit_code: None,
+            error_code: None,
+            stderr: None,
+        }
+    }
+}
+
+impl PowerShellExecutor for AuditedExecutor {
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+        let mut entry = self.entry("run", script);
+        let result = self.inner.run_with(script, options);
+
+        entry.ended_at = now_ms();
+        match &result {
+            Ok(_) => entry.exit_code = Some(0),
+            Err(e) => {
+                let details = e.details();
+                let stderr = details
+                    .record
+                    .as_ref()
+                    .map_or(details.message.as_str(), |r| r.raw.as_str());
+                entry.error_code = Some(e.code().to_string());
+                entry.stderr = Some(truncate(&redact(stderr, &self.context.secrets)));
+            }
+        }
+        self.log.record(&entry);
+        result
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        let mut entry = self.entry("spawn", script);
+        match self.inner.spawn(script) {
+            Ok(inner) => Ok(Box::new(AuditedProcess {
+                inner,
+                entry: Some(entry),
+                stderr: Arc::new(Mutex::new(Vec::new())),
+                secrets: self.context.secrets.clone(),
+                log: self.log.clone(),
+            })),
+            Err(e) => {
+                entry.ended_at = now_ms();
+                entry.stderr = Some(truncate(&e.to_string()));
+                self.log.record(&entry);
+                Err(e)
+            }
+        }
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), HyperVError> {
+        self.inner.kill(pid)
+    }
+}
+
+/// Spawned process that journals its exit code and stderr once it ends
+struct AuditedProcess {
+    inner: Box<dyn PowerShellProcess>,
+    /// Pending entry, written on `wait` (or on drop if never waited)
+    entry: Option<AuditEntry>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    secrets: Vec<String>,
+    log: Arc<AuditLog>,
+}
+
+impl AuditedProcess {
+    fn finish(&mut self, exit_code: Option<i32>) {
+        if let Some(mut entry) = self.entry.take() {
+            let stderr = self.stderr.lock().unwrap();
+            entry.ended_at = now_ms();
+            entry.exit_code = exit_code;
+            if !stderr.is_empty() {
+                let stderr = String::from_utf8_lossy(&stderr);
+                entry.stderr = Some(truncate(&redact(&stderr, &self.secrets)));
+            }
+            self.log.record(&entry);
+        }
+    }
+}
+
+impl PowerShellProcess for AuditedProcess {
+    fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.inner.take_stdout()
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        let inner = self.inner.take_stderr()?;
+        Some(Box::new(TeeReader::new(
+            inner,
+            self.stderr.clone(),
+            MAX_STDERR_BYTES,
+        )))
+    }
+
+    fn wait(&mut self) -> std::io::Result<Option<i32>> {
+        let result = self.inner.wait();
+        self.finish(result.as_ref().ok().copied().flatten());
+        result
+    }
+}
+
+impl Drop for AuditedProcess {
+    fn drop(&mut self) {
+        self.finish(None);
+    }
+}
+
+/// Query the audit journal by VM and time range
+#[tauri::command]
+pub async fn query_audit_log(
+    ps: State<'_, ExecutorState>,
+    query: AuditQuery,
+) -> Result<Vec<AuditEntry>, HyperVError> {
+    match ps.audit_log() {
+        Some(log) => tokio::task::spawn_blocking(move || log.query(&query))
+            .await
+            .map_err(HyperVError::task_failed)?,
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn redact_replaces_long_secrets_everywhere() {
+        let script = "Set-Secret -Value 'it''s hunter2'\nnet user admin it's hunter2";
+        assert_eq!(
+            redact(script, &secrets(&["it's hunter2"])),
+            "Set-Secret -Value '********'\nnet user admin ********"
+        );
+    }
+
+    #[test]
+    fn redact_short_secrets_only_as_literals() {
+        let script =
+            "New-VM -MemoryStartupBytes 1234MB -Name 'a'\nStart-VM -Name 'vm-a' -Key '1234'";
+        assert_eq!(
+            redact(script, &secrets(&["a", "1234", ""])),
+            "New-VM -MemoryStartupBytes 1234MB -Name '********'\nStart-VM -Name 'vm-a' -Key '********'"
+        );
+    }
+
+    #[test]
+    fn redact_password_bindings() {
+        let script = "$AdminPassword = 'x'\n$Name = 'x'\n  $password=$env:SECRET";
+        assert_eq!(
+            redact(script, &[]),
+            "$AdminPassword = '********'\n$Name = 'x'\n$password = '********'"
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, State, Window};
+
+use super::audit::AuditContext;
+use super::error::HyperVError;
+use super::executor::{ExecutorState, PowerShellExecutor};
+use super::rollback::ProvisionResource;
+use super::script::ScriptBuilder;
+use super::utils::{hex_file_name, now_ms};
+
+/// Prefix of the line CopyFilesToVM.ps1 prints once a checkpoint's work is
+/// done: `##CHECKPOINT|<id>`
+pub const CHECKPOINT_MARKER: &str = "##CHECKPOINT|";
+
+/// Resumable part of provisioning, in run order. Must match the
+/// `Complete-ProvisionCheckpoint` calls in CopyFilesToVM.template.ps1.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Checkpoint {
+    /// Virtual disk created with Windows applied
+    Image,
+    /// VM registered with its disk, TPM and DVD drive
+    Vm,
+    /// GPU partition adapter assigned
+    Gpu,
+    /// Host GPU drivers copied into the disk
+    Drivers,
+    /// VM started for the first time
+    FirstBoot,
+}
+
+impl Checkpoint {
+    pub const ALL: [Checkpoint; 5] = [
+        Checkpoint::Image,
+        Checkpoint::Vm,
+        Checkpoint::Gpu,
+        Checkpoint::Drivers,
+        Checkpoint::FirstBoot,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Checkpoint::Image => "image",
+            Checkpoint::Vm => "vm",
+            Checkpoint::Gpu => "gpu",
+            Checkpoint::Drivers => "drivers",
+            Checkpoint::FirstBoot => "first_boot",
+        }
+    }
+
+    /// Parse a `##CHECKPOINT|...` line; anything else is `None`
+    pub fn parse_marker(line: &str) -> Option<Self> {
+        let id = line.trim().strip_prefix(CHECKPOINT_MARKER)?.trim();
+        Self::ALL.into_iter().find(|c| c.id() == id)
+    }
+
+    /// Value of `__RESUME_CHECKPOINTS__` telling the script what to skip
+    pub fn join(checkpoints: &[Checkpoint]) -> String {
+        checkpoints
+            .iter()
+            .map(|c| c.id())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Persisted progress of a provisioning run that did not finish
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PhaseState {
+    pub vm: String,
+    /// Host profile the VM is provisioned on; `None` for this machine
+    pub host: Option<String>,
+    /// Path of the VM's virtual disk file
+    pub vhd_file: String,
+    /// ISO or golden image the disk was created from
+    #[serde(alias = "iso_path")]
+    pub source_path: String,
+    pub gpu_name: String,
+    /// Completed checkpoints, in run order
+    pub completed: Vec<Checkpoint>,
+    /// What the completed checkpoints created, undone if the run is abandoned
+    pub resources: Vec<ProvisionResource>,
+    /// Unix time in milliseconds
+    pub updated_at: u64,
+}
+
+impl PhaseState {
+    pub fn new(
+        vm: &str,
+        host: Option<String>,
+        vhd_file: &str,
+        source_path: &str,
+        gpu_name: &str,
+    ) -> Self {
+        Self {
+            vm: vm.to_string(),
+            host,
+            vhd_file: vhd_file.to_string(),
+            source_path: source_path.to_string(),
+            gpu_name: gpu_name.to_string(),
+            completed: Vec::new(),
+            resources: Vec::new(),
+            updated_at: now_ms(),
+        }
+    }
+
+    /// Record `checkpoint` as done along with the resources it created
+    pub fn complete(&mut self, checkpoint: Checkpoint, resources: Vec<ProvisionResource>) {
+        if !self.completed.contains(&checkpoint) {
+            self.completed.push(checkpoint);
+        }
+        for resource in resources {
+            if !self.resources.contains(&resource) {
+                self.resources.push(resource);
+            }
+        }
+        self.updated_at = now_ms();
+    }
+}
+
+/// Phase state files, one per host and VM, under the app data directory
+#[derive(Clone)]
+pub struct PhaseStateStore {
+    dir: PathBuf,
+}
+
+impl PhaseStateStore {
+    pub fn new(app: &AppHandle) -> Self {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join("provisioning");
+        Self::at(dir)
+    }
+
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Names are encoded, as they come from the frontend and host profiles
+    fn path(&self, host: Option<&str>, vm: &str) -> PathBuf {
+        let dir = match host {
+            Some(host) => self.dir.join("hosts").join(hex_file_name(host)),
+            None => self.dir.join("local"),
+        };
+        // Hyper-V VM names are case-insensitive
+        dir.join(format!("{}.json", hex_file_name(&vm.to_lowercase())))
+    }
+
+    pub fn load(&self, host: Option<&str>, vm: &str) -> Option<PhaseState> {
+        let content = fs::read_to_string(self.path(host, vm)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, state: &PhaseState) -> Result<(), String> {
+        let path = self.path(state.host.as_deref(), &state.vm);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let content = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+
+    pub fn remove(&self, host: Option<&str>, vm: &str) -> Result<(), String> {
+        match fs::remove_file(self.path(host, vm)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether the output of each checkpoint is present on the host
+#[derive(Deserialize)]
+struct CheckpointOutputs {
+    image: bool,
+    vm: bool,
+    gpu: bool,
+    drivers: bool,
+    first_boot: bool,
+}
+
+/// The completed checkpoints of `state` whose outputs are still present on
+/// the host, up to the first one that is not
+pub fn verify(
+    ps: &dyn PowerShellExecutor,
+    state: &PhaseState,
+) -> Result<Vec<Checkpoint>, HyperVError> {
+    let script = ScriptBuilder::new()
+        .with_get_vm_exact()
+        .arg("VMName", &state.vm)
+        .arg("VhdPath", &state.vhd_file)
+        .bool(
+            "CheckDrivers",
+            state.completed.contains(&Checkpoint::Drivers),
+        )
+        .build(
+            r#"
+            $vhd = Test-Path -LiteralPath $VhdPath
+            $vm = Get-VMExact $VMName -ErrorAction SilentlyContinue
+            $vmOk = $vhd -and [bool]$vm -and [bool](Get-VMHardDiskDrive -VM $vm | Where-Object { $_.Path -eq $VhdPath })
+            $gpu = $vmOk -and [bool](Get-VMGpuPartitionAdapter -VM $vm -ErrorAction SilentlyContinue)
+            $drivers = $false
+            if ($CheckDrivers -and $vhd) {
+                if ($vm -and $vm.State -ne 'Off') {
+                    # A running VM holds the disk; trust the recorded checkpoint
+                    $drivers = $true
+                } else {
+                    $disk = Mount-VHD -Path $VhdPath -ReadOnly -PassThru | Get-Disk
+                    try {
+                        $drivers = [bool]($disk | Get-Partition | Where-Object { "$($_.DriveLetter)" -match '^[A-Z]$' } |
+                            Where-Object { Test-Path "$($_.DriveLetter):\Windows\System32\HostDriverStore" })
+                    } finally {
+                        Dismount-VHD -Path $VhdPath
+                    }
+                }
+            }
+            [PSCustomObject]@{
+                image = [bool]$vhd
+                vm = [bool]$vmOk
+                gpu = [bool]$gpu
+                drivers = [bool]$drivers
+                first_boot = [bool]($vmOk -and $vm.State -eq 'Running')
+            } | ConvertTo-Json -Compress
+            "#,
+        );
+    let output = ps.run(&script)?;
+    let outputs: CheckpointOutputs = serde_json::from_str(output.trim()).map_err(|e| {
+        HyperVError::internal(format!("Failed to read checkpoint verification: {}", e))
+    })?;
+
+    Ok(Checkpoint::ALL
+        .into_iter()
+        .take_while(|c| {
+            state.completed.contains(c)
+                && match c {
+                    Checkpoint::Image => outputs.image,
+                    Checkpoint::Vm => outputs.vm,
+                    Checkpoint::Gpu => outputs.gpu,
+                    Checkpoint::Drivers => outputs.drivers,
+                    Checkpoint::FirstBoot => outputs.first_boot,
+                }
+        })
+        .collect())
+}
+
+/// A provisioning run that can be resumed
+#[derive(Serialize, Clone, Debug)]
+pub struct ResumeInfo {
+    pub state: PhaseState,
+    /// Completed checkpoints whose outputs are still present; resuming skips these
+    pub verified: Vec<Checkpoint>,
+    /// Where a resumed run starts
+    pub next: Option<Checkpoint>,
+}
+
+pub fn resume_info(
+    ps: &dyn PowerShellExecutor,
+    state: PhaseState,
+) -> Result<ResumeInfo, HyperVError> {
+    let verified = verify(ps, &state)?;
+    let next = Checkpoint::ALL.into_iter().find(|c| !verified.contains(c));
+    Ok(ResumeInfo {
+        state,
+        verified,
+        next,
+    })
+}
+
+/// The interrupted provisioning run of VM `name` on the active host, if any
+#[tauri::command]
+pub async fn get_provisioning_checkpoint(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    name: String,
+) -> Result<Option<ResumeInfo>, HyperVError> {
+    let store = PhaseStateStore::new(window.app_handle());
+    let host = ps.host().map(|h| h.name);
+    let Some(state) = store.load(host.as_deref(), &name) else {
+        return Ok(None);
+    };
+    let info = ps
+        .run_blocking(
+            AuditContext::new("get_provisioning_checkpoint").vm(&name),
+            None,
+            move |ps| resume_info(ps, state),
+        )
+        .await?;
+    Ok(Some(info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+    use std::path::Path;
+
+    fn state(vm: &str, host: Option<&str>) -> PhaseState {
+        PhaseState::new(
+            vm,
+            host.map(str::to_string),
+            "C:\\VMs\\Gaming.vhdx",
+            "C:\\iso\\win11.iso",
+            "AUTO",
+        )
+    }
+
+    fn outputs(image: bool, vm: bool, gpu: bool, drivers: bool, first_boot: bool) -> String {
+        format!(
+            r#"{{"image":{},"vm":{},"gpu":{},"drivers":{},"first_boot":{}}}"#,
+            image, vm, gpu, drivers, first_boot
+        )
+    }
+
+    #[test]
+    fn complete_records_each_checkpoint_once() {
+        let mut state = state("Gaming", None);
+        let vhd = ProvisionResource::Vhd("C:\\VMs\\Gaming.vhdx".to_string());
+        let vm = ProvisionResource::Vm("Gaming".to_string());
+        state.complete(Checkpoint::Image, vec![vhd.clone()]);
+        state.complete(Checkpoint::Vm, vec![vm.clone(), vhd.clone()]);
+        state.complete(Checkpoint::Image, vec![vhd.clone()]);
+        assert_eq!(state.completed, [Checkpoint::Image, Checkpoint::Vm]);
+        assert_eq!(state.resources, [vhd, vm]);
+        assert_eq!(Checkpoint::join(&state.completed), "image,vm");
+    }
+
+    #[test]
+    fn resume_info_stops_at_first_missing_output() {
+        let mut state = state("Gaming", None);
+        for checkpoint in [Checkpoint::Image, Checkpoint::Vm, Checkpoint::Gpu] {
+            state.complete(checkpoint, Vec::new());
+        }
+
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Get-VMGpuPartitionAdapter",
+            &outputs(true, true, false, true, true),
+        )]);
+        let info = resume_info(&ps, state.clone()).unwrap();
+        assert_eq!(info.verified, [Checkpoint::Image, Checkpoint::Vm]);
+        assert_eq!(info.next, Some(Checkpoint::Gpu));
+        assert!(ps.calls()[0].contains("$CheckDrivers = $false"));
+
+        // A missing disk invalidates everything after it
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Get-VMGpuPartitionAdapter",
+            &outputs(false, true, true, true, true),
+        )]);
+        let info = resume_info(&ps, state.clone()).unwrap();
+        assert!(info.verified.is_empty());
+        assert_eq!(info.next, Some(Checkpoint::Image));
+
+        // Outputs present for checkpoints never reached do not count
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Get-VMGpuPartitionAdapter",
+            &outputs(true, true, true, true, true),
+        )]);
+        let info = resume_info(&ps, state.clone()).unwrap();
+        assert_eq!(info.next, Some(Checkpoint::Drivers));
+
+        for checkpoint in [Checkpoint::Drivers, Checkpoint::FirstBoot] {
+            state.complete(checkpoint, Vec::new());
+        }
+        let info = resume_info(&ps, state.clone()).unwrap();
+        assert_eq!(info.verified, Checkpoint::ALL);
+        assert_eq!(info.next, None);
+
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok("Get-VMGpuPartitionAdapter", "")]);
+        assert!(matches!(
+            resume_info(&ps, state),
+            Err(HyperVError::Internal(_))
+        ));
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("phase-state-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = PhaseStateStore::at(&dir);
+
+        let mut local = state("Gaming", None);
+        local.complete(
+            Checkpoint::Image,
+            vec![ProvisionResource::Vhd(local.vhd_file.clone())],
+        );
+        store.save(&local).unwrap();
+        store.save(&state("Gaming", Some("lab"))).unwrap();
+
+        // VM names are case-insensitive, hosts are kept apart
+        let loaded = store.load(None, "GAMING").unwrap();
+        assert_eq!(loaded.completed, [Checkpoint::Image]);
+        assert_eq!(loaded.resources, local.resources);
+        assert!(store
+            .load(Some("lab"), "gaming")
+            .unwrap()
+            .completed
+            .is_empty());
+        assert!(store.load(Some("other"), "Gaming").is_none());
+
+        store.remove(None, "Gaming").unwrap();
+        assert!(store.load(None, "Gaming").is_none());
+        assert!(store.load(Some("lab"), "Gaming").is_some());
+        store.remove(None, "Gaming").unwrap();
+
+        // Names cannot reach outside the store
+        let hostile = state("..\\../../escape", Some("../.."));
+        store.save(&hostile).unwrap();
+        assert!(store.load(Some("../.."), "..\\../../escape").is_some());
+        let path = store.path(Some("../.."), "..\\../../escape");
+        assert!(path.is_file());
+        assert_eq!(
+            path.parent().and_then(Path::parent),
+            Some(&*dir.join("hosts"))
+        );
+        store.remove(Some("../.."), "..\\../../escape").unwrap();
+        assert!(store.load(Some("../.."), "..\\../../escape").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
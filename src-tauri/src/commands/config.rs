@@ -59,7 +59,12 @@ impl VMSettingsStore {
             let _ = fs::create_dir_all(&file_path);
         }
         file_path.push("vm_connection_settings.json");
+        Self::at(file_path)
+    }
 
+    /// Settings persisted in `file_path`
+    pub fn at(file_path: impl Into<PathBuf>) -> Self {
+        let file_path = file_path.into();
         let settings = if file_path.exists() {
             let content = fs::read_to_string(&file_path).unwrap_or_default();
             serde_json::from_str(&content).unwrap_or_default()
@@ -88,4 +93,17 @@ impl VMSettingsStore {
 
         Ok(())
     }
+
+    /// Forget the settings of `vm_name`, e.g. once the VM is gone
+    pub fn remove(&self, vm_name: &str) -> Result<(), String> {
+        let mut lock = self.settings.lock().unwrap();
+        if lock.remove(vm_name).is_none() {
+            return Ok(());
+        }
+
+        let content = serde_json::to_string_pretty(&*lock).map_err(|e| e.to_string())?;
+        fs::write(&self.file_path, content).map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,393 @@
+use serde::{Deserialize, Serialize};
+
+/// Largest disk the VHD format supports, in GB
+const VHD_MAX_SIZE_GB: u32 = 2040;
+/// Largest disk the VHDX format supports, in GB
+const VHDX_MAX_SIZE_GB: u32 = 64 * 1024;
+
+/// Virtual disk file format
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VhdFormat {
+    /// Legacy format; boots a Generation 1 VM from an MBR disk
+    Vhd,
+    #[default]
+    Vhdx,
+}
+
+impl VhdFormat {
+    /// File extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            VhdFormat::Vhd => "vhd",
+            VhdFormat::Vhdx => "vhdx",
+        }
+    }
+
+    /// `-VHDFormat` value of Convert-WindowsImage
+    pub fn ps_name(self) -> &'static str {
+        match self {
+            VhdFormat::Vhd => "VHD",
+            VhdFormat::Vhdx => "VHDX",
+        }
+    }
+
+    /// `-DiskLayout` value of Convert-WindowsImage. Generation 2 VMs only
+    /// boot VHDX disks, so a VHD gets an MBR layout for a Generation 1 VM.
+    pub fn disk_layout(self) -> &'static str {
+        match self {
+            VhdFormat::Vhd => "BIOS",
+            VhdFormat::Vhdx => "UEFI",
+        }
+    }
+
+    pub fn vm_generation(self) -> u32 {
+        match self {
+            VhdFormat::Vhd => 1,
+            VhdFormat::Vhdx => 2,
+        }
+    }
+
+    fn max_size_gb(self) -> u32 {
+        match self {
+            VhdFormat::Vhd => VHD_MAX_SIZE_GB,
+            VhdFormat::Vhdx => VHDX_MAX_SIZE_GB,
+        }
+    }
+}
+
+/// How the disk file claims space on the host
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskAllocation {
+    /// Grows as the guest writes
+    #[default]
+    Dynamic,
+    /// Full size allocated up front
+    Fixed,
+}
+
+/// Virtual disk options of a new VM. Every field is optional in the
+/// frontend payload; the defaults match what provisioning always created.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DiskOptions {
+    pub format: VhdFormat,
+    pub allocation: DiskAllocation,
+    /// Block size of a dynamic disk; `None` keeps the default for the format
+    pub block_size_kb: Option<u32>,
+    /// 512 or 4096; `None` keeps the Hyper-V default
+    pub logical_sector_bytes: Option<u32>,
+    /// 512 or 4096; `None` keeps the Hyper-V default
+    pub physical_sector_bytes: Option<u32>,
+    /// Size of an empty, formatted second disk; `None` for no data disk
+    pub data_disk_gb: Option<u32>,
+}
+
+impl DiskOptions {
+    /// Whether the options can create a disk of `disk_size_gb`, and the
+    /// reason they cannot. Golden images are copied or chained as they are,
+    /// so only the data disk can be configured for them.
+    pub fn check(
+        &self,
+        disk_size_gb: u32,
+        golden_image: bool,
+        tpm_enabled: bool,
+    ) -> Result<(), String> {
+        if golden_image
+            && (self.format != VhdFormat::Vhdx
+                || self.allocation != DiskAllocation::Dynamic
+                || self.block_size_kb.is_some()
+                || self.logical_sector_bytes.is_some()
+                || self.physical_sector_bytes.is_some())
+        {
+            return Err(
+                "A golden image keeps its own format, allocation and sector sizes".to_string(),
+            );
+        }
+        if self.format == VhdFormat::Vhd && tpm_enabled {
+            return Err(
+                "VHD disks boot a Generation 1 VM, which has no virtual TPM; use VHDX".to_string(),
+            );
+        }
+        if disk_size_gb > self.format.max_size_gb() {
+            return Err(format!(
+                "{} disks can be at most {} GB",
+                self.format.ps_name(),
+                self.format.max_size_gb()
+            ));
+        }
+
+        if let Some(block_size_kb) = self.block_size_kb {
+            if self.allocation == DiskAllocation::Fixed {
+                return Err("Block size only applies to dynamic disks".to_string());
+            }
+            let valid = match self.format {
+                VhdFormat::Vhd => block_size_kb == 512 || block_size_kb == 2048,
+                VhdFormat::Vhdx => {
+                    block_size_kb.is_power_of_two() && (1024..=256 * 1024).contains(&block_size_kb)
+                }
+            };
+            if !valid {
+                return Err(match self.format {
+                    VhdFormat::Vhd => "VHD block size must be 512 KB or 2 MB".to_string(),
+                    VhdFormat::Vhdx => {
+                        "VHDX block size must be a power of two from 1 MB to 256 MB".to_string()
+                    }
+                });
+            }
+        }
+
+        for sector in [self.logical_sector_bytes, self.physical_sector_bytes]
+            .into_iter()
+            .flatten()
+        {
+            if sector != 512 && sector != 4096 {
+                return Err("Sector size must be 512 or 4096 bytes".to_string());
+            }
+        }
+        if self.format == VhdFormat::Vhd && self.logical_sector_bytes == Some(4096) {
+            return Err("VHD disks only support 512 byte logical sectors".to_string());
+        }
+        if let (Some(logical), Some(physical)) =
+            (self.logical_sector_bytes, self.physical_sector_bytes)
+        {
+            if logical > physical {
+                return Err(
+                    "Logical sector size cannot exceed the physical sector size".to_string()
+                );
+            }
+        }
+
+        if let Some(data_disk_gb) = self.data_disk_gb {
+            if data_disk_gb == 0 || data_disk_gb > self.format.max_size_gb() {
+                return Err(format!(
+                    "Data disk must be between 1 and {} GB",
+                    self.format.max_size_gb()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Short description for the resource check that passed
+    pub fn describe(&self) -> String {
+        let allocation = match self.allocation {
+            DiskAllocation::Dynamic => "dynamic",
+            DiskAllocation::Fixed => "fixed",
+        };
+        let mut description = format!("{} {} disk", allocation, self.format.ps_name());
+        if let Some(data_disk_gb) = self.data_disk_gb {
+            description.push_str(&format!(" with a {} GB data disk", data_disk_gb));
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn check_enforces_format_and_sector_rules() {
+        // (options, disk GB, golden image, TPM, expected error fragment)
+        let cases = [
+            (json!({}), 64, false, true, None),
+            (json!({}), 64, true, true, None),
+            (json!({ "data_disk_gb": 200 }), 64, true, true, None),
+            (
+                json!({ "format": "vhd" }),
+                64,
+                true,
+                false,
+                Some("golden image"),
+            ),
+            (
+                json!({ "allocation": "fixed" }),
+                64,
+                true,
+                true,
+                Some("golden image"),
+            ),
+            (
+                json!({ "block_size_kb": 1024 }),
+                64,
+                true,
+                true,
+                Some("golden image"),
+            ),
+            (
+                json!({ "logical_sector_bytes": 512 }),
+                64,
+                true,
+                true,
+                Some("golden image"),
+            ),
+            (
+                json!({ "physical_sector_bytes": 4096 }),
+                64,
+                true,
+                true,
+                Some("golden image"),
+            ),
+            (
+                json!({ "format": "vhd" }),
+                64,
+                false,
+                true,
+                Some("no virtual TPM"),
+            ),
+            (json!({ "format": "vhd" }), 64, false, false, None),
+            (json!({ "format": "vhd" }), 2040, false, false, None),
+            (
+                json!({ "format": "vhd" }),
+                2041,
+                false,
+                false,
+                Some("at most 2040 GB"),
+            ),
+            (json!({}), 64 * 1024, false, true, None),
+            (
+                json!({}),
+                64 * 1024 + 1,
+                false,
+                true,
+                Some("at most 65536 GB"),
+            ),
+            (
+                json!({ "format": "vhd", "block_size_kb": 512 }),
+                64,
+                false,
+                false,
+                None,
+            ),
+            (
+                json!({ "format": "vhd", "block_size_kb": 2048 }),
+                64,
+                false,
+                false,
+                None,
+            ),
+            (
+                json!({ "format": "vhd", "block_size_kb": 1024 }),
+                64,
+                false,
+                false,
+                Some("512 KB or 2 MB"),
+            ),
+            (json!({ "block_size_kb": 1024 }), 64, false, true, None),
+            (json!({ "block_size_kb": 262144 }), 64, false, true, None),
+            (
+                json!({ "block_size_kb": 512 }),
+                64,
+                false,
+                true,
+                Some("1 MB to 256 MB"),
+            ),
+            (
+                json!({ "block_size_kb": 3072 }),
+                64,
+                false,
+                true,
+                Some("power of two"),
+            ),
+            (
+                json!({ "block_size_kb": 524288 }),
+                64,
+                false,
+                true,
+                Some("1 MB to 256 MB"),
+            ),
+            (
+                json!({ "allocation": "fixed", "block_size_kb": 32768 }),
+                64,
+                false,
+                true,
+                Some("dynamic disks"),
+            ),
+            (
+                json!({ "logical_sector_bytes": 4096, "physical_sector_bytes": 4096 }),
+                64,
+                false,
+                true,
+                None,
+            ),
+            (
+                json!({ "logical_sector_bytes": 1024 }),
+                64,
+                false,
+                true,
+                Some("512 or 4096"),
+            ),
+            (
+                json!({ "physical_sector_bytes": 0 }),
+                64,
+                false,
+                true,
+                Some("512 or 4096"),
+            ),
+            (
+                json!({ "format": "vhd", "logical_sector_bytes": 4096 }),
+                64,
+                false,
+                false,
+                Some("512 byte logical"),
+            ),
+            (
+                json!({ "format": "vhd", "physical_sector_bytes": 4096 }),
+                64,
+                false,
+                false,
+                None,
+            ),
+            (
+                json!({ "logical_sector_bytes": 4096, "physical_sector_bytes": 512 }),
+                64,
+                false,
+                true,
+                Some("cannot exceed"),
+            ),
+            (
+                json!({ "data_disk_gb": 0 }),
+                64,
+                false,
+                true,
+                Some("between 1 and 65536 GB"),
+            ),
+            (
+                json!({ "format": "vhd", "data_disk_gb": 2041 }),
+                64,
+                false,
+                false,
+                Some("between 1 and 2040 GB"),
+            ),
+        ];
+        for (options, size, golden, tpm, expected) in cases {
+            let parsed: DiskOptions = serde_json::from_value(options.clone()).unwrap();
+            let result = parsed.check(size, golden, tpm);
+            match expected {
+                None => assert_eq!(result, Ok(()), "{options} {size} GB"),
+                Some(fragment) => {
+                    let err = result.expect_err(&format!("{options} {size} GB should fail"));
+                    assert!(err.contains(fragment), "{options}: {err}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vhd_disks_boot_generation_one() {
+        let vhd = DiskOptions {
+            format: VhdFormat::Vhd,
+            allocation: DiskAllocation::Fixed,
+            data_disk_gb: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(vhd.format.vm_generation(), 1);
+        assert_eq!(vhd.format.disk_layout(), "BIOS");
+        assert_eq!(vhd.describe(), "fixed VHD disk with a 100 GB data disk");
+        assert_eq!(DiskOptions::default().format.vm_generation(), 2);
+        assert_eq!(DiskOptions::default().describe(), "dynamic VHDX disk");
+    }
+}
@@ -1,9 +1,21 @@
-﻿$params = @{
+﻿param(
+    # Passed by the caller so the password is never staged in a file
+    [string]$Password
+)
+$params = @{
     VMName = "__VM_NAME__"
     SourcePath = "__ISO_PATH__"
-    Edition    = 6
-    VhdFormat  = "VHDX"
-    DiskLayout = "UEFI"
+    SourceVhd = "__SOURCE_VHD__"
+    Differencing = "__DIFFERENCING__"
+    Edition    = __EDITION__
+    VhdFormat  = "__VHD_FORMAT__"
+    DiskLayout = "__DISK_LAYOUT__"
+    Generation = __VM_GENERATION__
+    FixedDisk  = "__FIXED_DISK__"
+    BlockSizeBytes = __BLOCK_SIZE_KB__KB
+    LogicalSectorBytes = __LOGICAL_SECTOR_BYTES__
+    PhysicalSectorBytes = __PHYSICAL_SECTOR_BYTES__
+    DataDiskBytes = __DATA_DISK_GB__GB
     SizeBytes  = __DISK_SIZE_GB__GB
     MemoryAmount = __MEMORY_GB__GB
     CPUCores = __CPU_COUNT__
@@ -15,7 +27,7 @@
     Team_ID = ""
     Key = ""
     Username = "__USERNAME__"
-    Password = "__PASSWORD__"
+    Password = $Password
     Autologon = "__AUTO_LOGON__"
 }
 $ErrorActionPreference = 'Stop'
@@ -38,6 +50,143 @@ Write-Host "DEBUG: Importing module..." -ForegroundColor Green
 Import-Module $PSSCriptRoot\Add-VMGpuPartitionAdapterFiles.psm1
 Write-Host "DEBUG: Module imported." -ForegroundColor Green
 
+# Provisioning phases in order; must match PROVISION_PHASES in progress.rs
+$ProvisionPhases = @('prepare', 'mount_iso', 'create_disk', 'apply_image', 'create_vm', 'assign_gpu', 'drivers', 'start_vm')
+
+function Set-ProvisionPhase {
+param(
+[string]$Phase,
+[int]$Percent = -1,
+[string]$Message = ""
+)
+    $Step = [array]::IndexOf($ProvisionPhases, $Phase) + 1
+    Write-ProvisionProgress -Phase $Phase -Step $Step -Total $ProvisionPhases.Count -Percent $Percent -Message $Message
+}
+
+# Tells the app about something to undo if provisioning fails or is cancelled;
+# Kind is one of iso_mount, vhd, vm, key_protector (see rollback.rs)
+function Register-ProvisionResource {
+param(
+[string]$Kind,
+[string]$Value
+)
+    Write-Host "##RESOURCE|$Kind|$Value"
+}
+
+# Checkpoints a previous run completed and the app verified; their work is
+# skipped (see checkpoint.rs)
+$CompletedCheckpoints = @("__RESUME_CHECKPOINTS__" -split ',' | Where-Object { $_ })
+
+# vmconnect only reaches VMs on this machine, so it is not opened for a
+# VM provisioned on a remote host
+$OpenConsole = "__OPEN_CONSOLE__" -eq "true"
+
+function Test-ProvisionCheckpoint {
+param(
+[string]$Checkpoint
+)
+    if ($CompletedCheckpoints -contains $Checkpoint) {
+        Write-Host "INFO   : Skipping $Checkpoint, completed by a previous run"
+        return $true
+    }
+    return $false
+}
+
+function Complete-ProvisionCheckpoint {
+param(
+[string]$Checkpoint
+)
+    Write-Host "##CHECKPOINT|$Checkpoint"
+}
+
+# Mounts an offline VHD(X) and returns the drive of its Windows partition
+function Mount-WindowsVHD {
+param(
+[string]$VHDPath
+)
+    $disk = Mount-VHD -Path $VHDPath -PassThru | Get-Disk
+    $windowsPartition = $disk | Get-Partition | Sort-Object Size | Select-Object -Last 1
+    if ("$($windowsPartition.DriveLetter)" -notmatch '^[A-Z]$') {
+        $windowsPartition | Add-PartitionAccessPath -AssignDriveLetter
+        $windowsPartition = $windowsPartition | Get-Partition
+        }
+    "$($windowsPartition.DriveLetter):"
+}
+
+# Creates the VM's disk from a sysprepped golden image, as a full copy or a
+# differencing child, and stages the answer file and first logon scripts in it
+function New-VHDFromGoldenImage {
+param(
+[string]$SourceVhd,
+[string]$VHDPath,
+[bool]$Differencing,
+[string]$UnattendPath,
+[string]$Team_ID,
+[string]$Key
+)
+    if ($Differencing) {
+        New-VHD -Path $VHDPath -ParentPath $SourceVhd -Differencing | Out-Null
+        }
+    else {
+        Set-ProvisionPhase -Phase apply_image -Percent 0 -Message "Copying $SourceVhd"
+        $sourceBytes = (Get-Item -LiteralPath $SourceVhd).Length
+        $copy = Start-Job -ScriptBlock { param($From, $To) Copy-Item -LiteralPath $From -Destination $To } -ArgumentList $SourceVhd, $VHDPath
+        while ($copy.State -eq 'Running') {
+            Start-Sleep -Seconds 2
+            $copied = (Get-Item -LiteralPath $VHDPath -ErrorAction SilentlyContinue).Length
+            if ($copied -and $sourceBytes) {
+                Set-ProvisionPhase -Phase apply_image -Percent ([math]::Min(99, [int](100 * $copied / $sourceBytes)))
+                }
+            }
+        $failed = $copy.State -eq 'Failed'
+        $reason = $copy.ChildJobs[0].JobStateInfo.Reason
+        Remove-Job $copy -Force
+        if ($failed) {
+            throw "Copying $SourceVhd failed: $reason"
+            }
+        }
+    Set-ProvisionPhase -Phase apply_image -Percent 100
+
+    $windowsDrive = Mount-WindowsVHD -VHDPath $VHDPath
+    try {
+        # Picked up by the specialize and oobeSystem passes of a sysprepped image
+        New-Item -ItemType Directory -Path "$windowsDrive\Windows\Panther" -Force | Out-Null
+        Copy-Unattend -UnattendPath $UnattendPath -Destination "$windowsDrive\Windows\Panther\unattend.xml"
+        Setup-Install -DriveLetter $windowsDrive -Team_ID $Team_ID -Key $Key
+        }
+    finally {
+        Dismount-VHD -Path $VHDPath
+        }
+}
+
+# Copies the staged answer file into the VM's disk. The staged copy holds a
+# token in place of the password, which is filled in from -Password here.
+function Copy-Unattend {
+param(
+[string]$UnattendPath,
+[string]$Destination
+)
+    $xml = [IO.File]::ReadAllText($UnattendPath)
+    $password = [Security.SecurityElement]::Escape($script:Password)
+    [IO.File]::WriteAllText($Destination, $xml.Replace('%PROVISION_PASSWORD%', $password))
+}
+
+# Copies the host's GPU driver files into the Windows partition of an
+# offline VHD(X)
+function Inject-GPUDrivers {
+param(
+[string]$VHDPath,
+[string]$GPUName
+)
+    $windowsDrive = Mount-WindowsVHD -VHDPath $VHDPath
+    try {
+        Add-VMGpuPartitionAdapterFiles -GPUName $GPUName -DriveLetter $windowsDrive
+        }
+    finally {
+        Dismount-VHD -Path $VHDPath
+        }
+}
+
 function Is-Administrator  
 {  
     $CurrentUser = [Security.Principal.WindowsIdentity]::GetCurrent();
@@ -90,16 +239,62 @@ Write-Host "DEBUG: ISO Mounted at $(($mountResult | Get-Volume).DriveLetter)" -F
 Function ConcatenateVHDPath {
 param(
 [string]$VHDPath,
-[string]$VMName
+[string]$VMName,
+[string]$VhdFormat = "VHDX"
 )
 if ($VHDPath[-1] -eq '\') {
-    $VHDPath + $VMName + ".vhdx"
+    $VHDPath + $VMName + "." + $VhdFormat.ToLower()
     }
 Else {
-    $VHDPath + "\" +  $VMName + ".vhdx"
+    $VHDPath + "\" +  $VMName + "." + $VhdFormat.ToLower()
     }
 }
 
+Function New-ProvisionVHD {
+param(
+[string]$Path,
+[uint64]$SizeBytes,
+[bool]$Fixed,
+[uint32]$BlockSizeBytes = 0,
+[uint32]$LogicalSectorBytes = 0,
+[uint32]$PhysicalSectorBytes = 0
+)
+    # Zero keeps the Hyper-V default for the format
+    $vhdParams = @{ Path = $Path; SizeBytes = $SizeBytes }
+    if ($Fixed) {
+        $vhdParams.Fixed = $true
+        }
+    else {
+        $vhdParams.Dynamic = $true
+        if ($BlockSizeBytes -gt 0) { $vhdParams.BlockSizeBytes = $BlockSizeBytes }
+        }
+    if ($LogicalSectorBytes -gt 0) { $vhdParams.LogicalSectorSizeBytes = $LogicalSectorBytes }
+    if ($PhysicalSectorBytes -gt 0) { $vhdParams.PhysicalSectorSizeBytes = $PhysicalSectorBytes }
+    New-VHD @vhdParams
+}
+
+Function New-DataDisk {
+param(
+[string]$Path,
+[uint64]$SizeBytes,
+[bool]$Fixed,
+[uint32]$BlockSizeBytes = 0,
+[uint32]$LogicalSectorBytes = 0,
+[uint32]$PhysicalSectorBytes = 0
+)
+    # Partitioned and formatted here so the guest sees a ready volume
+    New-ProvisionVHD -Path $Path -SizeBytes $SizeBytes -Fixed $Fixed -BlockSizeBytes $BlockSizeBytes -LogicalSectorBytes $LogicalSectorBytes -PhysicalSectorBytes $PhysicalSectorBytes | Out-Null
+    $disk = Mount-VHD -Path $Path -PassThru | Get-Disk
+    try {
+        Initialize-Disk -Number $disk.Number -PartitionStyle GPT
+        New-Partition -DiskNumber $disk.Number -UseMaximumSize |
+            Format-Volume -FileSystem NTFS -NewFileSystemLabel "Data" -Confirm:$false -Force | Out-Null
+        }
+    finally {
+        Dismount-VHD -Path $Path
+        }
+}
+
 Function SmartExit {
 param (
 [switch]$NoHalt,
@@ -113,9 +308,17 @@ Function Check-Params {
 param (
     $VMName,
     $SourcePath,
+    $SourceVhd,
+    $Differencing,
     $Edition,
     $VhdFormat,
     $DiskLayout,
+    $Generation,
+    $FixedDisk,
+    $BlockSizeBytes,
+    $LogicalSectorBytes,
+    $PhysicalSectorBytes,
+    $DataDiskBytes,
     $SizeBytes,
     $MemoryAmount,
     $CPUCores,
@@ -145,7 +348,12 @@ if (!(Get-VMSwitch -Name $NetworkSwitch -ErrorAction SilentlyContinue)) {
 if (!(Test-Path $VHDPath)) {
     $ExitReason += "VHDPath Directory doesn't exist, please create it before running this script."
     }
-if (!(test-path $SourcePath)) {
+if ($SourceVhd) {
+    if (!(Test-Path $SourceVhd)) {
+        $ExitReason += "Golden image $SourceVhd does not exist."
+        }
+    }
+elseif (!(test-path $SourcePath)) {
     $ExitReason += "ISO Path Invalid. Please enter a valid ISO Path in the SourcePath section of Params."
     }
 else {
@@ -454,6 +662,22 @@ function Convert-WindowsImage-Local {
         [ValidateRange(512MB, 64TB)]
         $SizeBytes = 25GB,
 
+        [Parameter(ParameterSetName="SRC")]
+        [switch]
+        $IsFixed = $false,
+
+        [Parameter(ParameterSetName="SRC")]
+        [UInt32]
+        $VhdBlockSizeBytes = 0,
+
+        [Parameter(ParameterSetName="SRC")]
+        [UInt32]
+        $LogicalSectorBytes = 0,
+
+        [Parameter(ParameterSetName="SRC")]
+        [UInt32]
+        $PhysicalSectorBytes = 0,
+
         [Parameter(ParameterSetName="SRC")]
         [Alias("Format")]
         [string]
@@ -1940,6 +2164,11 @@ You can use the fields below to configure the VHD or VHDX that you want to creat
                 $BlockSizeBytes = 512KB
             }
 
+            if ($VhdBlockSizeBytes -gt 0)
+            {
+                $BlockSizeBytes = $VhdBlockSizeBytes
+            }
+
             # Check if -VHDPath and -WorkingDirectory were both specified.
             if ((![String]::IsNullOrEmpty($VHDPath)) -and (![String]::IsNullOrEmpty($WorkingDirectory)))
             {
@@ -2116,8 +2345,15 @@ You can use the fields below to configure the VHD or VHDX that you want to creat
 
             if ($hyperVEnabled)
             {
-                Write-W2VInfo "Creating sparse disk..."
-                $newVhd = New-VHD -Path $VHDPath -SizeBytes $SizeBytes -BlockSizeBytes $BlockSizeBytes -Dynamic
+                if ($IsFixed)
+                {
+                    Write-W2VInfo "Creating fixed disk..."
+                }
+                else
+                {
+                    Write-W2VInfo "Creating sparse disk..."
+                }
+                $newVhd = New-ProvisionVHD -Path $VHDPath -SizeBytes $SizeBytes -Fixed $IsFixed -BlockSizeBytes $BlockSizeBytes -LogicalSectorBytes $LogicalSectorBytes -PhysicalSectorBytes $PhysicalSectorBytes
 
                 Write-W2VInfo "Mounting $VHDFormat..."
                 $disk = $newVhd | Mount-VHD -PassThru | Get-Disk
@@ -2293,9 +2529,35 @@ You can use the fields below to configure the VHD or VHDX that you want to creat
             ####################################################################################################
 
             Write-W2VInfo "Applying image to $VHDFormat. This could take a while..."
+            Set-ProvisionPhase -Phase apply_image -Percent 0 -Message "Applying image to $windowsDrive"
             if ((Get-Command Expand-WindowsImage -ErrorAction SilentlyContinue) -and ((-not $ApplyEA) -and ([string]::IsNullOrEmpty($DismPath))))
             {
-                Expand-WindowsImage -ApplyPath $windowsDrive -ImagePath $SourcePath -Index $ImageIndex -LogPath "$($logFolder)\DismLogs.log" | Out-Null
+                # Apply in a job and report progress as the bytes written to the
+                # Windows volume against the expanded size of the image
+                $imageBytes = (Get-WindowsImage -ImagePath $SourcePath -Index $ImageIndex).ImageSize
+                $volumeInfo = New-Object System.IO.DriveInfo($windowsDrive)
+                $usedBefore = $volumeInfo.TotalSize - $volumeInfo.AvailableFreeSpace
+                $applyJob = Start-Job -ScriptBlock {
+                    param($ApplyPath, $ImagePath, $Index, $LogPath)
+                    Expand-WindowsImage -ApplyPath $ApplyPath -ImagePath $ImagePath -Index $Index -LogPath $LogPath | Out-Null
+                } -ArgumentList $windowsDrive, $SourcePath, $ImageIndex, "$($logFolder)\DismLogs.log"
+                while ($applyJob.State -eq 'Running')
+                {
+                    Start-Sleep -Seconds 5
+                    if ($imageBytes -gt 0)
+                    {
+                        $volumeInfo.Refresh()
+                        $written = ($volumeInfo.TotalSize - $volumeInfo.AvailableFreeSpace) - $usedBefore
+                        Set-ProvisionPhase -Phase apply_image -Percent ([math]::Min(99, [int](100 * $written / $imageBytes)))
+                    }
+                }
+                if ($applyJob.State -ne 'Completed')
+                {
+                    $reason = $applyJob.ChildJobs[0].JobStateInfo.Reason
+                    Remove-Job -Job $applyJob -Force
+                    throw "Image Apply failed! $reason"
+                }
+                Receive-Job -Job $applyJob -AutoRemoveJob -Wait | Out-Null
             }
             else
             {
@@ -2324,6 +2586,7 @@ You can use the fields below to configure the VHD or VHDX that you want to creat
  	                throw "Image Apply failed! See DismImageApply logs for details"
                 }
             }
+            Set-ProvisionPhase -Phase apply_image -Percent 100
             Write-W2VInfo "Image was applied successfully. "
 
             #
@@ -2332,7 +2595,7 @@ You can use the fields below to configure the VHD or VHDX that you want to creat
             if (![string]::IsNullOrEmpty($UnattendPath))
             {
                 Write-W2VInfo "Applying unattend file ($(Split-Path $UnattendPath -Leaf))..."
-                Copy-Item -Path $UnattendPath -Destination (Join-Path $windowsDrive "unattend.xml") -Force
+                Copy-Unattend -UnattendPath $UnattendPath -Destination (Join-Path $windowsDrive "unattend.xml")
             }
 
             if (![string]::IsNullOrEmpty($MergeFolderPath))
@@ -2541,10 +2804,6 @@ You can use the fields below to configure the VHD or VHDX that you want to creat
             # Remove system partition access path, if necessary
             #
 
-            if (($GPUName)) {
-            Add-VMGpuPartitionAdapterFiles -GPUName $GPUName -DriveLetter $windowsDrive
-            }
-
             Write-W2VInfo "Setting up VBCable to install at boot"
             Setup-Install -DriveLetter $WindowsDrive -Team_ID $team_id -Key $key
 
@@ -4402,26 +4661,62 @@ param(
 [string]$GPUName,
 [float]$GPUResourceAllocationPercentage,
 [string]$SourcePath,
+[string]$SourceVhd,
+[string]$Differencing,
+[int]$Generation,
+[string]$FixedDisk,
+[uint32]$BlockSizeBytes,
+[uint32]$LogicalSectorBytes,
+[uint32]$PhysicalSectorBytes,
+[uint64]$DataDiskBytes,
 [string]$Team_ID,
 [string]$Key,
 [string]$username,
 [string]$password,
 [string]$autologon
 )
-    $VHDPath = ConcatenateVHDPath -VHDPath $VHDPath -VMName $VMName
-    $DriveLetter = Mount-ISOReliable -SourcePath $SourcePath
-
-    if ($(Get-VM -Name $VMName -ErrorAction SilentlyContinue) -ne $NULL) {
-        SmartExit -ExitReason "Virtual Machine already exists with name $VMName, please delete existing VM or change VMName"
+    $DataDiskPath = ConcatenateVHDPath -VHDPath $VHDPath -VMName "$VMName-data" -VhdFormat $VhdFormat
+    $VHDPath = ConcatenateVHDPath -VHDPath $VHDPath -VMName $VMName -VhdFormat $VhdFormat
+    if ($SourceVhd -and -not (Test-ProvisionCheckpoint image)) {
+        if ($(Get-VM -Name $VMName -ErrorAction SilentlyContinue) -ne $NULL) {
+            SmartExit -ExitReason "Virtual Machine already exists with name $VMName, please delete existing VM or change VMName"
+            }
+        if (Test-Path $vhdPath) {
+            SmartExit -ExitReason "Virtual Machine Disk already exists at $vhdPath, please delete existing VHDX or change VMName"
+            }
+        Set-ProvisionPhase -Phase create_disk -Message "Creating $VhdPath from $SourceVhd"
+        Register-ProvisionResource -Kind vhd -Value $VhdPath
+        New-VHDFromGoldenImage -SourceVhd $SourceVhd -VHDPath $VhdPath -Differencing ($Differencing -eq "true") -UnattendPath $UnattendPath -Team_ID $Team_ID -Key $Key
+        Complete-ProvisionCheckpoint image
         }
-    if (Test-Path $vhdPath) {
-        SmartExit -ExitReason "Virtual Machine Disk already exists at $vhdPath, please delete existing VHDX or change VMName"
+    if (-not $SourceVhd -and -not (Test-ProvisionCheckpoint image)) {
+        Set-ProvisionPhase -Phase mount_iso -Message "Mounting $SourcePath"
+        $ISOWasAttached = (Get-DiskImage -ImagePath $SourcePath -ErrorAction SilentlyContinue).Attached
+        $DriveLetter = Mount-ISOReliable -SourcePath $SourcePath
+        if (-not $ISOWasAttached) {
+            Register-ProvisionResource -Kind iso_mount -Value $SourcePath
+            }
+
+        if ($(Get-VM -Name $VMName -ErrorAction SilentlyContinue) -ne $NULL) {
+            SmartExit -ExitReason "Virtual Machine already exists with name $VMName, please delete existing VM or change VMName"
+            }
+        if (Test-Path $vhdPath) {
+            SmartExit -ExitReason "Virtual Machine Disk already exists at $vhdPath, please delete existing VHDX or change VMName"
+            }
+        Modify-AutoUnattend -username "$username" -password "$password" -autologon $autologon -hostname $VMName -UnattendPath $UnattendPath
+        Set-ProvisionPhase -Phase create_disk -Message "Creating $VhdPath"
+        Register-ProvisionResource -Kind vhd -Value $VhdPath
+        Convert-WindowsImage-Local -SourcePath $SourcePath -ISODriveLetter $DriveLetter -Edition $Edition -VHDFormat $Vhdformat -VHDPath $VhdPath -DiskLayout $DiskLayout -UnattendPath $UnattendPath -GPUName $GPUName -Team_ID $Team_ID -Key $Key -SizeBytes $SizeBytes -IsFixed:($FixedDisk -eq "true") -VhdBlockSizeBytes $BlockSizeBytes -LogicalSectorBytes $LogicalSectorBytes -PhysicalSectorBytes $PhysicalSectorBytes | Out-Null
+        if (!(Test-Path $vhdPath)) {
+            SmartExit -ExitReason "Failed to create VHDX, stopping script"
+            }
+        Complete-ProvisionCheckpoint image
         }
-    Modify-AutoUnattend -username "$username" -password "$password" -autologon $autologon -hostname $VMName -UnattendPath $UnattendPath
-    $MaxAvailableVersion = (Get-VMHostSupportedVersion).Version | Where-Object {$_.Major -lt 254}| Select-Object -Last 1 
-    Convert-WindowsImage-Local -SourcePath $SourcePath -ISODriveLetter $DriveLetter -Edition $Edition -VHDFormat $Vhdformat -VHDPath $VhdPath -DiskLayout $DiskLayout -UnattendPath $UnattendPath -GPUName $GPUName -Team_ID $Team_ID -Key $Key -SizeBytes $SizeBytes| Out-Null
-    if (Test-Path $vhdPath) {
-        New-VM -Name $VMName -MemoryStartupBytes $MemoryAmount -VHDPath $VhdPath -Generation 2 -SwitchName $NetworkSwitch -Version $MaxAvailableVersion | Out-Null
+    if (-not (Test-ProvisionCheckpoint vm)) {
+        $MaxAvailableVersion = (Get-VMHostSupportedVersion).Version | Where-Object {$_.Major -lt 254}| Select-Object -Last 1 
+        Set-ProvisionPhase -Phase create_vm -Message "Registering VM $VMName"
+        New-VM -Name $VMName -MemoryStartupBytes $MemoryAmount -VHDPath $VhdPath -Generation $Generation -SwitchName $NetworkSwitch -Version $MaxAvailableVersion | Out-Null
+        Register-ProvisionResource -Kind vm -Value $VMName
         Set-VM -Name $VMName -ProcessorCount $CPUCores -CheckpointType Disabled -LowMemoryMappedIoSpace 3GB -HighMemoryMappedIoSpace 32GB -GuestControlledCacheTypes $true -AutomaticStopAction ShutDown
         Set-VMMemory -VMName $VMName -DynamicMemoryEnabled $false 
         $CPUManufacturer = Get-CimInstance -ClassName Win32_Processor | Foreach-Object Manufacturer
@@ -4431,21 +4726,54 @@ param(
         Else {
             Set-VMProcessor -VMName $VMName -ExposeVirtualizationExtensions $true
             }
-        Set-VMKeyProtector -VMName $VMName -NewLocalKeyProtector
-        Enable-VMTPM -VMName $VMName 
-        Add-VMDvdDrive -VMName $VMName -Path $SourcePath
+        if ($Generation -ge 2) {
+            Set-VMKeyProtector -VMName $VMName -NewLocalKeyProtector
+            Register-ProvisionResource -Kind key_protector -Value $VMName
+            Enable-VMTPM -VMName $VMName 
+            }
+        if (-not $SourceVhd) {
+            if ($Generation -ge 2) {
+                Add-VMDvdDrive -VMName $VMName -Path $SourcePath
+                }
+            else {
+                # Generation 1 VMs come with a DVD drive on IDE 1:0
+                Set-VMDvdDrive -VMName $VMName -ControllerNumber 1 -ControllerLocation 0 -Path $SourcePath
+                }
+            }
+        if ($DataDiskBytes -gt 0) {
+            if (Test-Path $DataDiskPath) {
+                SmartExit -ExitReason "Data disk already exists at $DataDiskPath, please delete it or change VMName"
+                }
+            Set-ProvisionPhase -Phase create_vm -Message "Creating data disk $DataDiskPath"
+            Register-ProvisionResource -Kind vhd -Value $DataDiskPath
+            New-DataDisk -Path $DataDiskPath -SizeBytes $DataDiskBytes -Fixed ($FixedDisk -eq "true") -BlockSizeBytes $BlockSizeBytes -LogicalSectorBytes $LogicalSectorBytes -PhysicalSectorBytes $PhysicalSectorBytes
+            Add-VMHardDiskDrive -VMName $VMName -Path $DataDiskPath
+            }
+        Complete-ProvisionCheckpoint vm
+        }
+    if (-not (Test-ProvisionCheckpoint gpu)) {
+        Set-ProvisionPhase -Phase assign_gpu -Message "Assigning $GPUName"
         Assign-VMGPUPartitionAdapter -GPUName $GPUName -VMName $VMName -GPUResourceAllocationPercentage $GPUResourceAllocationPercentage
+        Complete-ProvisionCheckpoint gpu
+        }
+    if (-not (Test-ProvisionCheckpoint drivers)) {
+        if ($GPUName) {
+            Set-ProvisionPhase -Phase drivers -Percent 0 -Message "Copying driver files for $GPUName"
+            Inject-GPUDrivers -VHDPath $VhdPath -GPUName $GPUName
+            Set-ProvisionPhase -Phase drivers -Percent 100
+            }
+        Complete-ProvisionCheckpoint drivers
+        }
+    if ($OpenConsole) {
         Write-Host "INFO   : Starting and connecting to VM"
         Start-Process "vmconnect.exe" -ArgumentList "localhost $VMName"
         }
-    else {
-    SmartExit -ExitReason "Failed to create VHDX, stopping script"
-    }
 }
 
 # ... function definitions ...
 
 Write-Host "DEBUG: Checking params..." -ForegroundColor Green
+Set-ProvisionPhase -Phase prepare
 Check-Params @params
 Write-Host "DEBUG: Params checked." -ForegroundColor Green
 
@@ -4453,7 +4781,12 @@ Write-Host "DEBUG: Starting New-GPUEnabledVM..." -ForegroundColor Green
 New-GPUEnabledVM @params
 Write-Host "DEBUG: New-GPUEnabledVM finished." -ForegroundColor Green
 
-Start-VM -Name $params.VMName
+if (-not (Test-ProvisionCheckpoint first_boot)) {
+    Set-ProvisionPhase -Phase start_vm -Message "Starting $($params.VMName)"
+    Start-VM -Name $params.VMName
+    Set-ProvisionPhase -Phase start_vm -Percent 100
+    Complete-ProvisionCheckpoint first_boot
+    }
 
 Write-Host "If all went well the Virtual Machine will have started, 
 In a few minutes it will load the Windows desktop, 
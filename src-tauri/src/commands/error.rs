@@ -0,0 +1,479 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Details of a PowerShell `ErrorRecord` recovered from stderr
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    /// Error category, e.g. `ObjectNotFound` or `PermissionDenied`
+    pub category: Option<String>,
+    /// Full `CategoryInfo` line, e.g. `InvalidArgument: (x:String) [Get-VM], VirtualizationException`
+    pub category_info: Option<String>,
+    pub fully_qualified_error_id: Option<String>,
+    /// Raw (cleaned) stderr text
+    pub raw: String,
+}
+
+/// Payload shared by every `HyperVError` variant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorDetails {
+    pub message: String,
+    /// Suggested remediation shown to the user
+    pub hint: Option<String>,
+    pub record: Option<Box<ErrorRecord>>,
+}
+
+const ELEVATION_HINT: &str =
+    "Restart the application as Administrator or add your account to the 'Hyper-V Administrators' group.";
+
+/// Error returned by backend commands.
+/// Serialized as `{ code, message, hint, record }` so the frontend can branch on `code`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HyperVError {
+    NotElevated(ErrorDetails),
+    VmNotFound(ErrorDetails),
+    HyperVModuleMissing(ErrorDetails),
+    GpuInUse(ErrorDetails),
+    /// Another operation holds the VM
+    VmBusy(ErrorDetails),
+    Timeout(ErrorDetails),
+    Cancelled(ErrorDetails),
+    InvalidInput(ErrorDetails),
+    PowerShell(ErrorDetails),
+    Internal(ErrorDetails),
+}
+
+impl HyperVError {
+    pub fn details(&self) -> &ErrorDetails {
+        match self {
+            HyperVError::NotElevated(d)
+            | HyperVError::VmNotFound(d)
+            | HyperVError::HyperVModuleMissing(d)
+            | HyperVError::GpuInUse(d)
+            | HyperVError::VmBusy(d)
+            | HyperVError::Timeout(d)
+            | HyperVError::Cancelled(d)
+            | HyperVError::InvalidInput(d)
+            | HyperVError::PowerShell(d)
+            | HyperVError::Internal(d) => d,
+        }
+    }
+
+    fn details_mut(&mut self) -> &mut ErrorDetails {
+        match self {
+            HyperVError::NotElevated(d)
+            | HyperVError::VmNotFound(d)
+            | HyperVError::HyperVModuleMissing(d)
+            | HyperVError::GpuInUse(d)
+            | HyperVError::VmBusy(d)
+            | HyperVError::Timeout(d)
+            | HyperVError::Cancelled(d)
+            | HyperVError::InvalidInput(d)
+            | HyperVError::PowerShell(d)
+            | HyperVError::Internal(d) => d,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.details().message
+    }
+
+    /// Append `hint` to the error's existing hint, if any
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        let details = self.details_mut();
+        let hint = hint.into();
+        details.hint = Some(match details.hint.take() {
+            Some(existing) => format!("{} {}", existing, hint),
+            None => hint,
+        });
+        self
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            HyperVError::NotElevated(_) => "NOT_ELEVATED",
+            HyperVError::VmNotFound(_) => "VM_NOT_FOUND",
+            HyperVError::HyperVModuleMissing(_) => "HYPER_V_MODULE_MISSING",
+            HyperVError::GpuInUse(_) => "GPU_IN_USE",
+            HyperVError::VmBusy(_) => "VM_BUSY",
+            HyperVError::Timeout(_) => "TIMEOUT",
+            HyperVError::Cancelled(_) => "CANCELLED",
+            HyperVError::InvalidInput(_) => "INVALID_INPUT",
+            HyperVError::PowerShell(_) => "POWER_SHELL",
+            HyperVError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        HyperVError::Internal(ErrorDetails::new(message))
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        HyperVError::InvalidInput(ErrorDetails::new(message))
+    }
+
+    pub fn powershell(message: impl Into<String>) -> Self {
+        HyperVError::PowerShell(ErrorDetails::new(message))
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        HyperVError::Cancelled(ErrorDetails::new(message))
+    }
+
+    /// `context` failed on a file or process. Access denied means the app
+    /// lacks the rights it needs; anything else is internal.
+    pub fn io(context: &str, e: &std::io::Error) -> Self {
+        let message = format!("{}: {}", context, e);
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            HyperVError::NotElevated(ErrorDetails {
+                message,
+                hint: Some(ELEVATION_HINT.to_string()),
+                record: None,
+            })
+        } else {
+            HyperVError::internal(message)
+        }
+    }
+
+    /// A blocking task that panicked or was aborted
+    pub fn task_failed(e: tokio::task::JoinError) -> Self {
+        HyperVError::internal(format!("Task failed: {}", e))
+    }
+
+    pub fn timeout(message: impl Into<String>) -> Self {
+        HyperVError::Timeout(ErrorDetails {
+            message: message.into(),
+            hint: Some(
+                "The Hyper-V service may be unresponsive. Check 'Hyper-V Virtual Machine Management' in services.msc and retry.".to_string(),
+            ),
+            record: None,
+        })
+    }
+}
+
+impl ErrorDetails {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            hint: None,
+            record: None,
+        }
+    }
+}
+
+impl fmt::Display for HyperVError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for HyperVError {}
+
+impl From<String> for HyperVError {
+    fn from(message: String) -> Self {
+        HyperVError::internal(message)
+    }
+}
+
+impl From<&str> for HyperVError {
+    fn from(message: &str) -> Self {
+        HyperVError::internal(message)
+    }
+}
+
+impl From<std::io::Error> for HyperVError {
+    fn from(e: std::io::Error) -> Self {
+        HyperVError::internal(e.to_string())
+    }
+}
+
+/// Turn PowerShell stderr into readable text.
+/// `-EncodedCommand` may report errors as CLIXML (`#< CLIXML` followed by
+/// `<S S="Error">...</S>` records), which is decoded here.
+pub fn clean_stderr(stderr: &str) -> String {
+    let stderr = stderr.trim();
+    if !stderr.starts_with("#< CLIXML") {
+        return stderr.to_string();
+    }
+
+    let mut text = String::new();
+    let mut rest = stderr;
+    while let Some(start) = rest.find("<S S=\"Error\">") {
+        let after = &rest[start + "<S S=\"Error\">".len()..];
+        let end = after.find("</S>").unwrap_or(after.len());
+        text.push_str(&decode_clixml_string(&after[..end]));
+        rest = &after[end..];
+    }
+    text.trim().to_string()
+}
+
+fn decode_clixml_string(s: &str) -> String {
+    // XML entities first, then the `_xHHHH_` escapes CLIXML uses for control characters
+    let s = s
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while let Some(pos) = rest.find("_x") {
+        out.push_str(&rest[..pos]);
+        let candidate = &rest[pos..];
+        let decoded = candidate
+            .get(2..6)
+            .filter(|_| candidate.get(6..7) == Some("_"))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32);
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &candidate[7..];
+            }
+            None => {
+                out.push_str("_x");
+                rest = &candidate[2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Extract `CategoryInfo` / `FullyQualifiedErrorId` from a formatted ErrorRecord
+pub fn parse_error_record(stderr: &str) -> ErrorRecord {
+    let mut record = ErrorRecord {
+        raw: stderr.to_string(),
+        ..Default::default()
+    };
+
+    for line in stderr.lines() {
+        let line = line.trim().trim_start_matches('+').trim();
+        if let Some(value) = field_value(line, "CategoryInfo") {
+            record.category = value
+                .split(':')
+                .next()
+                .map(|c| c.trim().to_string())
+                .filter(|c| !c.is_empty());
+            record.category_info = Some(value);
+        } else if let Some(value) = field_value(line, "FullyQualifiedErrorId") {
+            record.fully_qualified_error_id = Some(value);
+        }
+    }
+
+    record
+}
+
+fn field_value(line: &str, field: &str) -> Option<String> {
+    let rest = line.strip_prefix(field)?;
+    let value = rest.trim_start().strip_prefix(':')?;
+    Some(value.trim().to_string())
+}
+
+/// First meaningful line of a formatted ErrorRecord (the message itself)
+fn error_message(stderr: &str) -> String {
+    let message: Vec<&str> = stderr
+        .lines()
+        .map(str::trim)
+        .take_while(|l| !is_position_line(l) && !l.starts_with('+'))
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    if message.is_empty() {
+        stderr.trim().to_string()
+    } else {
+        message.join(" ")
+    }
+}
+
+/// `At line:1 char:1` / `At C:\script.ps1:12 char:5` lines following the message
+fn is_position_line(line: &str) -> bool {
+    line.starts_with("At ") && line.contains(" char:")
+}
+
+/// Classify the stderr of a failed PowerShell invocation
+pub fn classify_powershell_error(stderr: &str) -> HyperVError {
+    let stderr = clean_stderr(stderr);
+    let record = parse_error_record(&stderr);
+    let message = error_message(&stderr);
+
+    let lower = stderr.to_lowercase();
+    let category = record.category.clone().unwrap_or_default();
+    let fqid = record
+        .fully_qualified_error_id
+        .clone()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let details = |hint: Option<&str>| ErrorDetails {
+        message: message.clone(),
+        hint: hint.map(str::to_string),
+        record: Some(Box::new(record.clone())),
+    };
+
+    if category == "PermissionDenied"
+        || fqid.contains("unauthorizedaccess")
+        || lower.contains("you do not have the required permission")
+        || lower.contains("requires elevation")
+        || lower.contains("must be run as administrator")
+        || lower.contains("access is denied")
+    {
+        HyperVError::NotElevated(details(Some(ELEVATION_HINT)))
+    } else if (fqid.contains("commandnotfoundexception")
+        && (lower.contains("-vm") || lower.contains("vmhost")))
+        || lower.contains("module 'hyper-v'")
+        || lower.contains("hyper-v module")
+    {
+        HyperVError::HyperVModuleMissing(details(Some(
+            "Enable 'Hyper-V Module for Windows PowerShell' under Windows Features (Hyper-V Management Tools).",
+        )))
+    } else if lower.contains("unable to find a virtual machine")
+        || (category == "ObjectNotFound" && lower.contains("virtual machine"))
+    {
+        HyperVError::VmNotFound(details(Some(
+            "Refresh the VM list; the virtual machine may have been renamed or deleted.",
+        )))
+    } else if lower.contains("gpu")
+        && (lower.contains("in use")
+            || lower.contains("being used")
+            || lower.contains("insufficient")
+            || lower.contains("no partitions available"))
+    {
+        HyperVError::GpuInUse(details(Some(
+            "Stop other VMs using this GPU or lower their GPU allocation, then retry.",
+        )))
+    } else if lower.contains("operation has timed out") || lower.contains("timed out") {
+        HyperVError::Timeout(details(None))
+    } else {
+        HyperVError::PowerShell(details(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "Get-VM : Hyper-V was unable to find a virtual machine with name \"Gaming\".\r\n\
+                          At line:1 char:1\r\n\
+                          + Get-VM -Name Gaming\r\n\
+                          + ~~~~~~~~~~~~~~~~~~~\r\n    \
+                          + CategoryInfo          : InvalidArgument: (Gaming:String) [Get-VM], VirtualizationException\r\n    \
+                          + FullyQualifiedErrorId : InvalidParameter,Microsoft.HyperV.PowerShell.Commands.GetVM\r\n";
+
+    #[test]
+    fn decodes_clixml_entities_and_escapes() {
+        assert_eq!(
+            decode_clixml_string("a &lt;b&gt; &quot;c&quot; &apos;d&apos; &amp;lt;_x000D__x000A_"),
+            "a <b> \"c\" 'd' &lt;\r\n"
+        );
+        // Anything that is not a full `_xHHHH_` escape is kept as it is
+        assert_eq!(
+            decode_clixml_string("my_xvm _x12_ _x00ZZ_ _x0041"),
+            "my_xvm _x12_ _x00ZZ_ _x0041"
+        );
+        assert_eq!(decode_clixml_string("_x0041__x00e9_"), "Aé");
+    }
+
+    #[test]
+    fn clean_stderr_decodes_clixml_error_records() {
+        assert_eq!(clean_stderr("  plain error\r\n"), "plain error");
+        let clixml = "#< CLIXML\r\n<Objs Version=\"1.1.0.1\" xmlns=\"http://schemas.microsoft.com/powershell/2004/04\">\
+                      <Obj S=\"progress\" RefId=\"0\"><TN RefId=\"0\"><T>System.Management.Automation.PSCustomObject</T></TN></Obj>\
+                      <S S=\"Error\">Get-VM : Access is denied._x000D__x000A_</S>\
+                      <S S=\"Error\">    + CategoryInfo : PermissionDenied: (:) [Get-VM], VirtualizationException_x000D__x000A_</S>\
+                      </Objs>";
+        assert_eq!(
+            clean_stderr(clixml),
+            "Get-VM : Access is denied.\r\n    + CategoryInfo : PermissionDenied: (:) [Get-VM], VirtualizationException"
+        );
+        assert_eq!(clean_stderr("#< CLIXML\r\n<Objs></Objs>"), "");
+    }
+
+    #[test]
+    fn parses_the_error_record() {
+        let err = classify_powershell_error(RECORD);
+        assert!(matches!(err, HyperVError::VmNotFound(_)), "{err:?}");
+        assert_eq!(
+            err.message(),
+            "Get-VM : Hyper-V was unable to find a virtual machine with name \"Gaming\"."
+        );
+        let record = err.details().record.as_deref().unwrap();
+        assert_eq!(record.category.as_deref(), Some("InvalidArgument"));
+        assert_eq!(
+            record.fully_qualified_error_id.as_deref(),
+            Some("InvalidParameter,Microsoft.HyperV.PowerShell.Commands.GetVM")
+        );
+    }
+
+    #[test]
+    fn classifies_each_error_kind() {
+        let cases = [
+            ("Get-VM : You do not have the required permission to complete this task.", "NOT_ELEVATED"),
+            ("x\n+ CategoryInfo : PermissionDenied: (:) [Start-VM], VirtualizationException", "NOT_ELEVATED"),
+            ("x\n+ FullyQualifiedErrorId : UnauthorizedAccess", "NOT_ELEVATED"),
+            ("Remove-Item : Access to the path is denied. Access is denied.", "NOT_ELEVATED"),
+            (
+                "Get-VM : The term 'Get-VM' is not recognized as the name of a cmdlet.\n\
+                 + FullyQualifiedErrorId : CommandNotFoundException",
+                "HYPER_V_MODULE_MISSING",
+            ),
+            ("Import-Module : The specified module 'Hyper-V' was not loaded.", "HYPER_V_MODULE_MISSING"),
+            ("Hyper-V was unable to find a virtual machine with name \"x\".", "VM_NOT_FOUND"),
+            (
+                "The virtual machine could not be found.\n+ CategoryInfo : ObjectNotFound: (x) [Get-VM]",
+                "VM_NOT_FOUND",
+            ),
+            ("Add-VMGpuPartitionAdapter : The GPU is in use by another VM.", "GPU_IN_USE"),
+            ("Insufficient GPU resources: no partitions available.", "GPU_IN_USE"),
+            ("Stop-VM : The operation has timed out.", "TIMEOUT"),
+            ("New-VHD : The system cannot find the path specified.", "POWER_SHELL"),
+            // A missing non-Hyper-V command is a plain PowerShell failure
+            (
+                "The term 'foo' is not recognized.\n+ FullyQualifiedErrorId : CommandNotFoundException",
+                "POWER_SHELL",
+            ),
+        ];
+        for (stderr, code) in cases {
+            let err = classify_powershell_error(stderr);
+            assert_eq!(err.code(), code, "{stderr}");
+            assert!(err.details().record.is_some());
+            assert_eq!(
+                err.details().hint.is_some(),
+                !matches!(code, "TIMEOUT" | "POWER_SHELL"),
+                "{stderr}"
+            );
+        }
+    }
+
+    #[test]
+    fn io_errors_keep_access_denied_apart() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let err = HyperVError::io("Failed to write xml", &denied);
+        assert_eq!(err.code(), "NOT_ELEVATED");
+        assert!(err.message().starts_with("Failed to write xml: "));
+        assert_eq!(err.details().hint.as_deref(), Some(ELEVATION_HINT));
+
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(
+            HyperVError::io("Failed to read", &missing).code(),
+            "INTERNAL"
+        );
+    }
+
+    #[test]
+    fn serializes_with_a_code_tag() {
+        let err = HyperVError::invalid_input("bad")
+            .with_hint("Fix it.")
+            .with_hint("Now.");
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "code": "INVALID_INPUT",
+                "message": "bad",
+                "hint": "Fix it. Now.",
+                "record": null,
+            })
+        );
+    }
+}
@@ -0,0 +1,548 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::audit::{AuditContext, AuditLog, AuditedExecutor};
+use super::error::HyperVError;
+use super::fixture::{
+    FixtureRecorder, RecordingExecutor, ReplayExecutor, RECORD_FIXTURE_ENV, REPLAY_FIXTURE_ENV,
+    REPLAY_MATCH_BODIES_ENV,
+};
+use super::host::{HostProfile, RemoteExecutor};
+use super::jobs::CallRegistry;
+use super::session::SessionPool;
+use super::utils::{kill_process_tree, powershell_result, run_powershell_with, spawn_powershell};
+
+/// Environment variable pointing to a JSON file of scripted responses.
+/// When set, the app runs against `ScriptedExecutor` instead of a real PowerShell.
+pub const SCRIPTED_EXECUTOR_ENV: &str = "HYPERV_GPU_SCRIPTED_POWERSHELL";
+
+/// Timeout applied when a call does not specify one
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Cooperative cancellation flag shared between a command and the scripts it runs
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Per-call execution options
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    /// Maximum run time; `DEFAULT_TIMEOUT` when not set
+    pub timeout: Option<Duration>,
+    pub cancel: Option<CancellationToken>,
+}
+
+impl RunOptions {
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            cancel: None,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|c| c.is_cancelled())
+    }
+
+    /// Fill unset fields from `fallback`
+    fn or(&self, fallback: &RunOptions) -> RunOptions {
+        RunOptions {
+            timeout: self.timeout.or(fallback.timeout),
+            cancel: self.cancel.clone().or_else(|| fallback.cancel.clone()),
+        }
+    }
+}
+
+/// A spawned PowerShell process whose output can be streamed
+pub trait PowerShellProcess: Send {
+    fn id(&self) -> u32;
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>>;
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>>;
+    /// Wait for the process to exit and return its exit code
+    fn wait(&mut self) -> std::io::Result<Option<i32>>;
+}
+
+/// Backend used to execute PowerShell scripts
+pub trait PowerShellExecutor: Send + Sync {
+    /// Run a script to completion and return its trimmed stdout
+    fn run(&self, script: &str) -> Result<String, HyperVError> {
+        self.run_with(script, &RunOptions::default())
+    }
+
+    /// Run a script with an explicit timeout / cancellation token
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError>;
+
+    /// Spawn a script for streaming output
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>>;
+
+    /// Kill a spawned process together with its child tree
+    fn kill(&self, pid: u32) -> Result<(), HyperVError>;
+}
+
+/// Shared executor stored in Tauri managed state
+pub struct ExecutorState {
+    executor: Arc<dyn PowerShellExecutor>,
+    audit: OnceLock<Arc<AuditLog>>,
+    recorder: Option<Arc<FixtureRecorder>>,
+    /// Remote Hyper-V host scripts run on; the local machine when `None`
+    host: RwLock<Option<HostProfile>>,
+    calls: Arc<CallRegistry>,
+}
+
+impl ExecutorState {
+    pub fn new(executor: Arc<dyn PowerShellExecutor>) -> Self {
+        Self {
+            executor,
+            audit: OnceLock::new(),
+            recorder: None,
+            host: RwLock::new(None),
+            calls: Arc::default(),
+        }
+    }
+
+    /// Record every script and its output into a replayable fixture
+    pub fn with_recorder(mut self, recorder: FixtureRecorder) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    /// Journal every script from now on (set once the app data dir is known)
+    pub fn set_audit_log(&self, log: AuditLog) {
+        let _ = self.audit.set(Arc::new(log));
+    }
+
+    pub fn audit_log(&self) -> Option<Arc<AuditLog>> {
+        self.audit.get().cloned()
+    }
+
+    /// Run Hyper-V scripts on `host` from now on (`None` or a local profile for this machine)
+    pub fn set_host(&self, host: Option<HostProfile>) {
+        *self.host.write().unwrap() = host.filter(|h| !h.is_local());
+    }
+
+    /// Active remote host, if any
+    pub fn host(&self) -> Option<HostProfile> {
+        self.host.read().unwrap().clone()
+    }
+
+    /// Calls made through `run_blocking` that are still running
+    pub fn calls(&self) -> &CallRegistry {
+        &self.calls
+    }
+
+    /// Executor for scripts run on behalf of `context`, on the active host
+    pub fn executor(&self, context: AuditContext) -> Arc<dyn PowerShellExecutor> {
+        self.executor_on(context, self.host())
+    }
+
+    /// Executor for scripts that must run on this machine whatever the active host
+    /// (elevation, credentials for local clients, copying files to a host)
+    pub fn local_executor(&self, context: AuditContext) -> Arc<dyn PowerShellExecutor> {
+        self.executor_on(context, None)
+    }
+
+    /// Executor for scripts run on `host` (`None` for this machine)
+    pub fn executor_on(
+        &self,
+        mut context: AuditContext,
+        host: Option<HostProfile>,
+    ) -> Arc<dyn PowerShellExecutor> {
+        let mut executor = self.executor.clone();
+        // Innermost, so recordings and the journal keep the unwrapped script
+        if let Some(host) = host {
+            context.host = Some(host.name.clone());
+            executor = Arc::new(RemoteExecutor::new(executor, host));
+        }
+        if let Some(recorder) = &self.recorder {
+            executor = Arc::new(RecordingExecutor::new(
+                executor,
+                recorder.clone(),
+                context.secrets().to_vec(),
+            ));
+        }
+        if let Some(log) = self.audit.get() {
+            executor = Arc::new(AuditedExecutor::new(executor, log.clone(), context));
+        }
+        executor
+    }
+
+    /// Run blocking PowerShell work off the async runtime.
+    /// Scripts inherit `timeout`; the call is listed in `calls()` while it
+    /// runs and its scripts are killed once it is cancelled there.
+    pub async fn run_blocking<T, F>(
+        &self,
+        context: AuditContext,
+        timeout: Option<Duration>,
+        f: F,
+    ) -> Result<T, HyperVError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn PowerShellExecutor) -> Result<T, HyperVError> + Send + 'static,
+    {
+        let host = self.host();
+        self.run_scoped(context, host, timeout, f).await
+    }
+
+    /// `run_blocking` on this machine whatever the active host
+    pub async fn run_blocking_local<T, F>(
+        &self,
+        context: AuditContext,
+        timeout: Option<Duration>,
+        f: F,
+    ) -> Result<T, HyperVError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn PowerShellExecutor) -> Result<T, HyperVError> + Send + 'static,
+    {
+        self.run_scoped(context, None, timeout, f).await
+    }
+
+    async fn run_scoped<T, F>(
+        &self,
+        context: AuditContext,
+        host: Option<HostProfile>,
+        timeout: Option<Duration>,
+        f: F,
+    ) -> Result<T, HyperVError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn PowerShellExecutor) -> Result<T, HyperVError> + Send + 'static,
+    {
+        let call = self.calls.register(
+            &context.kind,
+            context.vm.as_deref(),
+            host.as_ref().map(|h| h.name.as_str()),
+        );
+        let ps = ScopedExecutor::new(
+            self.executor_on(context, host),
+            RunOptions {
+                timeout,
+                cancel: Some(call.token()),
+            },
+        );
+        let result = tokio::task::spawn_blocking(move || f(&ps))
+            .await
+            .map_err(|e| HyperVError::internal(format!("Task failed: {}", e)))?;
+        drop(call);
+        result
+    }
+}
+
+impl Default for ExecutorState {
+    fn default() -> Self {
+        // Allow running the UI against canned responses (e.g. on non-Windows hosts)
+        if let Ok(path) = std::env::var(SCRIPTED_EXECUTOR_ENV) {
+            match ScriptedExecutor::from_file(&path) {
+                Ok(scripted) => return Self::new(Arc::new(scripted)),
+                Err(e) => eprintln!("Ignoring {}: {}", SCRIPTED_EXECUTOR_ENV, e),
+            }
+        }
+        // Replay a fixture recorded on another machine
+        if let Ok(path) = std::env::var(REPLAY_FIXTURE_ENV) {
+            match ReplayExecutor::from_file(&path) {
+                Ok(replay) if std::env::var(REPLAY_MATCH_BODIES_ENV).is_ok() => {
+                    return Self::new(Arc::new(replay.match_bodies()))
+                }
+                Ok(replay) => return Self::new(Arc::new(replay)),
+                Err(e) => eprintln!("Ignoring {}: {}", REPLAY_FIXTURE_ENV, e),
+            }
+        }
+
+        let state = Self::new(Arc::new(SessionPool::default()));
+        match std::env::var(RECORD_FIXTURE_ENV) {
+            Ok(path) => state.with_recorder(FixtureRecorder::new(path)),
+            Err(_) => state,
+        }
+    }
+}
+
+/// Executor that starts a fresh `powershell.exe` for every call
+pub struct SystemExecutor;
+
+impl PowerShellExecutor for SystemExecutor {
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+        run_powershell_with(script, options)
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        Ok(Box::new(spawn_powershell(script)?))
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), HyperVError> {
+        kill_process_tree(pid)
+    }
+}
+
+/// Executor that applies default run options (timeout, cancellation) to every call
+pub struct ScopedExecutor {
+    inner: Arc<dyn PowerShellExecutor>,
+    options: RunOptions,
+}
+
+impl ScopedExecutor {
+    pub fn new(inner: Arc<dyn PowerShellExecutor>, options: RunOptions) -> Self {
+        Self { inner, options }
+    }
+}
+
+impl PowerShellExecutor for ScopedExecutor {
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+        self.inner.run_with(script, &options.or(&self.options))
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        self.inner.spawn(script)
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), HyperVError> {
+        self.inner.kill(pid)
+    }
+}
+
+impl PowerShellProcess for Child {
+    fn id(&self) -> u32 {
+        Child::id(self)
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stdout
+            .take()
+            .map(|s| Box::new(s) as Box<dyn Read + Send>)
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stderr
+            .take()
+            .map(|s| Box::new(s) as Box<dyn Read + Send>)
+    }
+
+    fn wait(&mut self) -> std::io::Result<Option<i32>> {
+        Child::wait(self).map(|status| status.code())
+    }
+}
+
+/// Passes reads through while keeping a copy of the first `limit` bytes
+pub struct TeeReader {
+    inner: Box<dyn Read + Send>,
+    copy: Arc<Mutex<Vec<u8>>>,
+    limit: usize,
+}
+
+impl TeeReader {
+    pub fn new(inner: Box<dyn Read + Send>, copy: Arc<Mutex<Vec<u8>>>, limit: usize) -> Self {
+        Self { inner, copy, limit }
+    }
+}
+
+impl Read for TeeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let mut copy = self.copy.lock().unwrap();
+        let room = self.limit.saturating_sub(copy.len());
+        copy.extend_from_slice(&buf[..n.min(room)]);
+        Ok(n)
+    }
+}
+
+/// Canned response replayed by `ScriptedExecutor`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptRule {
+    /// Substring the script must contain for this rule to match
+    pub pattern: String,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+    #[serde(default)]
+    pub exit_code: i32,
+    /// Simulated run time, used to exercise timeouts and cancellation
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+impl ScriptRule {
+    pub fn ok(pattern: &str, stdout: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+            delay_ms: 0,
+        }
+    }
+
+    pub fn fail(pattern: &str, stderr: &str, exit_code: i32) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+            exit_code,
+            delay_ms: 0,
+        }
+    }
+}
+
+/// Fake executor that replays canned stdout/stderr/exit codes keyed by script pattern.
+/// Rules are matched in insertion order; every executed script is recorded.
+pub struct ScriptedExecutor {
+    rules: Vec<ScriptRule>,
+    calls: Mutex<Vec<String>>,
+    killed: Mutex<Vec<u32>>,
+    next_pid: AtomicU32,
+}
+
+impl ScriptedExecutor {
+    pub fn new(rules: Vec<ScriptRule>) -> Self {
+        Self {
+            rules,
+            calls: Mutex::new(Vec::new()),
+            killed: Mutex::new(Vec::new()),
+            next_pid: AtomicU32::new(1000),
+        }
+    }
+
+    /// Load rules from a JSON array of `ScriptRule`
+    pub fn from_file(path: &str) -> Result<Self, HyperVError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scripted responses: {}", e))?;
+        let rules: Vec<ScriptRule> = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid scripted responses: {}", e))?;
+        Ok(Self::new(rules))
+    }
+
+    pub fn with_rule(mut self, rule: ScriptRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Scripts executed so far, in order
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// PIDs passed to `kill` so far, in order
+    pub fn killed(&self) -> Vec<u32> {
+        self.killed.lock().unwrap().clone()
+    }
+
+    fn respond(&self, script: &str) -> Option<ScriptRule> {
+        self.calls.lock().unwrap().push(script.to_string());
+        self.rules
+            .iter()
+            .find(|rule| script.contains(&rule.pattern))
+            .cloned()
+    }
+}
+
+impl PowerShellExecutor for ScriptedExecutor {
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+        let rule = self
+            .respond(script)
+            .ok_or_else(|| format!("No scripted response for script: {}", script.trim()))?;
+
+        let started = Instant::now();
+        let delay = Duration::from_millis(rule.delay_ms);
+        while started.elapsed() < delay {
+            if options.is_cancelled() {
+                return Err(HyperVError::cancelled("PowerShell call was cancelled"));
+            }
+            if started.elapsed() >= options.timeout() {
+                return Err(HyperVError::timeout(format!(
+                    "PowerShell call did not finish within {}s",
+                    options.timeout().as_secs()
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        powershell_result(
+            rule.exit_code == 0,
+            rule.stdout.as_bytes(),
+            rule.stderr.as_bytes(),
+        )
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        let rule = self.respond(script).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No scripted response for script: {}", script.trim()),
+            )
+        })?;
+        Ok(Box::new(ScriptedProcess::new(
+            self.next_pid.fetch_add(1, Ordering::SeqCst),
+            rule.stdout.into_bytes(),
+            rule.stderr.into_bytes(),
+            rule.exit_code,
+        )))
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), HyperVError> {
+        self.killed.lock().unwrap().push(pid);
+        Ok(())
+    }
+}
+
+/// Process with canned output, handed out by fake executors
+pub struct ScriptedProcess {
+    pid: u32,
+    stdout: Option<Vec<u8>>,
+    stderr: Option<Vec<u8>>,
+    exit_code: i32,
+}
+
+impl ScriptedProcess {
+    pub fn new(pid: u32, stdout: Vec<u8>, stderr: Vec<u8>, exit_code: i32) -> Self {
+        Self {
+            pid,
+            stdout: Some(stdout),
+            stderr: Some(stderr),
+            exit_code,
+        }
+    }
+}
+
+impl PowerShellProcess for ScriptedProcess {
+    fn id(&self) -> u32 {
+        self.pid
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stdout
+            .take()
+            .map(|b| Box::new(Cursor::new(b)) as Box<dyn Read + Send>)
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stderr
+            .take()
+            .map(|b| Box::new(Cursor::new(b)) as Box<dyn Read + Send>)
+    }
+
+    fn wait(&mut self) -> std::io::Result<Option<i32>> {
+        Ok(Some(self.exit_code))
+    }
+}
@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::audit::redact;
+use super::error::HyperVError;
+use super::executor::{
+    PowerShellExecutor, PowerShellProcess, RunOptions, ScriptedProcess, TeeReader,
+};
+use super::utils::{now_ms, powershell_result};
+
+/// Environment variable naming a fixture file to record every script into
+pub const RECORD_FIXTURE_ENV: &str = "HYPERV_GPU_RECORD_FIXTURE";
+
+/// Environment variable naming a fixture file to replay instead of running PowerShell
+pub const REPLAY_FIXTURE_ENV: &str = "HYPERV_GPU_REPLAY_FIXTURE";
+
+/// Environment variable that, when set, lets a replayed script match a
+/// recording that differs only in its bound values
+pub const REPLAY_MATCH_BODIES_ENV: &str = "HYPERV_GPU_REPLAY_MATCH_BODIES";
+
+/// Bumped whenever the fixture layout changes incompatibly
+pub const FIXTURE_VERSION: u32 = 1;
+
+/// Recorded script output, replayable on any OS
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fixture {
+    pub version: u32,
+    /// App version that recorded the fixture
+    pub recorded_with: String,
+    /// Unix time in milliseconds
+    pub recorded_at: u64,
+    pub entries: Vec<FixtureEntry>,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self {
+            version: FIXTURE_VERSION,
+            recorded_with: env!("CARGO_PKG_VERSION").to_string(),
+            recorded_at: now_ms(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, HyperVError> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read fixture: {}", e))?;
+        let fixture: Fixture =
+            serde_json::from_str(&content).map_err(|e| format!("Invalid fixture: {}", e))?;
+        if fixture.version > FIXTURE_VERSION {
+            return Err(HyperVError::invalid_input(format!(
+                "Fixture version {} is newer than supported version {}",
+                fixture.version, FIXTURE_VERSION
+            )));
+        }
+        Ok(fixture)
+    }
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One script and what it produced, with credentials redacted
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixtureEntry {
+    /// `run` for captured calls, `spawn` for streamed ones
+    pub mode: String,
+    pub script: String,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+}
+
+/// Script with its `$Name = value` bindings removed, used to match recordings
+/// whose bound values (paths, VM names) differ from the replayed call
+fn script_body(script: &str) -> String {
+    script
+        .lines()
+        .filter(|line| {
+            let binding = line
+                .trim_start()
+                .strip_prefix('$')
+                .and_then(|rest| rest.split_once('='))
+                .map(|(name, _)| name.trim());
+            !binding.is_some_and(|name| {
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Fixture file being written by `RecordingExecutor`s
+pub struct FixtureRecorder {
+    path: PathBuf,
+    fixture: Mutex<Fixture>,
+}
+
+impl FixtureRecorder {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            fixture: Mutex::new(Fixture::new()),
+        }
+    }
+
+    /// Append an entry and rewrite the fixture file
+    fn record(&self, entry: FixtureEntry) {
+        let mut fixture = self.fixture.lock().unwrap();
+        fixture.entries.push(entry);
+        let written = serde_json::to_string_pretty(&*fixture)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&self.path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            eprintln!("Failed to write fixture {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Executor that records every script and its output into a fixture
+pub struct RecordingExecutor {
+    inner: Arc<dyn PowerShellExecutor>,
+    recorder: Arc<FixtureRecorder>,
+    secrets: Vec<String>,
+}
+
+impl RecordingExecutor {
+    pub fn new(
+        inner: Arc<dyn PowerShellExecutor>,
+        recorder: Arc<FixtureRecorder>,
+        secrets: Vec<String>,
+    ) -> Self {
+        Self {
+            inner,
+            recorder,
+            secrets,
+        }
+    }
+}
+
+impl PowerShellExecutor for RecordingExecutor {
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+        let result = self.inner.run_with(script, options);
+        let (stdout, stderr, exit_code) = match &result {
+            Ok(stdout) => (stdout.clone(), String::new(), 0),
+            // Not script output; replaying them would not be deterministic
+            Err(HyperVError::Timeout(_)) | Err(HyperVError::Cancelled(_)) => return result,
+            Err(e) => {
+                let details = e.details();
+                let stderr = details
+                    .record
+                    .as_ref()
+                    .map_or(details.message.clone(), |r| r.raw.clone());
+                (String::new(), stderr, 1)
+            }
+        };
+
+        self.recorder.record(FixtureEntry {
+            mode: "run".to_string(),
+            script: redact(script, &self.secrets),
+            stdout: redact(&stdout, &self.secrets),
+            stderr: redact(&stderr, &self.secrets),
+            exit_code: Some(exit_code),
+        });
+        result
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        let inner = self.inner.spawn(script)?;
+        Ok(Box::new(RecordingProcess {
+            inner,
+            script: script.to_string(),
+            stdout: Arc::new(Mutex::new(Vec::new())),
+            stderr: Arc::new(Mutex::new(Vec::new())),
+            recorder: self.recorder.clone(),
+            secrets: self.secrets.clone(),
+        }))
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), HyperVError> {
+        self.inner.kill(pid)
+    }
+}
+
+/// Spawned process whose full output is recorded once it exits
+struct RecordingProcess {
+    inner: Box<dyn PowerShellProcess>,
+    script: String,
+    stdout: Arc<Mutex<Vec<u8>>>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    recorder: Arc<FixtureRecorder>,
+    secrets: Vec<String>,
+}
+
+impl PowerShellProcess for RecordingProcess {
+    fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        let inner = self.inner.take_stdout()?;
+        Some(Box::new(TeeReader::new(
+            inner,
+            self.stdout.clone(),
+            usize::MAX,
+        )))
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        let inner = self.inner.take_stderr()?;
+        Some(Box::new(TeeReader::new(
+            inner,
+            self.stderr.clone(),
+            usize::MAX,
+        )))
+    }
+
+    fn wait(&mut self) -> std::io::Result<Option<i32>> {
+        let exit_code = self.inner.wait()?;
+        let stdout = String::from_utf8_lossy(&self.stdout.lock().unwrap()).to_string();
+        let stderr = String::from_utf8_lossy(&self.stderr.lock().unwrap()).to_string();
+        self.recorder.record(FixtureEntry {
+            mode: "spawn".to_string(),
+            script: redact(&self.script, &self.secrets),
+            stdout: redact(&stdout, &self.secrets),
+            stderr: redact(&stderr, &self.secrets),
+            exit_code,
+        });
+        Ok(exit_code)
+    }
+}
+
+/// Executor that serves recorded output instead of running PowerShell.
+/// A script is matched exactly (after redaction) or, with `match_bodies`, by
+/// its body without variable bindings when no exact recording exists.
+/// Repeated scripts get their recordings in order, the last one being
+/// repeated once all have been served.
+pub struct ReplayExecutor {
+    entries: Vec<FixtureEntry>,
+    served: Mutex<Vec<bool>>,
+    next_pid: AtomicU32,
+    match_bodies: bool,
+}
+
+impl ReplayExecutor {
+    pub fn new(fixture: Fixture) -> Self {
+        let served = vec![false; fixture.entries.len()];
+        Self {
+            entries: fixture.entries,
+            served: Mutex::new(served),
+            next_pid: AtomicU32::new(1000),
+            match_bodies: false,
+        }
+    }
+
+    /// Fall back to matching recordings by script body, ignoring bound values
+    pub fn match_bodies(mut self) -> Self {
+        self.match_bodies = true;
+        self
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, HyperVError> {
+        Ok(Self::new(Fixture::load(path)?))
+    }
+
+    fn find(&self, mode: &str, script: &str) -> Option<FixtureEntry> {
+        let script = redact(script, &[]);
+        let exact: Vec<usize> = self.matching(mode, |e| e.script == script);
+        let candidates = if exact.is_empty() && self.match_bodies {
+            let body = script_body(&script);
+            self.matching(mode, |e| script_body(&e.script) == body)
+        } else {
+            exact
+        };
+
+        let mut served = self.served.lock().unwrap();
+        let index = candidates
+            .iter()
+            .copied()
+            .find(|&i| !served[i])
+            .or_else(|| candidates.last().copied())?;
+        served[index] = true;
+        Some(self.entries[index].clone())
+    }
+
+    fn matching(&self, mode: &str, predicate: impl Fn(&FixtureEntry) -> bool) -> Vec<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.mode == mode && predicate(e))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl PowerShellExecutor for ReplayExecutor {
+    fn run_with(&self, script: &str, _options: &RunOptions) -> Result<String, HyperVError> {
+        let entry = self
+            .find("run", script)
+            .ok_or_else(|| format!("No recorded output for script: {}", script.trim()))?;
+        powershell_result(
+            entry.exit_code == Some(0),
+            entry.stdout.as_bytes(),
+            entry.stderr.as_bytes(),
+        )
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        let entry = self.find("spawn", script).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No recorded output for script: {}", script.trim()),
+            )
+        })?;
+        Ok(Box::new(ScriptedProcess::new(
+            self.next_pid.fetch_add(1, Ordering::SeqCst),
+            entry.stdout.into_bytes(),
+            entry.stderr.into_bytes(),
+            entry.exit_code.unwrap_or(1),
+        )))
+    }
+
+    fn kill(&self, _pid: u32) -> Result<(), HyperVError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+    use crate::commands::script::ScriptBuilder;
+    use std::time::Duration;
+
+    fn fixture_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("fixture-{}-{}.json", name, std::process::id()))
+    }
+
+    fn spawned_output(executor: &dyn PowerShellExecutor, script: &str) -> (String, Option<i32>) {
+        let mut process = executor.spawn(script).unwrap();
+        let mut stdout = String::new();
+        process
+            .take_stdout()
+            .unwrap()
+            .read_to_string(&mut stdout)
+            .unwrap();
+        (stdout, process.wait().unwrap())
+    }
+
+    #[test]
+    fn recording_replays_identically() {
+        let path = fixture_path("round-trip");
+        let login = ScriptBuilder::new()
+            .arg("Password", "hunter22")
+            .build("Connect-Host -Password $Password");
+        let scripted: Arc<dyn PowerShellExecutor> = Arc::new(ScriptedExecutor::new(vec![
+            ScriptRule::ok("Get-VM", "[{\"Name\":\"Win11\"}]"),
+            ScriptRule::fail("Stop-VM", "Stop-VM : Access is denied.", 1),
+            ScriptRule::ok("Connect-Host", "connected as hunter22"),
+            ScriptRule::ok("CopyFiles", "PROGRESS:50\nPROVISION_COMPLETE"),
+            ScriptRule {
+                delay_ms: 5_000,
+                ..ScriptRule::ok("Start-Sleep", "")
+            },
+        ]));
+        let recorder = Arc::new(FixtureRecorder::new(&path));
+        let recording =
+            RecordingExecutor::new(scripted.clone(), recorder, vec!["hunter22".to_string()]);
+
+        let listed = recording.run("Get-VM").unwrap();
+        let stopped = recording.run("Stop-VM").unwrap_err();
+        let connected = recording.run(&login).unwrap();
+        let spawned = spawned_output(&recording, "& CopyFiles.ps1");
+        // Timeouts are not script output and are left out of the fixture
+        let timeout = RunOptions::with_timeout(Duration::from_millis(20));
+        assert!(recording.run_with("Start-Sleep 5", &timeout).is_err());
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("hunter22"));
+        let replay = ReplayExecutor::from_file(path.to_str().unwrap()).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(replay.entries.len(), 4);
+
+        assert_eq!(replay.run("Get-VM").unwrap(), listed);
+        let replayed = replay.run("Stop-VM").unwrap_err();
+        assert!(matches!(replayed, HyperVError::NotElevated(_)));
+        assert_eq!(replayed.message(), stopped.message());
+        assert_eq!(replay.run(&login).unwrap(), "connected as ********");
+        assert_ne!(connected, "connected as ********");
+        assert_eq!(spawned_output(&replay, "& CopyFiles.ps1"), spawned);
+        assert!(replay.run_with("Start-Sleep 5", &timeout).is_err());
+    }
+
+    fn recorded(script: &str, stdout: &str) -> Fixture {
+        let mut fixture = Fixture::new();
+        fixture.entries.push(FixtureEntry {
+            mode: "run".to_string(),
+            script: script.to_string(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: Some(0),
+        });
+        fixture
+    }
+
+    #[test]
+    fn body_match_is_opt_in() {
+        let recorded_script = ScriptBuilder::new()
+            .arg("VMName", "Win11")
+            .build("Get-VM -Name $VMName");
+        let other_vm = ScriptBuilder::new()
+            .arg("VMName", "Dev")
+            .build("Get-VM -Name $VMName");
+
+        let strict = ReplayExecutor::new(recorded(&recorded_script, "Win11"));
+        assert_eq!(strict.run(&recorded_script).unwrap(), "Win11");
+        assert!(strict.run(&other_vm).is_err());
+
+        let lenient = ReplayExecutor::new(recorded(&recorded_script, "Win11")).match_bodies();
+        assert_eq!(lenient.run(&other_vm).unwrap(), "Win11");
+        assert!(lenient.run("Get-VM -Name 'Dev'").is_err());
+    }
+}
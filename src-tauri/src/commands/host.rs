@@ -0,0 +1,624 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State, Window};
+
+use super::audit::AuditContext;
+use super::error::HyperVError;
+use super::executor::{ExecutorState, PowerShellExecutor, PowerShellProcess, RunOptions};
+use super::script::ScriptBuilder;
+
+/// Opens (or reuses) a PSSession to the bound `$Remote*` host as `$RemoteSession`.
+/// Sessions are cached per host in the PowerShell process, so pooled hosts
+/// keep their connection across calls.
+const REMOTE_SESSION: &str = r#"
+$RemoteKey = "$RemoteHost|$RemoteUser|$RemoteUseSsl|$RemotePort"
+if (-not $global:HyperVGpuRemoteSessions) { $global:HyperVGpuRemoteSessions = @{} }
+$RemoteSession = $global:HyperVGpuRemoteSessions[$RemoteKey]
+if (-not $RemoteSession -or $RemoteSession.State -ne 'Opened') {
+    $SessionParams = @{ ComputerName = $RemoteHost; ErrorAction = 'Stop' }
+    if ($RemoteUser) {
+        if ($RemoteProtectedPassword -and -not $RemotePassword) {
+            $SecurePassword = ConvertTo-SecureString $RemoteProtectedPassword
+        } else {
+            $SecurePassword = ConvertTo-SecureString $RemotePassword -AsPlainText -Force
+        }
+        $SessionParams.Credential = New-Object System.Management.Automation.PSCredential($RemoteUser, $SecurePassword)
+    }
+    if ($RemoteUseSsl) { $SessionParams.UseSSL = $true }
+    if ($RemotePort -gt 0) { $SessionParams.Port = $RemotePort }
+    $RemoteSession = New-PSSession @SessionParams
+    $global:HyperVGpuRemoteSessions[$RemoteKey] = $RemoteSession
+}
+"#;
+
+/// Limits the directory `$Dir` to the current user, SYSTEM and
+/// Administrators, dropping inherited entries. Files created in it
+/// afterwards inherit the same access.
+const RESTRICT_DIR_ACL: &str = r#"
+$Acl = New-Object System.Security.AccessControl.DirectorySecurity
+$Acl.SetAccessRuleProtection($true, $false)
+$Inherit = [System.Security.AccessControl.InheritanceFlags]'ContainerInherit, ObjectInherit'
+$Sids = @([Security.Principal.WindowsIdentity]::GetCurrent().User.Value, 'S-1-5-18', 'S-1-5-32-544')
+foreach ($Sid in $Sids) {
+    $Identity = New-Object System.Security.Principal.SecurityIdentifier($Sid)
+    $Acl.AddAccessRule((New-Object System.Security.AccessControl.FileSystemAccessRule($Identity, 'FullControl', $Inherit, 'None', 'Allow')))
+}
+Set-Acl -LiteralPath $Dir -AclObject $Acl
+"#;
+
+/// How PowerShell remoting reaches the host
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    /// WinRM over HTTP (port 5985), encrypted by Kerberos/NTLM
+    #[default]
+    Wsman,
+    /// WinRM over HTTPS (port 5986)
+    WsmanHttps,
+}
+
+/// A Hyper-V host reachable through PowerShell remoting
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HostProfile {
+    pub name: String,
+    pub hostname: String,
+    /// Account to connect as; the current Windows identity when empty
+    pub username: Option<String>,
+    /// Password as entered in the frontend; never saved nor sent back
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// `password` encrypted with DPAPI for the current Windows user
+    /// (`ConvertFrom-SecureString`), which is what the host store keeps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protected_password: Option<String>,
+    #[serde(default)]
+    pub transport: Transport,
+    /// Non-default WinRM port
+    #[serde(default)]
+    pub port: Option<u16>,
+}
+
+impl HostProfile {
+    /// Whether the profile points back at this machine
+    pub fn is_local(&self) -> bool {
+        let host = self.hostname.trim();
+        host.is_empty()
+            || host == "."
+            || host == "127.0.0.1"
+            || host == "::1"
+            || host.eq_ignore_ascii_case("localhost")
+    }
+
+    /// Bind the connection parameters used by `REMOTE_SESSION`. A password
+    /// entered but not saved yet ends up in the script text, which reaches
+    /// PowerShell over stdin only; a saved one is decrypted there.
+    fn bind(&self, builder: ScriptBuilder) -> ScriptBuilder {
+        builder
+            .arg("RemoteHost", &self.hostname)
+            .arg("RemoteUser", self.username.as_deref().unwrap_or_default())
+            .arg(
+                "RemotePassword",
+                self.password.as_deref().unwrap_or_default(),
+            )
+            .arg(
+                "RemoteProtectedPassword",
+                self.protected_password.as_deref().unwrap_or_default(),
+            )
+            .bool("RemoteUseSsl", self.transport == Transport::WsmanHttps)
+            .int("RemotePort", self.port.map_or(0, i64::from))
+    }
+
+    /// Wrap `script` so it runs on this host through `Invoke-Command`
+    pub fn wrap(&self, script: &str) -> String {
+        self.bind(ScriptBuilder::new())
+            .arg("RemoteScript", script)
+            .build(&format!(
+                "{}Invoke-Command -Session $RemoteSession -ScriptBlock ([scriptblock]::Create($RemoteScript))",
+                REMOTE_SESSION
+            ))
+    }
+
+    /// Script copying the contents of `local_dir` into a fresh `dir_name`
+    /// under the host's temp directory, readable only by administrators and
+    /// the remote user; prints the remote directory
+    pub fn copy_script(&self, local_dir: &Path, dir_name: &str) -> String {
+        self.bind(ScriptBuilder::new())
+            .arg("LocalDir", &local_dir.to_string_lossy())
+            .arg("DirName", dir_name)
+            .build(&format!(
+                r#"{}
+$RemoteDir = Invoke-Command -Session $RemoteSession -ScriptBlock {{
+    param($DirName)
+    $Dir = Join-Path ([IO.Path]::GetTempPath()) $DirName
+    if (Test-Path -LiteralPath $Dir) {{ Remove-Item -LiteralPath $Dir -Recurse -Force }}
+    New-Item -ItemType Directory -Path $Dir -Force | Out-Null
+    {}
+    $Dir
+}} -ArgumentList $DirName
+Copy-Item -Path (Join-Path $LocalDir '*') -Destination $RemoteDir -ToSession $RemoteSession -Recurse -Force -ErrorAction Stop
+$RemoteDir
+"#,
+                REMOTE_SESSION, RESTRICT_DIR_ACL
+            ))
+    }
+
+    /// Copy of the profile safe to hand to the frontend
+    fn without_password(&self) -> Self {
+        Self {
+            password: None,
+            protected_password: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// Executor that runs every script on a remote host.
+/// Scripts still start in a local PowerShell process, which forwards them
+/// through a cached PSSession; killing it stops the remote pipeline too.
+pub struct RemoteExecutor {
+    inner: Arc<dyn PowerShellExecutor>,
+    profile: HostProfile,
+}
+
+impl RemoteExecutor {
+    pub fn new(inner: Arc<dyn PowerShellExecutor>, profile: HostProfile) -> Self {
+        Self { inner, profile }
+    }
+}
+
+impl PowerShellExecutor for RemoteExecutor {
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+        self.inner.run_with(&self.profile.wrap(script), options)
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        self.inner.spawn(&self.profile.wrap(script))
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), HyperVError> {
+        self.inner.kill(pid)
+    }
+}
+
+/// Copy a local directory to the host's temp directory and return the remote path.
+/// `ps` must be a local executor: the copy is driven from this machine.
+pub fn stage_on_host(
+    ps: &dyn PowerShellExecutor,
+    profile: &HostProfile,
+    local_dir: &Path,
+    dir_name: &str,
+) -> Result<String, HyperVError> {
+    let output = ps.run_with(
+        &profile.copy_script(local_dir, dir_name),
+        &RunOptions::with_timeout(std::time::Duration::from_secs(600)),
+    )?;
+    output
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| HyperVError::internal("Remote staging directory was not reported"))
+}
+
+/// `password` encrypted with DPAPI for the current Windows user. Only that
+/// user on this machine can decrypt it, in `REMOTE_SESSION`.
+pub fn protect_password(
+    ps: &dyn PowerShellExecutor,
+    password: &str,
+) -> Result<String, HyperVError> {
+    let script = ScriptBuilder::new()
+        .arg("Password", password)
+        .build("ConvertTo-SecureString $Password -AsPlainText -Force | ConvertFrom-SecureString");
+    let protected = ps.run(&script)?.trim().to_string();
+    if protected.is_empty() {
+        return Err(HyperVError::internal(
+            "The host password could not be encrypted",
+        ));
+    }
+    Ok(protected)
+}
+
+/// Whether `path` exists on the host `ps` runs on
+pub fn path_exists(ps: &dyn PowerShellExecutor, path: &str) -> bool {
+    let script = ScriptBuilder::new()
+        .arg("Path", path)
+        .build("Test-Path -LiteralPath $Path");
+    ps.run(&script)
+        .is_ok_and(|output| output.trim().eq_ignore_ascii_case("true"))
+}
+
+/// Restrict `dir` on the host `ps` runs on to the current user, SYSTEM and
+/// Administrators
+pub fn restrict_dir_acl(ps: &dyn PowerShellExecutor, dir: &str) -> Result<(), HyperVError> {
+    let script = ScriptBuilder::new().arg("Dir", dir).build(RESTRICT_DIR_ACL);
+    ps.run(&script).map(|_| ())
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct HostFile {
+    #[serde(default)]
+    hosts: Vec<HostProfile>,
+    /// Profile commands run against; the local machine when `None`
+    #[serde(default)]
+    active: Option<String>,
+}
+
+/// Saved host profiles, persisted next to the VM connection settings
+pub struct HostStore {
+    file_path: PathBuf,
+    data: Mutex<HostFile>,
+}
+
+impl HostStore {
+    pub fn new(app: &AppHandle) -> Self {
+        let mut file_path = app
+            .path()
+            .app_data_dir()
+            .unwrap_or_else(|_| PathBuf::from("."));
+        if !file_path.exists() {
+            let _ = fs::create_dir_all(&file_path);
+        }
+        file_path.push("hosts.json");
+        Self::at(file_path)
+    }
+
+    /// Profiles persisted in `file_path`
+    pub fn at(file_path: impl Into<PathBuf>) -> Self {
+        let file_path = file_path.into();
+        let data = fs::read_to_string(&file_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            file_path,
+            data: Mutex::new(data),
+        }
+    }
+
+    pub fn list(&self) -> Vec<HostProfile> {
+        self.data.lock().unwrap().hosts.clone()
+    }
+
+    pub fn get(&self, name: &str) -> Option<HostProfile> {
+        self.data
+            .lock()
+            .unwrap()
+            .hosts
+            .iter()
+            .find(|h| h.name == name)
+            .cloned()
+    }
+
+    /// The active profile, if one is selected and still exists
+    pub fn active(&self) -> Option<HostProfile> {
+        let active = self.data.lock().unwrap().active.clone()?;
+        self.get(&active)
+    }
+
+    /// Add or replace a profile. A missing password keeps the stored one.
+    pub fn save(&self, mut profile: HostProfile) -> Result<(), String> {
+        let mut data = self.data.lock().unwrap();
+        match data.hosts.iter_mut().find(|h| h.name == profile.name) {
+            Some(existing) => {
+                if profile.protected_password.is_none() {
+                    profile.protected_password = existing.protected_password.take();
+                }
+                *existing = profile;
+            }
+            None => data.hosts.push(profile),
+        }
+        self.persist(&data)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), String> {
+        let mut data = self.data.lock().unwrap();
+        data.hosts.retain(|h| h.name != name);
+        if data.active.as_deref() == Some(name) {
+            data.active = None;
+        }
+        self.persist(&data)
+    }
+
+    pub fn set_active(&self, name: Option<String>) -> Result<(), String> {
+        let mut data = self.data.lock().unwrap();
+        data.active = name;
+        self.persist(&data)
+    }
+
+    /// Encrypt passwords saved in plaintext by earlier versions. Until this
+    /// succeeds they are only kept in memory.
+    pub fn protect_saved_passwords(&self, ps: &dyn PowerShellExecutor) -> Result<(), HyperVError> {
+        let mut data = self.data.lock().unwrap();
+        let mut result = Ok(());
+        let mut changed = false;
+        for host in &mut data.hosts {
+            let Some(password) = host.password.as_deref() else {
+                continue;
+            };
+            match protect_password(ps, password) {
+                Ok(protected) => {
+                    host.protected_password = Some(protected);
+                    host.password = None;
+                    changed = true;
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if changed {
+            self.persist(&data)?;
+        }
+        result
+    }
+
+    fn persist(&self, data: &HostFile) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+        fs::write(&self.file_path, content).map_err(|e| e.to_string())
+    }
+}
+
+fn validate_profile(profile: &HostProfile) -> Result<(), HyperVError> {
+    if profile.name.trim().is_empty() {
+        return Err(HyperVError::invalid_input("Host name cannot be empty"));
+    }
+    if profile.hostname.trim().is_empty() {
+        return Err(HyperVError::invalid_input("Hostname cannot be empty"));
+    }
+    Ok(())
+}
+
+/// Saved host profiles, without passwords
+#[tauri::command]
+pub async fn list_hosts(window: Window) -> Result<Vec<HostProfile>, HyperVError> {
+    let store = HostStore::new(window.app_handle());
+    Ok(store
+        .list()
+        .iter()
+        .map(HostProfile::without_password)
+        .collect())
+}
+
+#[tauri::command]
+pub async fn save_host(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    mut profile: HostProfile,
+) -> Result<(), HyperVError> {
+    validate_profile(&profile)?;
+    // Only the password the user typed is encrypted and kept
+    profile.protected_password = None;
+    if let Some(password) = profile.password.take().filter(|p| !p.is_empty()) {
+        let context = AuditContext::new("save_host").secret(Some(&password));
+        profile.protected_password = Some(
+            ps.run_blocking_local(context, None, move |ps| protect_password(ps, &password))
+                .await?,
+        );
+    }
+    let store = HostStore::new(window.app_handle());
+    store.save(profile)?;
+    // Editing the active host takes effect immediately
+    if let Some(active) = store.active() {
+        ps.set_host(Some(active));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_host(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    name: String,
+) -> Result<(), HyperVError> {
+    let store = HostStore::new(window.app_handle());
+    store.remove(&name)?;
+    ps.set_host(store.active());
+    Ok(())
+}
+
+/// Name of the host commands run against; `None` for the local machine
+#[tauri::command]
+pub async fn get_active_host(ps: State<'_, ExecutorState>) -> Result<Option<String>, HyperVError> {
+    Ok(ps.host().map(|h| h.name))
+}
+
+/// Switch every subsequent command to the named host, or back to the local machine
+#[tauri::command]
+pub async fn set_active_host(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    name: Option<String>,
+) -> Result<(), HyperVError> {
+    let store = HostStore::new(window.app_handle());
+    let profile = match &name {
+        Some(name) => Some(store.get(name).ok_or_else(|| {
+            HyperVError::invalid_input(format!("Unknown host profile: {}", name))
+        })?),
+        None => None,
+    };
+    store.set_active(name)?;
+    ps.set_host(profile);
+    Ok(())
+}
+
+/// Connect to a host with the given profile and return its computer name
+#[tauri::command]
+pub async fn test_host_connection(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    mut profile: HostProfile,
+) -> Result<String, HyperVError> {
+    validate_profile(&profile)?;
+    if profile.password.is_none() {
+        let store = HostStore::new(window.app_handle());
+        profile.protected_password = store.get(&profile.name).and_then(|h| h.protected_password);
+    }
+    let context = AuditContext::new("test_host_connection").secret(profile.password.as_deref());
+    let local = ps.local_executor(context);
+    tokio::task::spawn_blocking(move || {
+        RemoteExecutor::new(local, profile).run("$env:COMPUTERNAME")
+    })
+    .await
+    .map_err(HyperVError::task_failed)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+    use std::io::Read;
+
+    fn profile() -> HostProfile {
+        HostProfile {
+            name: "lab".to_string(),
+            hostname: "hv01.lab".to_string(),
+            username: Some("LAB\\admin".to_string()),
+            password: Some("it's secret".to_string()),
+            protected_password: None,
+            transport: Transport::WsmanHttps,
+            port: Some(5986),
+        }
+    }
+
+    fn assert_bound_to_profile(script: &str) {
+        for binding in [
+            "$RemoteHost = 'hv01.lab'",
+            "$RemoteUser = 'LAB\\admin'",
+            "$RemotePassword = 'it''s secret'",
+            "$RemoteUseSsl = $true",
+            "$RemotePort = 5986",
+        ] {
+            assert!(script.contains(binding), "{} not in:\n{}", binding, script);
+        }
+        assert!(script.contains("ComputerName = $RemoteHost"));
+        assert!(script.contains("$SessionParams.Credential = New-Object"));
+    }
+
+    #[test]
+    fn remote_executor_runs_through_session() {
+        let scripted = Arc::new(ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Invoke-Command -Session $RemoteSession",
+            "Win11",
+        )]));
+        let remote = RemoteExecutor::new(scripted.clone(), profile());
+
+        assert_eq!(
+            remote
+                .run("Get-VM | Select-Object -ExpandProperty Name")
+                .unwrap(),
+            "Win11"
+        );
+        let mut process = remote.spawn("& 'C:\\staging\\CopyFilesToVM.ps1'").unwrap();
+        let mut stdout = String::new();
+        process
+            .take_stdout()
+            .unwrap()
+            .read_to_string(&mut stdout)
+            .unwrap();
+        assert_eq!(stdout, "Win11");
+
+        let calls = scripted.calls();
+        assert_eq!(calls.len(), 2);
+        for call in &calls {
+            assert_bound_to_profile(call);
+            assert!(call.ends_with(
+                "Invoke-Command -Session $RemoteSession -ScriptBlock ([scriptblock]::Create($RemoteScript))"
+            ));
+        }
+        assert!(calls[0].contains("$RemoteScript = 'Get-VM | Select-Object -ExpandProperty Name'"));
+        assert!(calls[1].contains("$RemoteScript = '& ''C:\\staging\\CopyFilesToVM.ps1'''"));
+    }
+
+    #[test]
+    fn stage_on_host_copies_and_reports_remote_dir() {
+        let scripted = ScriptedExecutor::new(vec![ScriptRule::ok(
+            "-ToSession $RemoteSession",
+            "WARNING: slow link\nC:\\Temp\\HyperV_GPU_Update\\Win11\n",
+        )]);
+
+        let remote_dir = stage_on_host(
+            &scripted,
+            &profile(),
+            Path::new("/opt/app/easy-gpu-pv"),
+            "HyperV_GPU_Update\\Win11",
+        )
+        .unwrap();
+        assert_eq!(remote_dir, "C:\\Temp\\HyperV_GPU_Update\\Win11");
+
+        let calls = scripted.calls();
+        assert_eq!(calls.len(), 1);
+        assert_bound_to_profile(&calls[0]);
+        assert!(calls[0].contains("$LocalDir = '/opt/app/easy-gpu-pv'"));
+        assert!(calls[0].contains("$DirName = 'HyperV_GPU_Update\\Win11'"));
+        assert!(calls[0].contains("Set-Acl -LiteralPath $Dir"));
+    }
+
+    #[test]
+    fn passwords_are_stored_encrypted_only() {
+        let path = std::env::temp_dir().join(format!("hosts-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"hosts":[{"name":"lab","hostname":"hv01.lab","username":"admin","password":"plain"}],"active":"lab"}"#,
+        )
+        .unwrap();
+        let scripted = ScriptedExecutor::new(vec![ScriptRule::ok(
+            "ConvertFrom-SecureString",
+            "01000000d08c9ddf\r\n",
+        )]);
+
+        // Plaintext from earlier versions is encrypted on load
+        let store = HostStore::at(&path);
+        store.protect_saved_passwords(&scripted).unwrap();
+        assert!(scripted.calls()[0].contains("$Password = 'plain'"));
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains("plain"), "{}", saved);
+        assert!(saved.contains("01000000d08c9ddf"));
+
+        // A new password replaces the stored one, a missing one keeps it
+        let mut edited = profile();
+        edited.password = None;
+        edited.protected_password = None;
+        store.save(edited.clone()).unwrap();
+        let active = HostStore::at(&path).active().unwrap();
+        assert_eq!(
+            active.protected_password.as_deref(),
+            Some("01000000d08c9ddf")
+        );
+        assert_eq!(active.password, None);
+        store
+            .save(HostProfile {
+                protected_password: Some("01000000ffff".to_string()),
+                ..edited
+            })
+            .unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("01000000ffff"));
+
+        let listed = serde_json::to_string(&active.without_password()).unwrap();
+        assert!(!listed.contains("password"), "{}", listed);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn saved_passwords_are_decrypted_in_the_session() {
+        let scripted = Arc::new(ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Invoke-Command",
+            "HV01",
+        )]));
+        let saved = HostProfile {
+            password: None,
+            protected_password: Some("01000000d08c9ddf".to_string()),
+            ..profile()
+        };
+        RemoteExecutor::new(scripted.clone(), saved)
+            .run("$env:COMPUTERNAME")
+            .unwrap();
+        let script = &scripted.calls()[0];
+        assert!(script.contains("$RemotePassword = ''"));
+        assert!(script.contains("$RemoteProtectedPassword = '01000000d08c9ddf'"));
+        assert!(script.contains("ConvertTo-SecureString $RemoteProtectedPassword\n"));
+    }
+}
@@ -0,0 +1,972 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+use tauri::State;
+
+use super::audit::AuditContext;
+use super::error::HyperVError;
+use super::executor::{ExecutorState, PowerShellExecutor};
+use super::script::ScriptBuilder;
+use super::utils::parse_json_list;
+use super::wim::{read_wim, WimImage};
+
+/// Mounting an ISO and reading every image of an ESD can take a while
+const EDITIONS_TIMEOUT: Duration = Duration::from_secs(120);
+/// Logical sector size of ISO9660, and of UDF on optical media
+const SECTOR: u64 = 2048;
+/// Largest directory the inspector reads, far above any installation media
+const MAX_DIR_BYTES: u64 = 16 * 1024 * 1024;
+/// Largest text file the inspector reads
+const MAX_TEXT_BYTES: u64 = 64 * 1024;
+/// Install images, in the order Windows Setup looks for them
+const INSTALL_IMAGES: [&str; 2] = ["sources/install.wim", "sources/install.esd"];
+
+/// One Windows image in the install.wim/install.esd of an ISO
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IsoEdition {
+    /// Image index, the value passed to the image conversion as `Edition`
+    pub index: u32,
+    pub name: String,
+    /// x86, x64, arm or arm64
+    pub architecture: String,
+    /// Windows build number, e.g. 22631
+    pub build: u32,
+    /// e.g. Professional
+    #[serde(default)]
+    pub edition_id: Option<String>,
+    /// Default language, e.g. en-US
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Size of the applied image
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+}
+
+impl From<&WimImage> for IsoEdition {
+    fn from(image: &WimImage) -> Self {
+        IsoEdition {
+            index: image.index,
+            name: image.name.clone(),
+            architecture: image.architecture.clone().unwrap_or_default(),
+            build: image.build.unwrap_or_default(),
+            edition_id: image.edition_id.clone(),
+            language: image.language.clone(),
+            size_bytes: image.total_bytes,
+        }
+    }
+}
+
+/// List the images in the install media of the ISO at `iso_path`.
+/// The ISO is dismounted again unless it was already attached.
+pub fn list_iso_editions_sync(
+    ps: &dyn PowerShellExecutor,
+    iso_path: &str,
+) -> Result<Vec<IsoEdition>, HyperVError> {
+    let script = ScriptBuilder::new().arg("IsoPath", iso_path).build(
+        r#"
+        if (-not (Test-Path -LiteralPath $IsoPath)) { throw "ISO not found: $IsoPath" }
+        $wasAttached = (Get-DiskImage -ImagePath $IsoPath).Attached
+        if (-not $wasAttached) { Mount-DiskImage -ImagePath $IsoPath -StorageType ISO | Out-Null }
+        try {
+            $letter = (Get-DiskImage -ImagePath $IsoPath | Get-Volume).DriveLetter
+            $image = @("wim", "esd") | ForEach-Object { "$($letter):\sources\install.$_" } |
+                Where-Object { Test-Path $_ } | Select-Object -First 1
+            if (-not $image) { throw "No sources\install.wim or sources\install.esd on $IsoPath" }
+            $arch = @{ 0 = 'x86'; 5 = 'arm'; 9 = 'x64'; 12 = 'arm64' }
+            @(Get-WindowsImage -ImagePath $image | ForEach-Object {
+                $info = Get-WindowsImage -ImagePath $image -Index $_.ImageIndex
+                [PSCustomObject]@{
+                    index = [int]$_.ImageIndex
+                    name = $_.ImageName
+                    architecture = if ($arch.ContainsKey([int]$info.Architecture)) { $arch[[int]$info.Architecture] } else { "$($info.Architecture)" }
+                    build = [int]$info.Build
+                    edition_id = $info.EditionId
+                    language = @($info.Languages)[0]
+                    size_bytes = [uint64]$info.ImageSize
+                }
+            }) | ConvertTo-Json -Compress
+        } finally {
+            if (-not $wasAttached) { Dismount-DiskImage -ImagePath $IsoPath | Out-Null }
+        }
+        "#,
+    );
+    let output = ps.run(&script)?;
+    parse_json_list(&output)
+        .map_err(|e| HyperVError::internal(format!("Failed to read ISO editions: {}", e)))
+}
+
+/// Windows editions available on the ISO at `iso_path`, on the active host.
+/// ISOs on this machine are read in place; remote ones, and install images
+/// the parser cannot read, are mounted and listed with DISM.
+#[tauri::command]
+pub async fn list_iso_editions(
+    ps: State<'_, ExecutorState>,
+    iso_path: String,
+) -> Result<Vec<IsoEdition>, HyperVError> {
+    if ps.host().is_none() {
+        let path = iso_path.clone();
+        let info = tokio::task::spawn_blocking(move || inspect_iso_file(Path::new(&path), false))
+            .await
+            .map_err(HyperVError::task_failed)?;
+        if let Ok(info) = info {
+            if !info.editions.is_empty() {
+                return Ok(info.editions);
+            }
+        }
+    }
+    ps.run_blocking(
+        AuditContext::new("list_iso_editions"),
+        Some(EDITIONS_TIMEOUT),
+        move |ps| list_iso_editions_sync(ps, &iso_path),
+    )
+    .await
+}
+
+/// What the ISO inspector found on an image, read without mounting it
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct IsoInfo {
+    pub volume_label: String,
+    /// "udf" or "iso9660", the directory the files were looked up in
+    pub filesystem: String,
+    /// `sources/install.wim` or `sources/install.esd`, if the image has one
+    pub install_image: Option<String>,
+    pub install_image_bytes: Option<u64>,
+    /// Windows build number, e.g. 22631
+    pub build: Option<u32>,
+    /// x86, x64 or arm64
+    pub architecture: Option<String>,
+    /// Images of the install image; empty when its metadata cannot be read
+    pub editions: Vec<IsoEdition>,
+    /// Hex SHA-256 of the whole ISO, when requested
+    pub sha256: Option<String>,
+}
+
+impl IsoInfo {
+    /// Whether the image is Windows installation media
+    pub fn is_windows_installer(&self) -> bool {
+        self.install_image.is_some()
+    }
+}
+
+/// Architecture name used by `IsoEdition` for a Windows or PowerShell
+/// spelling of it, e.g. `amd64` or `AMD64`
+pub fn normalize_architecture(name: &str) -> Option<&'static str> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "amd64" | "x64" => Some("x64"),
+        "x86" | "i386" => Some("x86"),
+        "arm64" | "a64" => Some("arm64"),
+        "arm" => Some("arm"),
+        _ => None,
+    }
+}
+
+/// A file or directory on an ISO, as the byte ranges holding its data
+#[derive(Debug, Clone, Default)]
+pub struct IsoFile {
+    /// (offset in the image, length) of each recorded extent
+    ranges: Vec<(u64, u64)>,
+    /// Data stored in the UDF file entry itself
+    embedded: Option<Vec<u8>>,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Where a directory entry's data is described
+enum Locator {
+    /// ISO9660 records carry the extent directly
+    Extent { lba: u32, size: u32, is_dir: bool },
+    /// UDF entries point at a file entry in the partition
+    Icb { lbn: u32 },
+}
+
+#[derive(Clone, Copy)]
+enum Filesystem {
+    Iso9660 {
+        root_lba: u32,
+        root_size: u32,
+    },
+    Udf {
+        partition_start: u64,
+        block_size: u64,
+        root_lbn: u32,
+    },
+}
+
+/// Read-only view of the directory tree of an ISO9660 or UDF image
+pub struct IsoImage<R> {
+    reader: R,
+    fs: Filesystem,
+    /// ISO9660 directory of a hybrid image, tried when UDF lacks a file
+    fallback: Option<Filesystem>,
+    pub volume_label: String,
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> io::Result<u16> {
+    bytes
+        .get(at..at + 2)
+        .and_then(|b| b.try_into().ok())
+        .map(u16::from_le_bytes)
+        .ok_or_else(|| invalid("Truncated descriptor"))
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> io::Result<u32> {
+    bytes
+        .get(at..at + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or_else(|| invalid("Truncated descriptor"))
+}
+
+fn le_u64(bytes: &[u8], at: usize) -> io::Result<u64> {
+    bytes
+        .get(at..at + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| invalid("Truncated descriptor"))
+}
+
+/// OSTA CS0 string: a compression id of 8 (one byte per character) or
+/// 16 (UTF-16BE) followed by the characters
+fn udf_string(bytes: &[u8]) -> String {
+    match bytes.split_first() {
+        Some((16, chars)) => {
+            let units: Vec<u16> = chars
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        Some((_, chars)) => chars.iter().map(|&c| c as char).collect(),
+        None => String::new(),
+    }
+}
+
+/// ISO9660 file identifier without its `;1` version and trailing dot
+fn iso9660_name(bytes: &[u8]) -> String {
+    let name: String = bytes.iter().map(|&c| c as char).collect();
+    let name = name.split(';').next().unwrap_or_default();
+    name.strip_suffix('.').unwrap_or(name).to_string()
+}
+
+impl<R: Read + Seek> IsoImage<R> {
+    /// Recognise the filesystems on `reader`, preferring UDF: Windows
+    /// media only list a README in their ISO9660 directory.
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let mut iso9660 = None;
+        let mut volume_label = String::new();
+        let mut udf = false;
+        // Volume descriptors and the UDF recognition sequence follow each other
+        for sector in 16..64 {
+            let descriptor = match read_at(&mut reader, sector * SECTOR, SECTOR as usize) {
+                Ok(d) => d,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            match &descriptor[1..6] {
+                b"CD001" if descriptor[0] == 1 => {
+                    volume_label = iso9660_name(&descriptor[40..72]).trim().to_string();
+                    iso9660 = Some(Filesystem::Iso9660 {
+                        root_lba: le_u32(&descriptor, 156 + 2)?,
+                        root_size: le_u32(&descriptor, 156 + 10)?,
+                    });
+                }
+                b"CD001" | b"BEA01" => {}
+                b"NSR02" | b"NSR03" => udf = true,
+                b"TEA01" => break,
+                _ if iso9660.is_some() || udf => break,
+                _ => {}
+            }
+        }
+
+        let udf = if udf {
+            match open_udf(&mut reader) {
+                Ok((fs, label)) => {
+                    if !label.is_empty() {
+                        volume_label = label;
+                    }
+                    Some(fs)
+                }
+                Err(e) if iso9660.is_none() => return Err(e),
+                Err(_) => None,
+            }
+        } else {
+            None
+        };
+        let (fs, fallback) = match (udf, iso9660) {
+            (Some(udf), iso9660) => (udf, iso9660),
+            (None, Some(iso9660)) => (iso9660, None),
+            (None, None) => return Err(invalid("Not an ISO9660 or UDF image")),
+        };
+        Ok(IsoImage {
+            reader,
+            fs,
+            fallback,
+            volume_label,
+        })
+    }
+
+    /// "udf" or "iso9660"
+    pub fn filesystem(&self) -> &'static str {
+        match self.fs {
+            Filesystem::Iso9660 { .. } => "iso9660",
+            Filesystem::Udf { .. } => "udf",
+        }
+    }
+
+    /// The file at `path`, '/'-separated and matched case-insensitively
+    pub fn find(&mut self, path: &str) -> io::Result<Option<IsoFile>> {
+        if let Some(file) = self.find_in(self.fs, path)? {
+            return Ok(Some(file));
+        }
+        match self.fallback {
+            Some(fs) => self.find_in(fs, path),
+            None => Ok(None),
+        }
+    }
+
+    fn find_in(&mut self, fs: Filesystem, path: &str) -> io::Result<Option<IsoFile>> {
+        let mut current = match fs {
+            Filesystem::Iso9660 {
+                root_lba,
+                root_size,
+            } => self.resolve(
+                fs,
+                Locator::Extent {
+                    lba: root_lba,
+                    size: root_size,
+                    is_dir: true,
+                },
+            )?,
+            Filesystem::Udf { root_lbn, .. } => self.resolve(fs, Locator::Icb { lbn: root_lbn })?,
+        };
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            if !current.is_dir {
+                return Ok(None);
+            }
+            let entry = self
+                .children(fs, &current)?
+                .into_iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(component));
+            match entry {
+                Some((_, locator)) => current = self.resolve(fs, locator)?,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+
+    /// Up to `len` bytes of `file` starting at `offset`
+    pub fn read(&mut self, file: &IsoFile, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let end = file.size.min(offset.saturating_add(len));
+        if let Some(data) = &file.embedded {
+            let start = (offset as usize).min(data.len());
+            return Ok(data[start..(end as usize).min(data.len())].to_vec());
+        }
+        let mut out = Vec::new();
+        let mut position = 0u64;
+        for &(start, length) in &file.ranges {
+            let (from, to) = (position.max(offset), (position + length).min(end));
+            if from < to {
+                out.extend(read_at(
+                    &mut self.reader,
+                    start + from - position,
+                    (to - from) as usize,
+                )?);
+            }
+            position += length;
+            if position >= end {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    fn children(&mut self, fs: Filesystem, dir: &IsoFile) -> io::Result<Vec<(String, Locator)>> {
+        let data = self.read(dir, 0, MAX_DIR_BYTES)?;
+        let mut entries = Vec::new();
+        let mut pos = 0usize;
+        match fs {
+            Filesystem::Iso9660 { .. } => {
+                while pos < data.len() {
+                    let length = data[pos] as usize;
+                    if length == 0 {
+                        // Records do not cross sectors; the rest is padding
+                        pos = (pos / SECTOR as usize + 1) * SECTOR as usize;
+                        continue;
+                    }
+                    let record = data
+                        .get(pos..pos + length)
+                        .ok_or_else(|| invalid("Truncated directory record"))?;
+                    let name_len = *record
+                        .get(32)
+                        .ok_or_else(|| invalid("Truncated directory record"))?
+                        as usize;
+                    let name = record
+                        .get(33..33 + name_len)
+                        .ok_or_else(|| invalid("Truncated directory record"))?;
+                    // 0 and 1 are the directory itself and its parent
+                    if name != [0] && name != [1] {
+                        entries.push((
+                            iso9660_name(name),
+                            Locator::Extent {
+                                lba: le_u32(record, 2)?,
+                                size: le_u32(record, 10)?,
+                                is_dir: record[25] & 0x02 != 0,
+                            },
+                        ));
+                    }
+                    pos += length;
+                }
+            }
+            Filesystem::Udf { .. } => {
+                // File identifier descriptors
+                while pos + 38 <= data.len() && le_u16(&data, pos)? == 257 {
+                    let characteristics = data[pos + 18];
+                    let name_len = data[pos + 19] as usize;
+                    let lbn = le_u32(&data, pos + 24)?;
+                    let impl_len = le_u16(&data, pos + 36)? as usize;
+                    let name_at = pos + 38 + impl_len;
+                    let name = data
+                        .get(name_at..name_at + name_len)
+                        .ok_or_else(|| invalid("Truncated file identifier"))?;
+                    // Skip deleted entries and the parent directory
+                    if characteristics & 0x0C == 0 {
+                        entries.push((udf_string(name), Locator::Icb { lbn }));
+                    }
+                    pos += (38 + impl_len + name_len + 3) & !3;
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn resolve(&mut self, fs: Filesystem, locator: Locator) -> io::Result<IsoFile> {
+        match (fs, locator) {
+            (_, Locator::Extent { lba, size, is_dir }) => Ok(IsoFile {
+                ranges: vec![(lba as u64 * SECTOR, size as u64)],
+                embedded: None,
+                size: size as u64,
+                is_dir,
+            }),
+            (
+                Filesystem::Udf {
+                    partition_start,
+                    block_size,
+                    ..
+                },
+                Locator::Icb { lbn },
+            ) => {
+                let block = |lbn: u32| (partition_start + lbn as u64) * block_size;
+                let entry = read_at(&mut self.reader, block(lbn), block_size as usize)?;
+                let (ea_len, ad_len, base) = match le_u16(&entry, 0)? {
+                    261 => (le_u32(&entry, 168)?, le_u32(&entry, 172)?, 176),
+                    266 => (le_u32(&entry, 208)?, le_u32(&entry, 212)?, 216),
+                    tag => {
+                        return Err(invalid(format!(
+                            "Expected a UDF file entry, found tag {}",
+                            tag
+                        )))
+                    }
+                };
+                let size = le_u64(&entry, 56)?;
+                let is_dir = entry[27] == 4;
+                let start = base + ea_len as usize;
+                let descriptors = entry
+                    .get(start..start + ad_len as usize)
+                    .ok_or_else(|| invalid("Truncated UDF file entry"))?;
+                let mut file = IsoFile {
+                    size,
+                    is_dir,
+                    ..Default::default()
+                };
+                // Bits 0-2 of the ICB flags give the allocation descriptor type
+                let stride = match le_u16(&entry, 34)? & 7 {
+                    0 => 8,
+                    1 => 16,
+                    3 => {
+                        file.embedded = Some(descriptors.to_vec());
+                        return Ok(file);
+                    }
+                    other => {
+                        return Err(invalid(format!(
+                            "Unsupported UDF allocation descriptor type {}",
+                            other
+                        )))
+                    }
+                };
+                for descriptor in descriptors.chunks_exact(stride) {
+                    let raw = le_u32(descriptor, 0)?;
+                    let (length, kind) = ((raw & 0x3FFF_FFFF) as u64, raw >> 30);
+                    // Kind 3 continues the descriptors elsewhere, which
+                    // installation media never need
+                    if length == 0 || kind == 3 {
+                        break;
+                    }
+                    if kind == 0 {
+                        file.ranges.push((block(le_u32(descriptor, 4)?), length));
+                    }
+                }
+                Ok(file)
+            }
+            (Filesystem::Iso9660 { .. }, Locator::Icb { .. }) => {
+                Err(invalid("UDF entry in an ISO9660 directory"))
+            }
+        }
+    }
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buffer = vec![0; len];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Follow the anchor, partition and logical volume descriptors of a UDF
+/// volume to its root directory
+fn open_udf<R: Read + Seek>(reader: &mut R) -> io::Result<(Filesystem, String)> {
+    let anchor = read_at(reader, 256 * SECTOR, SECTOR as usize)?;
+    if le_u16(&anchor, 0)? != 2 {
+        return Err(invalid("No UDF anchor volume descriptor at sector 256"));
+    }
+    let sequence_len = le_u32(&anchor, 16)? as u64;
+    let sequence_at = le_u32(&anchor, 20)? as u64;
+
+    let mut partitions = Vec::new();
+    let mut volume = None;
+    for sector in 0..(sequence_len / SECTOR).min(64) {
+        let descriptor = read_at(reader, (sequence_at + sector) * SECTOR, SECTOR as usize)?;
+        match le_u16(&descriptor, 0)? {
+            // Partition descriptor
+            5 => partitions.push((le_u16(&descriptor, 22)?, le_u32(&descriptor, 188)?)),
+            // Logical volume descriptor
+            6 => {
+                let label_len = descriptor[84 + 127] as usize;
+                // Only type 1 partition maps, which address the partition directly
+                if descriptor[440] != 1 {
+                    return Err(invalid("Unsupported UDF partition map"));
+                }
+                volume = Some((
+                    udf_string(&descriptor[84..84 + label_len.min(127)]),
+                    le_u32(&descriptor, 212)?,
+                    le_u32(&descriptor, 252)?,
+                    le_u16(&descriptor, 444)?,
+                ));
+            }
+            // Terminating descriptor
+            8 => break,
+            _ => {}
+        }
+    }
+    let (label, block_size, fsd_lbn, partition) =
+        volume.ok_or_else(|| invalid("No UDF logical volume descriptor"))?;
+    let partition_start = partitions
+        .iter()
+        .find(|(number, _)| *number == partition)
+        .map(|(_, start)| *start as u64)
+        .ok_or_else(|| invalid("UDF partition descriptor missing"))?;
+    if block_size == 0 || !block_size.is_power_of_two() {
+        return Err(invalid(format!("Invalid UDF block size {}", block_size)));
+    }
+    let block_size = block_size as u64;
+    let fileset = read_at(
+        reader,
+        (partition_start + fsd_lbn as u64) * block_size,
+        block_size as usize,
+    )?;
+    if le_u16(&fileset, 0)? != 256 {
+        return Err(invalid("No UDF file set descriptor"));
+    }
+    Ok((
+        Filesystem::Udf {
+            partition_start,
+            block_size,
+            root_lbn: le_u32(&fileset, 404)?,
+        },
+        label.trim().to_string(),
+    ))
+}
+
+/// Build and architecture from the `sources/idwbinfo.txt` of Windows media,
+/// e.g. `BuildArch=amd64` and `BuildLabEx=22621.1.amd64fre.ni_release...`
+fn parse_build_info(text: &str) -> (Option<u32>, Option<String>) {
+    let mut build = None;
+    let mut architecture = None;
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "buildarch" => architecture = normalize_architecture(value).map(str::to_string),
+            "buildlabex" | "buildlab" | "buildversion" if build.is_none() => {
+                let digits: String = value
+                    .trim()
+                    .chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect();
+                build = digits.parse().ok();
+            }
+            _ => {}
+        }
+    }
+    (build, architecture)
+}
+
+/// Architecture from a Microsoft volume label, e.g. `CCCOMA_X64FRE_EN-US_DV9`
+fn label_architecture(label: &str) -> Option<String> {
+    let label = label.to_ascii_uppercase();
+    [
+        ("X64", "x64"),
+        ("A64", "arm64"),
+        ("ARM64", "arm64"),
+        ("X86", "x86"),
+    ]
+    .iter()
+    .find(|(marker, _)| label.contains(marker))
+    .map(|(_, arch)| arch.to_string())
+}
+
+/// Inspect the ISO image in `reader`
+pub fn inspect<R: Read + Seek>(reader: R) -> io::Result<IsoInfo> {
+    let mut image = IsoImage::open(reader)?;
+    let mut info = IsoInfo {
+        volume_label: image.volume_label.clone(),
+        filesystem: image.filesystem().to_string(),
+        ..Default::default()
+    };
+    for path in INSTALL_IMAGES {
+        if let Some(file) = image.find(path)?.filter(|f| !f.is_dir) {
+            info.install_image = Some(path.to_string());
+            info.install_image_bytes = Some(file.size);
+            // Encrypted or damaged images still count as install media
+            if let Ok(wim) = read_wim(|offset, len| image.read(&file, offset, len)) {
+                info.editions = wim.images.iter().map(IsoEdition::from).collect();
+            }
+            break;
+        }
+    }
+    if let Some(file) = image.find("sources/idwbinfo.txt")? {
+        let text = image.read(&file, 0, MAX_TEXT_BYTES)?;
+        (info.build, info.architecture) = parse_build_info(&String::from_utf8_lossy(&text));
+    }
+    if let Some(first) = info.editions.first() {
+        info.build = info.build.or((first.build > 0).then_some(first.build));
+        if info.architecture.is_none() && !first.architecture.is_empty() {
+            info.architecture = normalize_architecture(&first.architecture).map(str::to_string);
+        }
+    }
+    if info.architecture.is_none() {
+        info.architecture = label_architecture(&info.volume_label);
+    }
+    Ok(info)
+}
+
+/// Hex SHA-256 of everything `reader` yields
+pub fn sha256_hex<R: Read>(mut reader: R) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Inspect the ISO at `path` on this machine, hashing it when `hash` is set
+pub fn inspect_iso_file(path: &Path, hash: bool) -> Result<IsoInfo, HyperVError> {
+    let open = || {
+        File::open(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                HyperVError::invalid_input(format!("ISO not found: {}", path.display()))
+            }
+            _ => HyperVError::internal(format!("Failed to open {}: {}", path.display(), e)),
+        })
+    };
+    let mut info = inspect(BufReader::new(open()?)).map_err(|e| {
+        HyperVError::invalid_input(format!(
+            "{} is not a readable ISO image: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    if hash {
+        info.sha256 = Some(sha256_hex(open()?)?);
+    }
+    Ok(info)
+}
+
+/// Inspect an ISO on this machine without mounting it: its install image,
+/// Windows build and architecture, and optionally its SHA-256
+#[tauri::command]
+pub async fn inspect_iso(iso_path: String, hash: Option<bool>) -> Result<IsoInfo, HyperVError> {
+    tokio::task::spawn_blocking(move || {
+        inspect_iso_file(Path::new(&iso_path), hash.unwrap_or(false))
+    })
+    .await
+    .map_err(HyperVError::task_failed)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    const S: usize = SECTOR as usize;
+    /// First sector of the UDF partition; UDF blocks are one sector
+    const PARTITION: usize = 260;
+
+    const BUILD_INFO: &str =
+        "[BUILDINFO]\r\nBuildArch=amd64\r\nBuildLabEx=22631.1.amd64fre.ni_release.220506-1250\r\n";
+
+    /// Image laid out sector by sector
+    struct Image(Vec<u8>);
+
+    impl Image {
+        fn new(sectors: usize) -> Self {
+            Image(vec![0; sectors * S])
+        }
+
+        fn put(&mut self, sector: usize, at: usize, bytes: &[u8]) {
+            let offset = sector * S + at;
+            self.0[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        fn volume_descriptor(&mut self, sector: usize, kind: u8, id: &[u8; 5]) {
+            self.put(sector, 0, &[kind]);
+            self.put(sector, 1, id);
+            self.put(sector, 6, &[1]);
+        }
+
+        /// Primary volume descriptor and terminator at sectors 16 and 17
+        fn iso9660(&mut self, label: &str, root_lba: u32) {
+            self.volume_descriptor(16, 1, b"CD001");
+            self.put(16, 40, format!("{:<32}", label).as_bytes());
+            self.put(16, 156, &dir_record(&[0], root_lba, SECTOR as u32, true));
+            self.volume_descriptor(17, 255, b"CD001");
+        }
+
+        fn iso9660_dir(&mut self, lba: usize, parent: u32, entries: &[Vec<u8>]) {
+            let mut data = dir_record(&[0], lba as u32, SECTOR as u32, true);
+            data.extend(dir_record(&[1], parent, SECTOR as u32, true));
+            data.extend(entries.concat());
+            self.put(lba, 0, &data);
+        }
+    }
+
+    fn dir_record(name: &[u8], lba: u32, size: u32, is_dir: bool) -> Vec<u8> {
+        let len = (33 + name.len() + 1) & !1;
+        let mut record = vec![0; len];
+        record[0] = len as u8;
+        record[2..6].copy_from_slice(&lba.to_le_bytes());
+        record[6..10].copy_from_slice(&lba.to_be_bytes());
+        record[10..14].copy_from_slice(&size.to_le_bytes());
+        record[14..18].copy_from_slice(&size.to_be_bytes());
+        record[25] = if is_dir { 2 } else { 0 };
+        record[32] = name.len() as u8;
+        record[33..33 + name.len()].copy_from_slice(name);
+        record
+    }
+
+    /// UDF file entry whose allocation descriptors (or embedded data) are `data`
+    fn file_entry(is_dir: bool, size: usize, ad_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut entry = vec![0; S];
+        entry[0..2].copy_from_slice(&261u16.to_le_bytes());
+        entry[27] = if is_dir { 4 } else { 5 };
+        entry[34..36].copy_from_slice(&ad_type.to_le_bytes());
+        entry[56..64].copy_from_slice(&(size as u64).to_le_bytes());
+        entry[172..176].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        entry[176..176 + data.len()].copy_from_slice(data);
+        entry
+    }
+
+    /// Directory held in its file entry
+    fn udf_dir(fids: &[Vec<u8>]) -> Vec<u8> {
+        let data = fids.concat();
+        file_entry(true, data.len(), 3, &data)
+    }
+
+    fn fid(name: &str, lbn: u32, characteristics: u8) -> Vec<u8> {
+        let mut name_bytes = Vec::new();
+        if !name.is_empty() {
+            name_bytes.push(8);
+            name_bytes.extend(name.as_bytes());
+        }
+        let mut fid = vec![0; (38 + name_bytes.len() + 3) & !3];
+        fid[0..2].copy_from_slice(&257u16.to_le_bytes());
+        fid[18] = characteristics;
+        fid[19] = name_bytes.len() as u8;
+        fid[24..28].copy_from_slice(&lbn.to_le_bytes());
+        fid[38..38 + name_bytes.len()].copy_from_slice(&name_bytes);
+        fid
+    }
+
+    fn short_ad(len: usize, lbn: u32) -> Vec<u8> {
+        [(len as u32).to_le_bytes(), lbn.to_le_bytes()].concat()
+    }
+
+    /// install.wim content, split over two extents
+    fn install_wim() -> Vec<u8> {
+        (0..S + 100).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Windows media as Microsoft ships them: a UDF volume with the install
+    /// files, bridged by an ISO9660 directory holding only a README
+    fn windows_image() -> Vec<u8> {
+        let mut image = Image::new(PARTITION + 13);
+        image.iso9660("CCCOMA_X64FRE_EN-US_DV9", 30);
+        image.iso9660_dir(30, 30, &[dir_record(b"README.TXT;1", 31, 5, false)]);
+        image.put(31, 0, b"hello");
+        image.volume_descriptor(18, 0, b"BEA01");
+        image.volume_descriptor(19, 0, b"NSR02");
+        image.volume_descriptor(20, 0, b"TEA01");
+
+        // Anchor, then partition, logical volume and terminating descriptors
+        image.put(256, 0, &2u16.to_le_bytes());
+        image.put(256, 16, &(3 * SECTOR as u32).to_le_bytes());
+        image.put(256, 20, &257u32.to_le_bytes());
+        image.put(257, 0, &5u16.to_le_bytes());
+        image.put(257, 188, &(PARTITION as u32).to_le_bytes());
+        image.put(258, 0, &6u16.to_le_bytes());
+        let label = "CCCOMA_X64FRE_EN-US_DV9";
+        image.put(258, 84, &[8]);
+        image.put(258, 85, label.as_bytes());
+        image.put(258, 84 + 127, &[label.len() as u8 + 1]);
+        image.put(258, 212, &(SECTOR as u32).to_le_bytes());
+        image.put(258, 440, &[1]);
+        image.put(259, 0, &8u16.to_le_bytes());
+
+        // File set descriptor, then the tree by logical block number
+        image.put(PARTITION, 0, &256u16.to_le_bytes());
+        image.put(PARTITION, 404, &1u32.to_le_bytes());
+        image.put(
+            PARTITION + 1,
+            0,
+            &udf_dir(&[fid("", 1, 0x0A), fid("sources", 2, 0x02)]),
+        );
+        image.put(
+            PARTITION + 2,
+            0,
+            &udf_dir(&[
+                fid("", 1, 0x0A),
+                fid("install.wim", 3, 0),
+                fid("idwbinfo.txt", 4, 0),
+            ]),
+        );
+        let wim = install_wim();
+        let extents = [short_ad(S, 10), short_ad(100, 12)].concat();
+        image.put(PARTITION + 3, 0, &file_entry(false, wim.len(), 0, &extents));
+        image.put(PARTITION + 10, 0, &wim[..S]);
+        image.put(PARTITION + 12, 0, &wim[S..]);
+        image.put(
+            PARTITION + 4,
+            0,
+            &file_entry(false, BUILD_INFO.len(), 3, BUILD_INFO.as_bytes()),
+        );
+        image.0
+    }
+
+    fn data_image() -> Vec<u8> {
+        let mut image = Image::new(20);
+        image.iso9660("BACKUP_2024", 18);
+        image.iso9660_dir(18, 18, &[dir_record(b"README.TXT;1", 19, 5, false)]);
+        image.put(19, 0, b"notes");
+        image.0
+    }
+
+    fn temp_iso(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.iso", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn inspects_windows_media() {
+        let info = inspect(Cursor::new(windows_image())).unwrap();
+        assert!(info.is_windows_installer());
+        assert_eq!(info.filesystem, "udf");
+        assert_eq!(info.volume_label, "CCCOMA_X64FRE_EN-US_DV9");
+        assert_eq!(info.install_image.as_deref(), Some("sources/install.wim"));
+        assert_eq!(info.install_image_bytes, Some(install_wim().len() as u64));
+        assert_eq!(info.build, Some(22631));
+        assert_eq!(info.architecture.as_deref(), Some("x64"));
+        // Not a WIM, which the inspector tolerates
+        assert!(info.editions.is_empty());
+    }
+
+    #[test]
+    fn reads_files_across_extents_and_filesystems() {
+        let mut image = IsoImage::open(Cursor::new(windows_image())).unwrap();
+        let wim = image.find("SOURCES/Install.WIM").unwrap().unwrap();
+        assert!(!wim.is_dir);
+        assert_eq!(
+            image.read(&wim, S as u64 - 10, 30).unwrap(),
+            install_wim()[S - 10..S + 20]
+        );
+        assert_eq!(image.read(&wim, 0, u64::MAX).unwrap(), install_wim());
+        assert!(image.find("sources/install.wim/x").unwrap().is_none());
+
+        // Only in the ISO9660 directory of the bridge
+        let readme = image.find("readme.txt").unwrap().unwrap();
+        assert_eq!(image.read(&readme, 0, 100).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn inspects_non_windows_media() {
+        let info = inspect(Cursor::new(data_image())).unwrap();
+        assert!(!info.is_windows_installer());
+        assert_eq!(info.filesystem, "iso9660");
+        assert_eq!(info.volume_label, "BACKUP_2024");
+        assert_eq!((info.build, info.architecture), (None, None));
+    }
+
+    #[test]
+    fn rejects_truncated_media() {
+        let image = windows_image();
+
+        // Cut before the volume descriptors
+        let error = inspect(Cursor::new(&image[..10 * S])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Cut inside the UDF descriptors: only the ISO9660 bridge is readable
+        let info = inspect(Cursor::new(&image[..258 * S])).unwrap();
+        assert_eq!(info.filesystem, "iso9660");
+        assert!(!info.is_windows_installer());
+
+        let path = temp_iso("truncated", &image[..10 * S]);
+        let error = inspect_iso_file(&path, false).unwrap_err();
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(error, HyperVError::InvalidInput(_)));
+        assert!(error.message().contains("is not a readable ISO image"));
+    }
+
+    #[test]
+    fn inspects_iso_files_with_hash() {
+        let bytes = data_image();
+        let path = temp_iso("hashed", &bytes);
+        let info = inspect_iso_file(&path, true).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(info.sha256, Some(sha256_hex(Cursor::new(&bytes)).unwrap()));
+
+        assert_eq!(
+            sha256_hex(Cursor::new(b"abc")).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let missing = inspect_iso_file(Path::new("/nonexistent/win11.iso"), false).unwrap_err();
+        assert!(missing.message().starts_with("ISO not found"));
+    }
+}
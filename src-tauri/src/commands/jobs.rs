@@ -0,0 +1,503 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::State;
+
+use super::checkpoint::Checkpoint;
+use super::error::{ErrorDetails, HyperVError};
+use super::executor::{CancellationToken, ExecutorState};
+use super::host::HostProfile;
+use super::progress::ProgressEvent;
+use super::rollback::{ProvisionResource, RollbackReport};
+use super::runlog::{LogStream, RunLog, RunStatus};
+use super::utils::now_ms;
+
+/// Most recent log lines kept per job
+const MAX_LOG_LINES: usize = 5000;
+
+/// Finished jobs kept around for `list_provisioning_jobs` / log lookups
+const MAX_FINISHED_JOBS: usize = 20;
+
+/// Event carrying a `JobLogLine` for every line a job logs
+pub const JOB_LOG_EVENT: &str = "provisioning-log";
+
+/// Payload of `JOB_LOG_EVENT`
+#[derive(Serialize, Clone, Debug)]
+pub struct JobLogLine {
+    pub job_id: u64,
+    pub vm: String,
+    pub line: String,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Snapshot of a job for the frontend
+#[derive(Serialize, Clone, Debug)]
+pub struct JobSummary {
+    pub id: u64,
+    pub vm: String,
+    /// Remote host profile the VM is provisioned on; `None` for this machine
+    pub host: Option<String>,
+    pub status: JobStatus,
+    /// PID of the running provisioning script, if any
+    pub pid: Option<u32>,
+    /// Last progress the provisioning script reported
+    pub progress: Option<ProgressEvent>,
+    /// Checkpoints this run completed
+    pub checkpoints: Vec<Checkpoint>,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub error: Option<String>,
+    /// What was undone after the job failed or was cancelled
+    pub rollback: Option<RollbackReport>,
+}
+
+struct JobState {
+    status: JobStatus,
+    pid: Option<u32>,
+    progress: Option<ProgressEvent>,
+    log: VecDeque<String>,
+    resources: Vec<ProvisionResource>,
+    /// Resources before this index belong to a completed checkpoint
+    committed: usize,
+    checkpoints: Vec<Checkpoint>,
+    rollback: Option<RollbackReport>,
+    ended_at: Option<u64>,
+    error: Option<String>,
+}
+
+/// One `create_vm` run: its process, output and outcome
+pub struct ProvisioningJob {
+    pub id: u64,
+    pub vm: String,
+    /// Host the job runs on, so cancellation reaches it after the active host changes
+    pub host: Option<HostProfile>,
+    started_at: u64,
+    cancel: CancellationToken,
+    /// Persisted copy of the log, kept after the app exits
+    run_log: OnceLock<RunLog>,
+    state: Mutex<JobState>,
+}
+
+impl ProvisioningJob {
+    pub fn is_running(&self) -> bool {
+        self.state.lock().unwrap().status == JobStatus::Running
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Flag the job as cancelled; its process still has to be killed
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.state.lock().unwrap().pid
+    }
+
+    pub fn set_pid(&self, pid: Option<u32>) {
+        self.state.lock().unwrap().pid = pid;
+    }
+
+    pub fn set_progress(&self, progress: ProgressEvent) {
+        self.state.lock().unwrap().progress = Some(progress);
+    }
+
+    /// Also write everything the job logs from now on to `run_log`
+    pub fn persist_to(&self, run_log: RunLog) {
+        let _ = self.run_log.set(run_log);
+    }
+
+    /// Append a line to the job's log buffer and its persisted log
+    pub fn log(&self, stream: LogStream, line: &str) {
+        if let Some(run_log) = self.run_log.get() {
+            run_log.write(stream, line);
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.log.len() == MAX_LOG_LINES {
+            state.log.pop_front();
+        }
+        state.log.push_back(stream.display(line));
+    }
+
+    pub fn log_lines(&self) -> Vec<String> {
+        self.state.lock().unwrap().log.iter().cloned().collect()
+    }
+
+    /// Remember something the job created, to undo it if the job fails
+    pub fn track(&self, resource: ProvisionResource) {
+        let mut state = self.state.lock().unwrap();
+        if !state.resources.contains(&resource) {
+            state.resources.push(resource);
+        }
+    }
+
+    /// Mark `checkpoint` as done. Returns the lasting resources created
+    /// since the previous checkpoint, which now belong to it.
+    pub fn complete_checkpoint(&self, checkpoint: Checkpoint) -> Vec<ProvisionResource> {
+        let mut state = self.state.lock().unwrap();
+        state.checkpoints.push(checkpoint);
+        let created = state.resources[state.committed..]
+            .iter()
+            .filter(|r| !r.is_transient())
+            .cloned()
+            .collect();
+        state.committed = state.resources.len();
+        created
+    }
+
+    /// Resources to undo, in the order they were created: all of them, or
+    /// only those a resumed run would not reuse
+    pub fn rollback_resources(&self, all: bool) -> Vec<ProvisionResource> {
+        let state = self.state.lock().unwrap();
+        state
+            .resources
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| all || *i >= state.committed || r.is_transient())
+            .map(|(_, r)| r.clone())
+            .collect()
+    }
+
+    pub fn set_rollback(&self, report: RollbackReport) {
+        self.state.lock().unwrap().rollback = Some(report);
+    }
+
+    /// Record the outcome; cancellation wins over whatever error it caused
+    pub fn finish<T>(&self, result: &Result<T, HyperVError>) {
+        let mut state = self.state.lock().unwrap();
+        state.pid = None;
+        state.ended_at = Some(now_ms());
+        state.status = match result {
+            Ok(_) => JobStatus::Succeeded,
+            Err(_) if self.cancel.is_cancelled() => JobStatus::Cancelled,
+            Err(HyperVError::Cancelled(_)) => JobStatus::Cancelled,
+            Err(_) => JobStatus::Failed,
+        };
+        state.error = result.as_ref().err().map(|e| e.message().to_string());
+        if let Some(run_log) = self.run_log.get() {
+            let status = match state.status {
+                JobStatus::Running => RunStatus::Running,
+                JobStatus::Succeeded => RunStatus::Succeeded,
+                JobStatus::Failed => RunStatus::Failed,
+                JobStatus::Cancelled => RunStatus::Cancelled,
+            };
+            run_log.finish(status, state.error.clone());
+        }
+    }
+
+    pub fn summary(&self) -> JobSummary {
+        let state = self.state.lock().unwrap();
+        JobSummary {
+            id: self.id,
+            vm: self.vm.clone(),
+            host: self.host.as_ref().map(|h| h.name.clone()),
+            status: state.status,
+            pid: state.pid,
+            progress: state.progress.clone(),
+            checkpoints: state.checkpoints.clone(),
+            started_at: self.started_at,
+            ended_at: state.ended_at,
+            error: state.error.clone(),
+            rollback: state.rollback.clone(),
+        }
+    }
+
+    fn runs_on(&self, host: Option<&str>, vm: &str) -> bool {
+        self.host.as_ref().map(|h| h.name.as_str()) == host && self.vm.eq_ignore_ascii_case(vm)
+    }
+}
+
+/// Provisioning jobs by id, so several VMs can be created in parallel
+/// and each one cancelled on its own
+#[derive(Default)]
+pub struct JobRegistry {
+    next_id: Mutex<u64>,
+    jobs: Mutex<Vec<Arc<ProvisioningJob>>>,
+}
+
+impl JobRegistry {
+    /// Register a running job for `vm`; fails if one is already running for it
+    pub fn start(
+        &self,
+        host: Option<HostProfile>,
+        vm: &str,
+    ) -> Result<Arc<ProvisioningJob>, HyperVError> {
+        let host_name = host.as_ref().map(|h| h.name.clone());
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(running) = jobs
+            .iter()
+            .find(|j| j.is_running() && j.runs_on(host_name.as_deref(), vm))
+        {
+            return Err(HyperVError::VmBusy(ErrorDetails {
+                message: format!(
+                    "VM '{}' is already being provisioned (job {})",
+                    vm, running.id
+                ),
+                hint: Some("Cancel the running job or wait for it to finish.".to_string()),
+                record: None,
+            }));
+        }
+
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let job = Arc::new(ProvisioningJob {
+            id,
+            vm: vm.to_string(),
+            host,
+            started_at: now_ms(),
+            cancel: CancellationToken::new(),
+            run_log: OnceLock::new(),
+            state: Mutex::new(JobState {
+                status: JobStatus::Running,
+                pid: None,
+                progress: None,
+                log: VecDeque::new(),
+                resources: Vec::new(),
+                committed: 0,
+                checkpoints: Vec::new(),
+                rollback: None,
+                ended_at: None,
+                error: None,
+            }),
+        });
+        jobs.push(job.clone());
+
+        // Drop the oldest finished jobs beyond the limit
+        let finished = jobs.iter().filter(|j| !j.is_running()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+        jobs.retain(|j| {
+            if excess > 0 && !j.is_running() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+        Ok(job)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<ProvisioningJob>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|j| j.id == id)
+            .cloned()
+    }
+
+    /// The running job for `vm` on `host`
+    pub fn running(&self, host: Option<&str>, vm: &str) -> Option<Arc<ProvisioningJob>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|j| j.is_running() && j.runs_on(host, vm))
+            .cloned()
+    }
+
+    /// Every known job, oldest first
+    pub fn list(&self) -> Vec<JobSummary> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|j| j.summary())
+            .collect()
+    }
+}
+
+/// A PowerShell call made through `ExecutorState::run_blocking`
+#[derive(Serialize, Clone, Debug)]
+pub struct RunningCall {
+    pub id: u64,
+    /// Command that made the call, e.g. `update_vm`
+    pub kind: String,
+    pub vm: Option<String>,
+    /// Remote host profile the call runs on; `None` for this machine
+    pub host: Option<String>,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+}
+
+/// Calls in flight with the tokens that cancel them. Tauri never drops the
+/// future of a running command, so `cancel_call` is how the frontend stops
+/// one; the scripts it runs are killed and it fails with `Cancelled`.
+#[derive(Default)]
+pub struct CallRegistry {
+    next_id: Mutex<u64>,
+    calls: Mutex<Vec<(RunningCall, CancellationToken)>>,
+}
+
+impl CallRegistry {
+    /// List a call until the returned guard is dropped
+    pub fn register(
+        self: &Arc<Self>,
+        kind: &str,
+        vm: Option<&str>,
+        host: Option<&str>,
+    ) -> CallGuard {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let token = CancellationToken::new();
+        let call = RunningCall {
+            id,
+            kind: kind.to_string(),
+            vm: vm.map(str::to_string),
+            host: host.map(str::to_string),
+            started_at: now_ms(),
+        };
+        self.calls.lock().unwrap().push((call, token.clone()));
+        CallGuard {
+            registry: self.clone(),
+            id,
+            token,
+        }
+    }
+
+    /// Running calls, oldest first
+    pub fn list(&self) -> Vec<RunningCall> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(call, _)| call.clone())
+            .collect()
+    }
+
+    /// Cancel call `id`; `false` if it is not running
+    pub fn cancel(&self, id: u64) -> bool {
+        let calls = self.calls.lock().unwrap();
+        match calls.iter().find(|(call, _)| call.id == id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Registration of a running call; cancels and unlists it when dropped
+pub struct CallGuard {
+    registry: Arc<CallRegistry>,
+    id: u64,
+    token: CancellationToken,
+}
+
+impl CallGuard {
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.token.cancel();
+        self.registry
+            .calls
+            .lock()
+            .unwrap()
+            .retain(|(call, _)| call.id != self.id);
+    }
+}
+
+/// Running and recently finished provisioning jobs
+#[tauri::command]
+pub async fn list_provisioning_jobs(
+    jobs: State<'_, JobRegistry>,
+) -> Result<Vec<JobSummary>, HyperVError> {
+    Ok(jobs.list())
+}
+
+/// Buffered output of a provisioning job
+#[tauri::command]
+pub async fn get_provisioning_log(
+    jobs: State<'_, JobRegistry>,
+    job_id: u64,
+) -> Result<Vec<String>, HyperVError> {
+    jobs.get(job_id)
+        .map(|job| job.log_lines())
+        .ok_or_else(|| HyperVError::invalid_input(format!("Unknown job: {}", job_id)))
+}
+
+/// PowerShell calls of running commands
+#[tauri::command]
+pub async fn list_running_calls(
+    ps: State<'_, ExecutorState>,
+) -> Result<Vec<RunningCall>, HyperVError> {
+    Ok(ps.calls().list())
+}
+
+/// Cancel a call listed by `list_running_calls`
+#[tauri::command]
+pub async fn cancel_call(ps: State<'_, ExecutorState>, call_id: u64) -> Result<(), HyperVError> {
+    if ps.calls().cancel(call_id) {
+        Ok(())
+    } else {
+        Err(HyperVError::invalid_input(format!(
+            "No call {} is running",
+            call_id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::audit::AuditContext;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn cancel_call_stops_running_command() {
+        let mut slow = ScriptRule::ok("Get-VM", "[]");
+        slow.delay_ms = 30_000;
+        let state = Arc::new(ExecutorState::new(Arc::new(ScriptedExecutor::new(vec![
+            slow,
+        ]))));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let running = state.clone();
+        let call = runtime.spawn(async move {
+            running
+                .run_blocking(AuditContext::new("list_vms").vm("vm"), None, |ps| {
+                    ps.run("Get-VM")
+                })
+                .await
+        });
+
+        let started = Instant::now();
+        let listed = loop {
+            if let Some(call) = state.calls().list().pop() {
+                break call;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(listed.kind, "list_vms");
+        assert_eq!(listed.vm.as_deref(), Some("vm"));
+        assert!(!state.calls().cancel(listed.id + 1));
+        assert!(state.calls().cancel(listed.id));
+
+        let result = runtime.block_on(call).unwrap();
+        assert!(matches!(result, Err(HyperVError::Cancelled(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(state.calls().list().is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::State;
+use tokio::sync::Notify;
+
+use super::error::{ErrorDetails, HyperVError};
+use super::utils::now_ms;
+
+/// What to do when the VM is already held by another operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// Queue behind the running and already queued operations
+    Wait,
+    /// Fail with `VmBusy` straight away
+    Reject,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    Queued,
+    Running,
+}
+
+/// An operation holding or waiting for a VM
+#[derive(Serialize, Clone, Debug)]
+pub struct VmOperation {
+    pub id: u64,
+    /// Remote host profile the VM lives on; `None` for this machine
+    pub host: Option<String>,
+    pub vm: String,
+    /// Command name, e.g. `update_vm_config`
+    pub operation: String,
+    /// Short human readable label, e.g. `Updating GPU`
+    pub description: String,
+    pub status: OperationStatus,
+    /// Unix time in milliseconds
+    pub queued_at: u64,
+    pub started_at: Option<u64>,
+}
+
+#[derive(Default)]
+struct LockTable {
+    next_id: u64,
+    /// Every operation in arrival order; per VM the first one runs
+    operations: Vec<VmOperation>,
+}
+
+impl LockTable {
+    fn for_vm<'a>(
+        &'a self,
+        host: Option<&'a str>,
+        vm: &'a str,
+    ) -> impl Iterator<Item = &'a VmOperation> {
+        // Hyper-V VM names are case-insensitive
+        self.operations
+            .iter()
+            .filter(move |op| op.host.as_deref() == host && op.vm.eq_ignore_ascii_case(vm))
+    }
+
+    /// Start `id` if it is first in line for its VM; `true` once it runs
+    fn try_start(&mut self, id: u64) -> bool {
+        let Some((host, vm)) = self
+            .operations
+            .iter()
+            .find(|op| op.id == id)
+            .map(|op| (op.host.clone(), op.vm.clone()))
+        else {
+            return false;
+        };
+        if self.for_vm(host.as_deref(), &vm).next().map(|op| op.id) != Some(id) {
+            return false;
+        }
+        let op = self
+            .operations
+            .iter_mut()
+            .find(|op| op.id == id)
+            .expect("operation is queued");
+        if op.status == OperationStatus::Queued {
+            op.status = OperationStatus::Running;
+            op.started_at = Some(now_ms());
+        }
+        true
+    }
+}
+
+/// Serializes operations that change a VM, one at a time per VM.
+/// Conflicting calls either queue in arrival order or are rejected, and the
+/// queue is visible to the frontend through `list_vm_operations`.
+#[derive(Default)]
+pub struct VmLocks {
+    table: Mutex<LockTable>,
+    changed: Notify,
+}
+
+impl VmLocks {
+    /// Hold `vm` on `host` for `operation` until the returned guard is dropped.
+    /// Dropping the future while queued gives up the place in line.
+    pub async fn acquire(
+        &self,
+        host: Option<&str>,
+        vm: &str,
+        operation: &str,
+        description: &str,
+        policy: LockPolicy,
+    ) -> Result<VmLock<'_>, HyperVError> {
+        let id = {
+            let mut table = self.table.lock().unwrap();
+            if policy == LockPolicy::Reject {
+                if let Some(holder) = table.for_vm(host, vm).next() {
+                    return Err(busy_error(holder));
+                }
+            }
+            table.next_id += 1;
+            let id = table.next_id;
+            table.operations.push(VmOperation {
+                id,
+                host: host.map(str::to_string),
+                vm: vm.to_string(),
+                operation: operation.to_string(),
+                description: description.to_string(),
+                status: OperationStatus::Queued,
+                queued_at: now_ms(),
+                started_at: None,
+            });
+            id
+        };
+
+        let lock = VmLock { locks: self, id };
+        loop {
+            // Registered before checking so a release in between is not missed
+            let changed = self.changed.notified();
+            if self.table.lock().unwrap().try_start(id) {
+                return Ok(lock);
+            }
+            changed.await;
+        }
+    }
+
+    /// Running and queued operations, oldest first
+    pub fn operations(&self) -> Vec<VmOperation> {
+        self.table.lock().unwrap().operations.clone()
+    }
+
+    fn release(&self, id: u64) {
+        self.table
+            .lock()
+            .unwrap()
+            .operations
+            .retain(|op| op.id != id);
+        self.changed.notify_waiters();
+    }
+}
+
+/// Held (or queued) claim on a VM, released on drop
+pub struct VmLock<'a> {
+    locks: &'a VmLocks,
+    id: u64,
+}
+
+impl Drop for VmLock<'_> {
+    fn drop(&mut self) {
+        self.locks.release(self.id);
+    }
+}
+
+fn busy_error(holder: &VmOperation) -> HyperVError {
+    HyperVError::VmBusy(ErrorDetails {
+        message: format!("VM '{}' is busy: {}", holder.vm, holder.description),
+        hint: Some("Wait for the current operation to finish and try again.".to_string()),
+        record: None,
+    })
+}
+
+/// Operations currently running or queued on VMs
+#[tauri::command]
+pub async fn list_vm_operations(
+    locks: State<'_, VmLocks>,
+) -> Result<Vec<VmOperation>, HyperVError> {
+    Ok(locks.operations())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn statuses(locks: &VmLocks) -> Vec<(String, OperationStatus)> {
+        locks
+            .operations()
+            .into_iter()
+            .map(|op| (op.operation, op.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reject_fails_with_vm_busy_whatever_the_case() {
+        let locks = VmLocks::default();
+        let _held = locks
+            .acquire(
+                None,
+                "Gaming",
+                "update_vm_config",
+                "Updating GPU",
+                LockPolicy::Reject,
+            )
+            .await
+            .unwrap();
+        let err = locks
+            .acquire(None, "GAMING", "start_vm", "Starting", LockPolicy::Reject)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, HyperVError::VmBusy(_)), "{err:?}");
+        assert!(err.message().contains("Updating GPU"));
+        assert_eq!(statuses(&locks).len(), 1);
+
+        // Other VMs and the same name on another host do not contend
+        locks
+            .acquire(None, "Work", "start_vm", "Starting", LockPolicy::Reject)
+            .await
+            .unwrap();
+        locks
+            .acquire(
+                Some("lab"),
+                "Gaming",
+                "start_vm",
+                "Starting",
+                LockPolicy::Reject,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn wait_queues_until_the_holder_is_dropped() {
+        let locks = Arc::new(VmLocks::default());
+        let held = locks
+            .acquire(
+                None,
+                "Gaming",
+                "update_vm_config",
+                "Updating GPU",
+                LockPolicy::Wait,
+            )
+            .await
+            .unwrap();
+        let waiter = tokio::spawn({
+            let locks = locks.clone();
+            async move {
+                let _lock = locks
+                    .acquire(None, "gaming", "start_vm", "Starting", LockPolicy::Wait)
+                    .await
+                    .unwrap();
+                statuses(&locks)
+            }
+        });
+        while locks.operations().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            statuses(&locks),
+            vec![
+                ("update_vm_config".to_string(), OperationStatus::Running),
+                ("start_vm".to_string(), OperationStatus::Queued),
+            ]
+        );
+        // A queued operation also blocks Reject
+        assert!(locks
+            .acquire(None, "Gaming", "stop_vm", "Stopping", LockPolicy::Reject)
+            .await
+            .is_err());
+
+        drop(held);
+        assert_eq!(
+            waiter.await.unwrap(),
+            vec![("start_vm".to_string(), OperationStatus::Running)]
+        );
+        assert!(locks.operations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropping_releases_the_lock_and_the_place_in_line() {
+        let locks = VmLocks::default();
+        let held = locks
+            .acquire(
+                None,
+                "Gaming",
+                "update_vm_config",
+                "Updating GPU",
+                LockPolicy::Wait,
+            )
+            .await
+            .unwrap();
+        let queued = tokio::time::timeout(
+            Duration::from_millis(20),
+            locks.acquire(None, "Gaming", "start_vm", "Starting", LockPolicy::Wait),
+        )
+        .await;
+        assert!(queued.is_err());
+        assert_eq!(statuses(&locks).len(), 1);
+
+        drop(held);
+        assert!(locks.operations().is_empty());
+        locks
+            .acquire(None, "Gaming", "start_vm", "Starting", LockPolicy::Reject)
+            .await
+            .unwrap();
+    }
+}
@@ -1,9 +1,41 @@
+pub mod audit;
+pub mod checkpoint;
 pub mod config;
+pub mod disk;
+pub mod error;
+pub mod executor;
+pub mod fixture;
+pub mod host;
+pub mod iso;
+pub mod jobs;
+pub mod lock;
+pub mod plan;
+pub mod progress;
 pub mod rdp;
+pub mod rollback;
+pub mod runlog;
+pub mod script;
+pub mod session;
 pub mod system;
+pub mod template;
+pub mod unattend;
 pub mod utils;
+pub mod validation;
 pub mod vm;
+pub mod wim;
 
+pub use audit::query_audit_log;
+pub use checkpoint::get_provisioning_checkpoint;
+pub use error::HyperVError;
+pub use executor::ExecutorState;
+pub use host::{
+    delete_host, get_active_host, list_hosts, save_host, set_active_host, test_host_connection,
+};
+pub use iso::{inspect_iso, list_iso_editions};
+pub use jobs::{cancel_call, get_provisioning_log, list_provisioning_jobs, list_running_calls};
+pub use lock::list_vm_operations;
+pub use runlog::{get_vm_run_log, list_vm_runs};
 pub use system::*;
 pub use utils::*;
 pub use vm::*;
+pub use wim::inspect_wim;
@@ -0,0 +1,220 @@
+use serde::Serialize;
+
+/// Result of a command that supports dry runs: either the real outcome or the
+/// plan of what would have been done. Untagged, so completed results keep
+/// their usual shape.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum RunOutcome<T> {
+    Completed(T),
+    DryRun(ExecutionPlan),
+}
+
+/// Everything a command would do, produced without changing the host
+#[derive(Serialize, Debug, Default)]
+pub struct ExecutionPlan {
+    /// Always `true`; lets the frontend tell a plan from a completed result
+    pub dry_run: bool,
+    /// Command that produced the plan, e.g. `create_vm`
+    pub operation: String,
+    pub vm_name: String,
+    pub checks: Vec<ResourceCheck>,
+    /// Files that would be written, with secrets redacted
+    pub files: Vec<PlannedFile>,
+    /// Scripts that would be executed, in order
+    pub commands: Vec<String>,
+    /// Hyper-V cmdlets referenced by the executed scripts, in first-use order
+    pub cmdlets: Vec<String>,
+}
+
+impl ExecutionPlan {
+    pub fn new(operation: &str, vm_name: &str) -> Self {
+        Self {
+            dry_run: true,
+            operation: operation.to_string(),
+            vm_name: vm_name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// How a failed check affects the command
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The command refuses to run
+    #[default]
+    Error,
+    /// Reported, but the command runs anyway
+    Warning,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ResourceCheck {
+    pub name: String,
+    /// Config field the check is about, for field-level errors in the form
+    pub field: String,
+    pub severity: Severity,
+    pub passed: bool,
+    pub message: String,
+}
+
+impl ResourceCheck {
+    pub fn new(name: &str, passed: bool, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            field: name.to_string(),
+            severity: Severity::Error,
+            passed,
+            message: message.into(),
+        }
+    }
+
+    /// Attribute the check to `field` rather than the field named like it
+    pub fn field(mut self, field: &str) -> Self {
+        self.field = field.to_string();
+        self
+    }
+
+    /// Report a failure without blocking the command
+    pub fn warning(mut self) -> Self {
+        self.severity = Severity::Warning;
+        self
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        !self.passed && self.severity == Severity::Error
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlannedFile {
+    pub path: String,
+    pub description: String,
+    /// Rendered contents; `None` for files copied verbatim
+    pub contents: Option<String>,
+}
+
+/// Verbs used by Hyper-V module cmdlets
+const HYPERV_VERBS: &[&str] = &[
+    "Add",
+    "Checkpoint",
+    "Compare",
+    "Complete",
+    "Connect",
+    "Convert",
+    "Debug",
+    "Disable",
+    "Disconnect",
+    "Dismount",
+    "Enable",
+    "Export",
+    "Get",
+    "Grant",
+    "Import",
+    "Measure",
+    "Merge",
+    "Mount",
+    "Move",
+    "New",
+    "Optimize",
+    "Remove",
+    "Rename",
+    "Repair",
+    "Reset",
+    "Resize",
+    "Restart",
+    "Resume",
+    "Revoke",
+    "Save",
+    "Set",
+    "Start",
+    "Stop",
+    "Suspend",
+    "Test",
+    "Update",
+];
+
+/// Hyper-V cmdlets (`Verb-VM...` / `Verb-VHD...`) referenced by a script.
+/// Functions the script defines itself are left out, so modules it imports
+/// should be passed along with it.
+pub fn hyperv_cmdlets(script: &str) -> Vec<String> {
+    let defined: Vec<String> = script
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start_matches('\u{feff}').trim_start();
+            let keyword = line.get(..9)?;
+            if !keyword.eq_ignore_ascii_case("function ") {
+                return None;
+            }
+            let rest = line[9..].trim_start();
+            rest.split(|c: char| c.is_whitespace() || c == '{' || c == '(')
+                .next()
+                .map(|name| name.to_ascii_lowercase())
+        })
+        .collect();
+
+    let mut cmdlets: Vec<String> = Vec::new();
+    let tokens = script.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'));
+    for token in tokens {
+        let Some((verb, noun)) = token.split_once('-') else {
+            continue;
+        };
+        let is_hyperv = HYPERV_VERBS.contains(&verb)
+            && (noun.starts_with("VM") || noun.starts_with("VHD") || noun.starts_with("Vhd"))
+            && noun.chars().all(|c| c.is_ascii_alphanumeric());
+        if is_hyperv
+            && !defined.contains(&token.to_ascii_lowercase())
+            && !cmdlets.iter().any(|c| c.eq_ignore_ascii_case(token))
+        {
+            cmdlets.push(token.to_string());
+        }
+    }
+    cmdlets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_hyperv_cmdlets_in_first_use_order() {
+        let script = "$vm = Get-VM -Name $Name\n\
+                      Set-VMProcessor $vm -Count 4; New-VHD -Path $p\n\
+                      if (-not (Get-VM $Name)) { Mount-VHD $p }\n\
+                      Get-Process | Out-Null; Remove-Item $p; Get-VMHost";
+        assert_eq!(
+            hyperv_cmdlets(script),
+            vec![
+                "Get-VM",
+                "Set-VMProcessor",
+                "New-VHD",
+                "Mount-VHD",
+                "Get-VMHost"
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_are_dropped_whatever_the_case() {
+        assert_eq!(
+            hyperv_cmdlets("Get-VHD $a\nGet-Vhd $b\nGet-VHD $c"),
+            vec!["Get-VHD"]
+        );
+    }
+
+    #[test]
+    fn functions_the_script_defines_are_left_out() {
+        let script = "\u{feff}function Add-VMGpuPartitionAdapterFiles {\n}\n\
+                      FUNCTION Test-VMReady($vm) { Get-VM $vm }\n\
+                      \tfunction   Stop-VMQuietly{ Stop-VM $args[0] -Force }\n\
+                      Add-VMGpuPartitionAdapterFiles; add-vmgpupartitionadapterfiles\n\
+                      Test-VMReady $Name; Stop-VMQuietly $Name";
+        assert_eq!(hyperv_cmdlets(script), vec!["Get-VM", "Stop-VM"]);
+    }
+
+    #[test]
+    fn non_hyperv_tokens_are_ignored() {
+        assert!(hyperv_cmdlets("Get-Disk; New-Item; Get-VMs-Thing; Get-; -VM; Read-VM").is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+use serde::Serialize;
+use std::time::Instant;
+
+/// Prefix of the progress lines the easy-gpu-pv scripts print:
+/// `##PROGRESS|<phase id>|<step>|<total>|<percent>|<message>`.
+/// Step and total are 0 when the script does not know them (the shared GPU
+/// driver module), percent is -1 when the phase has no finer progress.
+pub const PROGRESS_MARKER: &str = "##PROGRESS|";
+
+/// Event carrying a `ProgressEvent`
+pub const PROGRESS_EVENT: &str = "vm-progress";
+
+/// A provisioning phase and its share of the total run time
+pub struct Phase {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub weight: u32,
+}
+
+/// Phases of CopyFilesToVM.ps1, in order. Must match `$ProvisionPhases`
+/// in CopyFilesToVM.template.ps1.
+pub const PROVISION_PHASES: &[Phase] = &[
+    Phase {
+        id: "prepare",
+        name: "Checking parameters",
+        weight: 1,
+    },
+    Phase {
+        id: "mount_iso",
+        name: "Mounting ISO",
+        weight: 2,
+    },
+    Phase {
+        id: "create_disk",
+        name: "Creating virtual disk",
+        weight: 4,
+    },
+    Phase {
+        id: "apply_image",
+        name: "Applying Windows image",
+        weight: 60,
+    },
+    Phase {
+        id: "create_vm",
+        name: "Creating VM",
+        weight: 6,
+    },
+    Phase {
+        id: "assign_gpu",
+        name: "Assigning GPU",
+        weight: 4,
+    },
+    Phase {
+        id: "drivers",
+        name: "Copying GPU drivers",
+        weight: 20,
+    },
+    Phase {
+        id: "start_vm",
+        name: "Starting VM",
+        weight: 3,
+    },
+];
+
+/// One parsed progress line
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressMarker {
+    pub phase: String,
+    pub step: u32,
+    pub total: u32,
+    pub percent: Option<f64>,
+    pub message: String,
+}
+
+/// Parse a `##PROGRESS|...` line; anything else is `None`
+pub fn parse_marker(line: &str) -> Option<ProgressMarker> {
+    let rest = line.trim().strip_prefix(PROGRESS_MARKER)?;
+    let mut fields = rest.splitn(5, '|');
+    let phase = fields.next()?.trim();
+    if phase.is_empty() {
+        return None;
+    }
+    let step = fields.next()?.trim().parse().ok()?;
+    let total = fields.next()?.trim().parse().ok()?;
+    let percent = fields
+        .next()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|p| *p >= 0.0)
+        .map(|p| p.min(100.0));
+    let message = fields.next().unwrap_or_default().trim().to_string();
+    Some(ProgressMarker {
+        phase: phase.to_string(),
+        step,
+        total,
+        percent,
+        message,
+    })
+}
+
+/// Progress of a provisioning run, sent to the frontend as `vm-progress`
+#[derive(Serialize, Clone, Debug)]
+pub struct ProgressEvent {
+    pub job_id: u64,
+    pub vm: String,
+    pub phase: String,
+    pub phase_name: String,
+    /// 1-based index of the phase
+    pub step: u32,
+    pub total_steps: u32,
+    /// Progress within the phase, when the script reports it
+    pub phase_percent: Option<f64>,
+    pub overall_percent: f64,
+    pub message: String,
+    pub elapsed_secs: u64,
+    /// Estimated seconds left, once there is enough progress to go by
+    pub eta_secs: Option<u64>,
+}
+
+/// Turns progress markers into `ProgressEvent`s, weighting each phase by
+/// its expected share of the run for the overall percentage and ETA
+pub struct ProgressTracker {
+    job_id: u64,
+    vm: String,
+    phases: &'static [Phase],
+    started: Instant,
+    overall: f64,
+}
+
+impl ProgressTracker {
+    pub fn new(job_id: u64, vm: &str, phases: &'static [Phase]) -> Self {
+        Self {
+            job_id,
+            vm: vm.to_string(),
+            phases,
+            started: Instant::now(),
+            overall: 0.0,
+        }
+    }
+
+    /// Fold in a marker. Overall progress never goes backwards.
+    pub fn update(&mut self, marker: &ProgressMarker) -> ProgressEvent {
+        let index = self.phases.iter().position(|p| p.id == marker.phase);
+        let (step, total_steps) = match index {
+            Some(i) => (i as u32 + 1, self.phases.len() as u32),
+            None => (marker.step, marker.total),
+        };
+        let phase_name = index
+            .map(|i| self.phases[i].name.to_string())
+            .unwrap_or_else(|| marker.phase.clone());
+
+        let overall = match index {
+            Some(i) => {
+                let total: u32 = self.phases.iter().map(|p| p.weight).sum();
+                let done: u32 = self.phases[..i].iter().map(|p| p.weight).sum();
+                let current = self.phases[i].weight as f64 * marker.percent.unwrap_or(0.0) / 100.0;
+                100.0 * (done as f64 + current) / total.max(1) as f64
+            }
+            None if marker.total > 0 => {
+                let within = marker.percent.unwrap_or(0.0) / 100.0;
+                100.0 * (marker.step.saturating_sub(1) as f64 + within) / marker.total as f64
+            }
+            None => self.overall,
+        };
+        self.overall = self.overall.max(overall.min(100.0));
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        // Too little progress makes for wild estimates
+        let eta_secs = (self.overall >= 2.0 && self.overall < 100.0)
+            .then(|| (elapsed * (100.0 - self.overall) / self.overall).round() as u64);
+
+        ProgressEvent {
+            job_id: self.job_id,
+            vm: self.vm.clone(),
+            phase: marker.phase.clone(),
+            phase_name,
+            step,
+            total_steps,
+            phase_percent: marker.percent,
+            overall_percent: (self.overall * 10.0).round() / 10.0,
+            message: marker.message.clone(),
+            elapsed_secs: elapsed as u64,
+            eta_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(phase: &str, percent: Option<f64>) -> ProgressMarker {
+        ProgressMarker {
+            phase: phase.to_string(),
+            step: 0,
+            total: 0,
+            percent,
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn parse_marker_reads_well_formed_lines() {
+        assert_eq!(
+            parse_marker("  ##PROGRESS|apply_image|3|8|42.5|Applying image | index 6\r\n"),
+            Some(ProgressMarker {
+                phase: "apply_image".into(),
+                step: 3,
+                total: 8,
+                percent: Some(42.5),
+                message: "Applying image | index 6".into(),
+            })
+        );
+        // -1 means no finer progress, and the message is optional
+        let m = parse_marker("##PROGRESS|drivers|0|0|-1").unwrap();
+        assert_eq!(m.percent, None);
+        assert_eq!(m.message, "");
+        assert_eq!(
+            parse_marker("##PROGRESS|drivers|0|0|250|x")
+                .unwrap()
+                .percent,
+            Some(100.0)
+        );
+    }
+
+    #[test]
+    fn parse_marker_rejects_malformed_lines() {
+        for line in [
+            "",
+            "Copying files...",
+            "PROGRESS|drivers|0|0|10|x",
+            "##PROGRESS|",
+            "##PROGRESS||1|8|10|x",
+            "##PROGRESS|drivers",
+            "##PROGRESS|drivers|one|8|10|x",
+            "##PROGRESS|drivers|1|-8|10|x",
+            "##PROGRESS|drivers|1|8",
+        ] {
+            assert_eq!(parse_marker(line), None, "{line:?}");
+        }
+        // An unparsable percent only loses the percent
+        assert_eq!(
+            parse_marker("##PROGRESS|drivers|1|8|n/a|x")
+                .unwrap()
+                .percent,
+            None
+        );
+    }
+
+    #[test]
+    fn overall_percent_is_weighted_by_phase() {
+        let total: u32 = PROVISION_PHASES.iter().map(|p| p.weight).sum();
+        let weight = |id: &str| PROVISION_PHASES.iter().find(|p| p.id == id).unwrap().weight;
+        let before = |id: &str| {
+            PROVISION_PHASES
+                .iter()
+                .take_while(|p| p.id != id)
+                .map(|p| p.weight)
+                .sum::<u32>()
+        };
+        let percent = |done: f64| (1000.0 * done / total as f64).round() / 10.0;
+
+        let mut tracker = ProgressTracker::new(7, "Gaming", PROVISION_PHASES);
+        let event = tracker.update(&marker("prepare", None));
+        assert_eq!(
+            (event.step, event.total_steps),
+            (1, PROVISION_PHASES.len() as u32)
+        );
+        assert_eq!(event.phase_name, "Checking parameters");
+        assert_eq!(event.overall_percent, 0.0);
+        assert_eq!(event.eta_secs, None);
+
+        let event = tracker.update(&marker("apply_image", Some(50.0)));
+        assert_eq!(event.step, 4);
+        assert_eq!(
+            event.overall_percent,
+            percent(before("apply_image") as f64 + weight("apply_image") as f64 / 2.0)
+        );
+        assert!(event.eta_secs.is_some());
+
+        let event = tracker.update(&marker("start_vm", Some(100.0)));
+        assert_eq!(event.overall_percent, 100.0);
+        assert_eq!(event.eta_secs, None);
+    }
+
+    #[test]
+    fn overall_percent_never_goes_backwards() {
+        let mut tracker = ProgressTracker::new(1, "Gaming", PROVISION_PHASES);
+        let ahead = tracker
+            .update(&marker("drivers", Some(10.0)))
+            .overall_percent;
+        let event = tracker.update(&marker("create_disk", Some(90.0)));
+        assert_eq!(event.overall_percent, ahead);
+        assert_eq!(event.phase, "create_disk");
+        assert_eq!(event.step, 3);
+        // A phase-less marker keeps the overall progress where it was
+        let event = tracker.update(&marker("driver_module", Some(30.0)));
+        assert_eq!(event.overall_percent, ahead);
+        assert_eq!(event.phase_name, "driver_module");
+    }
+
+    #[test]
+    fn unknown_phases_use_their_own_step_counts() {
+        let mut tracker = ProgressTracker::new(1, "Gaming", PROVISION_PHASES);
+        let event = tracker.update(&ProgressMarker {
+            phase: "update_drivers".into(),
+            step: 2,
+            total: 4,
+            percent: Some(50.0),
+            message: "Copying".into(),
+        });
+        assert_eq!((event.step, event.total_steps), (2, 4));
+        assert_eq!(event.overall_percent, 37.5);
+        assert_eq!(event.phase_percent, Some(50.0));
+        assert_eq!(event.message, "Copying");
+    }
+}
@@ -0,0 +1,446 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::config::VMSettingsStore;
+use super::error::HyperVError;
+use super::executor::PowerShellExecutor;
+use super::script::ScriptBuilder;
+
+/// Prefix of the lines CopyFilesToVM.ps1 prints once it has created
+/// something that must be undone if provisioning does not finish:
+/// `##RESOURCE|<kind>|<value>`
+pub const RESOURCE_MARKER: &str = "##RESOURCE|";
+
+/// Files of a staging directory that can carry credentials
+pub const CREDENTIAL_FILES: [&str; 2] = ["autounattend.xml", "CopyFilesToVM.ps1"];
+
+/// Something a provisioning run created, in the order it was created
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum ProvisionResource {
+    /// Local staging directory holding the rendered scripts and answer file
+    Staging(PathBuf),
+    /// Copy of the staging directory on a remote host
+    RemoteStaging(String),
+    /// Installation ISO mounted on the host
+    IsoMount(String),
+    /// Virtual disk created for the VM
+    Vhd(String),
+    /// VM registered with Hyper-V
+    Vm(String),
+    /// Local key protector and vTPM of the VM
+    KeyProtector(String),
+}
+
+impl ProvisionResource {
+    /// Parse a `##RESOURCE|...` line; anything else is `None`
+    pub fn parse_marker(line: &str) -> Option<Self> {
+        let (kind, value) = line.trim().strip_prefix(RESOURCE_MARKER)?.split_once('|')?;
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            return None;
+        }
+        match kind.trim() {
+            "iso_mount" => Some(ProvisionResource::IsoMount(value)),
+            "vhd" => Some(ProvisionResource::Vhd(value)),
+            "vm" => Some(ProvisionResource::Vm(value)),
+            "key_protector" => Some(ProvisionResource::KeyProtector(value)),
+            _ => None,
+        }
+    }
+
+    /// Only needed while the run is in progress; always undone when it stops,
+    /// even if a checkpoint was reached after it was created
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ProvisionResource::Staging(_)
+                | ProvisionResource::RemoteStaging(_)
+                | ProvisionResource::IsoMount(_)
+        )
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            ProvisionResource::Staging(path) => format!("staging directory {}", path.display()),
+            ProvisionResource::RemoteStaging(path) => format!("remote staging directory {}", path),
+            ProvisionResource::IsoMount(path) => format!("ISO mount {}", path),
+            ProvisionResource::Vhd(path) => format!("virtual disk {}", path),
+            ProvisionResource::Vm(name) => format!("VM '{}'", name),
+            ProvisionResource::KeyProtector(name) => format!("key protector of VM '{}'", name),
+        }
+    }
+}
+
+/// Outcome of undoing one resource
+#[derive(Serialize, Clone, Debug)]
+pub struct RollbackStep {
+    pub resource: ProvisionResource,
+    pub cleaned: bool,
+    /// Why the resource could not be cleaned
+    pub error: Option<HyperVError>,
+}
+
+/// What a rollback cleaned and what it left behind
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RollbackReport {
+    pub steps: Vec<RollbackStep>,
+}
+
+impl RollbackReport {
+    pub fn failed(&self) -> impl Iterator<Item = &RollbackStep> {
+        self.steps.iter().filter(|s| !s.cleaned)
+    }
+
+    /// One line summary for error hints
+    pub fn summary(&self) -> String {
+        let failed: Vec<String> = self.failed().map(|s| s.resource.describe()).collect();
+        let cleaned = self.steps.len() - failed.len();
+        if failed.is_empty() {
+            format!(
+                "Rolled back {} resource(s) created during provisioning.",
+                cleaned
+            )
+        } else {
+            format!(
+                "Rolled back {} resource(s); could not clean up: {}. Remove them manually.",
+                cleaned,
+                failed.join(", ")
+            )
+        }
+    }
+}
+
+/// Undo `resources` newest first, reporting each step through `log`.
+/// Settings stored for a removed VM are dropped from `settings` as well.
+pub fn rollback(
+    ps: &dyn PowerShellExecutor,
+    settings: &VMSettingsStore,
+    resources: &[ProvisionResource],
+    log: impl Fn(String),
+) -> RollbackReport {
+    let mut report = RollbackReport::default();
+    for resource in resources.iter().rev() {
+        let result = undo(ps, resource).and_then(|()| match resource {
+            ProvisionResource::Vm(name) => settings.remove(name).map_err(HyperVError::internal),
+            _ => Ok(()),
+        });
+        match &result {
+            Ok(()) => log(format!("[ROLLBACK] Removed {}", resource.describe())),
+            Err(e) => log(format!(
+                "[ROLLBACK] Could not remove {}: {}",
+                resource.describe(),
+                e
+            )),
+        }
+        report.steps.push(RollbackStep {
+            resource: resource.clone(),
+            cleaned: result.is_ok(),
+            error: result.err(),
+        });
+    }
+    report
+}
+
+/// Directory holding the local staging directory of every provisioning run
+pub fn staging_root() -> PathBuf {
+    env::temp_dir().join("HyperV_GPU_Provisioning")
+}
+
+/// Overwrite the credential files of staging directory `dir` with zeros,
+/// then remove the directory. Anything but a direct child of
+/// `staging_root` is refused.
+pub fn scrub_staging_dir(dir: &Path) -> io::Result<()> {
+    if dir.parent() != Some(staging_root().as_path()) || dir.file_name().is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a staging directory", dir.display()),
+        ));
+    }
+    for name in CREDENTIAL_FILES {
+        let path = dir.join(name);
+        let len = match fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let mut file = fs::OpenOptions::new().write(true).open(&path)?;
+        file.write_all(&vec![0; len as usize])?;
+        file.sync_all()?;
+    }
+    match fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Undo a single resource; staging directories are scrubbed before removal
+pub fn undo(ps: &dyn PowerShellExecutor, resource: &ProvisionResource) -> Result<(), HyperVError> {
+    let script = match resource {
+        ProvisionResource::Staging(path) => {
+            return scrub_staging_dir(path).map_err(|e| {
+                HyperVError::internal(format!("Failed to remove {}: {}", path.display(), e))
+            });
+        }
+        ProvisionResource::RemoteStaging(path) => ScriptBuilder::new()
+            .arg("Path", path)
+            .arg("Files", &CREDENTIAL_FILES.join("|"))
+            .build(
+                r#"
+            if (Test-Path -LiteralPath $Path) {
+                foreach ($Name in $Files -split '\|') {
+                    $File = Join-Path $Path $Name
+                    if (-not (Test-Path -LiteralPath $File)) { continue }
+                    $Stream = [IO.File]::OpenWrite($File)
+                    try {
+                        $Stream.Write((New-Object byte[] $Stream.Length), 0, $Stream.Length)
+                        $Stream.Flush($true)
+                    } finally {
+                        $Stream.Dispose()
+                    }
+                }
+                Remove-Item -LiteralPath $Path -Recurse -Force
+            }
+            "#,
+            ),
+        ProvisionResource::IsoMount(path) => ScriptBuilder::new().arg("Path", path).build(
+            r#"
+            $image = Get-DiskImage -ImagePath $Path -ErrorAction SilentlyContinue
+            if ($image -and $image.Attached) { Dismount-DiskImage -ImagePath $Path | Out-Null }
+            "#,
+        ),
+        // Killed mid-apply, Convert-WindowsImage leaves the disk attached
+        ProvisionResource::Vhd(path) => ScriptBuilder::new().arg("Path", path).build(
+            r#"
+            if (Test-Path -LiteralPath $Path) {
+                Dismount-VHD -Path $Path -ErrorAction SilentlyContinue
+                Dismount-DiskImage -ImagePath $Path -ErrorAction SilentlyContinue | Out-Null
+                Remove-Item -LiteralPath $Path -Force
+            }
+            "#,
+        ),
+        ProvisionResource::Vm(name) => ScriptBuilder::new()
+            .with_get_vm_exact()
+            .arg("VMName", name)
+            .build(
+                r#"
+            $vm = Get-VMExact $VMName -ErrorAction SilentlyContinue
+            if ($vm) {
+                if ($vm.State -ne 'Off') { Stop-VM -VM $vm -TurnOff -Force }
+                Remove-VM -VM $vm -Force
+            }
+            "#,
+            ),
+        ProvisionResource::KeyProtector(name) => ScriptBuilder::new()
+            .with_get_vm_exact()
+            .arg("VMName", name)
+            .build(
+                r#"
+            $vm = Get-VMExact $VMName -ErrorAction SilentlyContinue
+            if ($vm) {
+                if ($vm.State -ne 'Off') { Stop-VM -VM $vm -TurnOff -Force }
+                if ((Get-VMSecurity -VM $vm).TpmEnabled) { Disable-VMTPM -VM $vm }
+            }
+            "#,
+            ),
+    };
+    ps.run(&script).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::config::VMConnectionSettings;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+    use std::sync::Mutex;
+
+    #[test]
+    fn parse_marker_reads_script_resources() {
+        let cases = [
+            (
+                "##RESOURCE|iso_mount|D:\\win11.iso",
+                ProvisionResource::IsoMount("D:\\win11.iso".to_string()),
+            ),
+            (
+                "##RESOURCE|vhd|C:\\VMs\\Gaming.vhdx",
+                ProvisionResource::Vhd("C:\\VMs\\Gaming.vhdx".to_string()),
+            ),
+            (
+                "  ##RESOURCE|vm| Gaming  ",
+                ProvisionResource::Vm("Gaming".to_string()),
+            ),
+            (
+                "##RESOURCE|key_protector|Gaming",
+                ProvisionResource::KeyProtector("Gaming".to_string()),
+            ),
+            // Only the first separator splits; values may hold more
+            (
+                "##RESOURCE|vm|a|b",
+                ProvisionResource::Vm("a|b".to_string()),
+            ),
+        ];
+        for (line, resource) in cases {
+            assert_eq!(
+                ProvisionResource::parse_marker(line),
+                Some(resource),
+                "{}",
+                line
+            );
+        }
+        for line in [
+            "##RESOURCE|vm|",
+            "##RESOURCE|vm",
+            "##RESOURCE|staging|C:\\Temp",
+            "##RESOURCE|remote_staging|C:\\Temp",
+            "##RESOURCE|disk|C:\\x.vhdx",
+            "RESOURCE|vm|Gaming",
+            "Created vm Gaming",
+        ] {
+            assert_eq!(ProvisionResource::parse_marker(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn describe_names_each_resource() {
+        assert_eq!(
+            ProvisionResource::Vm("Gaming".to_string()).describe(),
+            "VM 'Gaming'"
+        );
+        assert_eq!(
+            ProvisionResource::Vhd("C:\\VMs\\Gaming.vhdx".to_string()).describe(),
+            "virtual disk C:\\VMs\\Gaming.vhdx"
+        );
+        assert_eq!(
+            ProvisionResource::KeyProtector("Gaming".to_string()).describe(),
+            "key protector of VM 'Gaming'"
+        );
+        assert_eq!(
+            ProvisionResource::RemoteStaging("C:\\Temp\\x".to_string()).describe(),
+            "remote staging directory C:\\Temp\\x"
+        );
+    }
+
+    fn settings(name: &str) -> (VMSettingsStore, PathBuf) {
+        let path =
+            std::env::temp_dir().join(format!("rollback-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let store = VMSettingsStore::at(&path);
+        store
+            .set("Gaming".to_string(), VMConnectionSettings::default())
+            .unwrap();
+        (store, path)
+    }
+
+    fn resources() -> Vec<ProvisionResource> {
+        vec![
+            ProvisionResource::IsoMount("D:\\win11.iso".to_string()),
+            ProvisionResource::Vhd("C:\\VMs\\Gaming.vhdx".to_string()),
+            ProvisionResource::Vm("Gaming".to_string()),
+            ProvisionResource::KeyProtector("Gaming".to_string()),
+        ]
+    }
+
+    #[test]
+    fn rollback_undoes_newest_first() {
+        let ps = ScriptedExecutor::new(vec![
+            ScriptRule::ok("Disable-VMTPM", ""),
+            ScriptRule::ok("Remove-VM", ""),
+            ScriptRule::ok("Dismount-VHD", ""),
+            ScriptRule::ok("Dismount-DiskImage", ""),
+        ]);
+        let (settings, path) = settings("order");
+        let lines = Mutex::new(Vec::new());
+        let report = rollback(&ps, &settings, &resources(), |line| {
+            lines.lock().unwrap().push(line)
+        });
+
+        let calls = ps.calls();
+        let order = [
+            "Disable-VMTPM",
+            "Remove-VM",
+            "Dismount-VHD",
+            "Get-DiskImage",
+        ];
+        assert_eq!(calls.len(), order.len());
+        for (call, cmdlet) in calls.iter().zip(order) {
+            assert!(call.contains(cmdlet), "{} not in {}", cmdlet, call);
+        }
+        assert!(report.failed().next().is_none());
+        assert_eq!(
+            report.summary(),
+            "Rolled back 4 resource(s) created during provisioning."
+        );
+        assert_eq!(
+            lines.into_inner().unwrap()[1],
+            "[ROLLBACK] Removed VM 'Gaming'"
+        );
+        // The settings of the removed VM go with it
+        assert!(!fs::read_to_string(&path).unwrap().contains("Gaming"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rollback_reports_partial_failure() {
+        let ps = ScriptedExecutor::new(vec![
+            ScriptRule::ok("Disable-VMTPM", ""),
+            ScriptRule::fail(
+                "Remove-VM",
+                "Remove-VM : You do not have the required permission to complete this task.\n    + CategoryInfo          : PermissionDenied: (:) [Remove-VM], VirtualizationException",
+                1,
+            ),
+            ScriptRule::ok("Dismount-VHD", ""),
+            ScriptRule::ok("Dismount-DiskImage", ""),
+        ]);
+        let (settings, path) = settings("partial");
+        let report = rollback(&ps, &settings, &resources(), |_| {});
+
+        // Every resource is attempted; the failure keeps its classification
+        assert_eq!(ps.calls().len(), 4);
+        let failed: Vec<_> = report.failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(
+            failed[0].resource,
+            ProvisionResource::Vm("Gaming".to_string())
+        );
+        assert!(matches!(failed[0].error, Some(HyperVError::NotElevated(_))));
+        assert_eq!(
+            report.summary(),
+            "Rolled back 3 resource(s); could not clean up: VM 'Gaming'. Remove them manually."
+        );
+        // The VM is still there, and so are its settings
+        assert!(fs::read_to_string(&path).unwrap().contains("Gaming"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn scrub_zeroes_credentials_before_removing() {
+        let dir = staging_root().join(format!("scrub-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("drivers")).unwrap();
+        for name in CREDENTIAL_FILES {
+            fs::write(dir.join(name), "password").unwrap();
+        }
+        fs::write(dir.join("drivers").join("nv.inf"), "driver").unwrap();
+        // A second link to the answer file outlives the directory
+        let link = std::env::temp_dir().join(format!("scrub-link-{}", std::process::id()));
+        let _ = fs::remove_file(&link);
+        fs::hard_link(dir.join("autounattend.xml"), &link).unwrap();
+
+        let ps = ScriptedExecutor::new(Vec::new());
+        undo(&ps, &ProvisionResource::Staging(dir.clone())).unwrap();
+        assert!(!dir.exists());
+        assert_eq!(fs::read(&link).unwrap(), vec![0; "password".len()]);
+        assert!(ps.calls().is_empty());
+        // Already gone is fine
+        scrub_staging_dir(&dir).unwrap();
+        fs::remove_file(&link).unwrap();
+
+        assert!(matches!(
+            undo(&ps, &ProvisionResource::Staging(std::env::temp_dir())),
+            Err(HyperVError::Internal(_))
+        ));
+    }
+}
@@ -0,0 +1,519 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State, Window};
+
+use super::audit::redact;
+use super::error::HyperVError;
+use super::executor::ExecutorState;
+use super::utils::{hex_file_name, now_ms};
+
+/// Runs kept per VM; the oldest are deleted when a new one starts
+const MAX_RUNS_PER_VM: usize = 20;
+
+/// Finished runs older than this are deleted when a new one starts
+const MAX_RUN_AGE_MS: u64 = 30 * 24 * 60 * 60 * 1000;
+
+/// Log size after which a run's further output is dropped
+const MAX_RUN_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Where a logged line came from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogStream {
+    /// Written by the app itself, e.g. warnings and rollback steps
+    App,
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    /// `line` as shown in a live log, where the stream is not a field
+    pub fn display(self, line: &str) -> String {
+        match self {
+            LogStream::Stderr => format!("[ERROR] {}", line),
+            _ => line.to_string(),
+        }
+    }
+}
+
+/// One line of a persisted run log
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunLogLine {
+    /// Unix time in milliseconds
+    pub at: u64,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// Still marked running when the next run of the VM started, i.e. the
+    /// app exited or crashed mid-run
+    Interrupted,
+}
+
+/// One `create_vm` or `update_vm_config` run of a VM
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunSummary {
+    pub id: String,
+    pub vm: String,
+    /// Remote host profile the run targeted; `None` for this machine
+    pub host: Option<String>,
+    /// Command that started the run, e.g. `create_vm`
+    pub operation: String,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub status: RunStatus,
+    pub error: Option<String>,
+    /// Output past `MAX_RUN_LOG_BYTES` was dropped
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Per-VM history of operation logs: `<id>.jsonl` holds a run's lines and
+/// `<id>.json` its summary
+pub struct RunLogStore {
+    dir: PathBuf,
+}
+
+impl RunLogStore {
+    pub fn new(app: &AppHandle) -> Self {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join("logs");
+        Self::at(dir)
+    }
+
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Names are encoded, as they come from the frontend and host profiles
+    fn vm_dir(&self, host: Option<&str>, vm: &str) -> PathBuf {
+        let dir = match host {
+            Some(host) => self.dir.join("hosts").join(hex_file_name(host)),
+            None => self.dir.join("local"),
+        };
+        // Hyper-V VM names are case-insensitive
+        dir.join(hex_file_name(&vm.to_lowercase()))
+    }
+
+    /// Open the log of a new `operation` run of `vm`. Runs still marked
+    /// running are flagged as interrupted, since the VM lock allows only one
+    /// at a time, and runs beyond the retention limits are deleted.
+    /// A log that cannot be written is reported and the run goes unlogged.
+    pub fn start(&self, host: Option<&str>, vm: &str, operation: &str) -> RunLog {
+        let started_at = now_ms();
+        let summary = RunSummary {
+            id: format!("{}-{}", started_at, operation),
+            vm: vm.to_string(),
+            host: host.map(str::to_string),
+            operation: operation.to_string(),
+            started_at,
+            ended_at: None,
+            status: RunStatus::Running,
+            error: None,
+            truncated: false,
+        };
+        let dir = self.vm_dir(host, vm);
+        let file = match self.open_run(&dir, &summary) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Failed to open run log for {}: {}", vm, e);
+                None
+            }
+        };
+        RunLog {
+            dir,
+            secrets: Vec::new(),
+            state: Mutex::new(RunState {
+                file,
+                bytes: 0,
+                summary,
+            }),
+        }
+    }
+
+    fn open_run(&self, dir: &Path, summary: &RunSummary) -> std::io::Result<File> {
+        fs::create_dir_all(dir)?;
+        for mut stale in read_summaries(dir)
+            .into_iter()
+            .filter(|s| s.status == RunStatus::Running)
+        {
+            stale.status = RunStatus::Interrupted;
+            write_summary(dir, &stale)?;
+        }
+        prune(dir, summary.started_at)?;
+
+        write_summary(dir, summary)?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("{}.jsonl", summary.id)))
+    }
+
+    /// Runs of `vm`, newest first
+    pub fn list(&self, host: Option<&str>, vm: &str) -> Vec<RunSummary> {
+        read_summaries(&self.vm_dir(host, vm))
+    }
+
+    /// Lines of run `id` of `vm`
+    pub fn read(
+        &self,
+        host: Option<&str>,
+        vm: &str,
+        id: &str,
+    ) -> Result<Vec<RunLogLine>, HyperVError> {
+        // Ids are generated by `start`; anything else could escape the directory
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(HyperVError::invalid_input(format!(
+                "Invalid run id: {}",
+                id
+            )));
+        }
+        let path = self.vm_dir(host, vm).join(format!("{}.jsonl", id));
+        let file = File::open(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                HyperVError::invalid_input(format!("Unknown run of '{}': {}", vm, id))
+            }
+            _ => HyperVError::internal(format!("Failed to read {}: {}", path.display(), e)),
+        })?;
+        let mut lines = Vec::new();
+        for line in BufReader::new(file).lines() {
+            // Skip lines torn by a crash mid-write
+            if let Ok(line) = serde_json::from_str::<RunLogLine>(&line?) {
+                lines.push(line);
+            }
+        }
+        Ok(lines)
+    }
+}
+
+fn read_summaries(dir: &Path) -> Vec<RunSummary> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut summaries: Vec<RunSummary> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .filter_map(|p| serde_json::from_str(&fs::read_to_string(p).ok()?).ok())
+        .collect();
+    summaries.sort_by_key(|s| Reverse(s.started_at));
+    summaries
+}
+
+fn write_summary(dir: &Path, summary: &RunSummary) -> std::io::Result<()> {
+    let content = serde_json::to_string_pretty(summary)?;
+    fs::write(dir.join(format!("{}.json", summary.id)), content)
+}
+
+/// Delete finished runs older than `MAX_RUN_AGE_MS` and the oldest runs
+/// beyond `MAX_RUNS_PER_VM`, leaving room for the one about to start
+fn prune(dir: &Path, now: u64) -> std::io::Result<()> {
+    for (index, summary) in read_summaries(dir).iter().enumerate() {
+        let expired = summary.status != RunStatus::Running
+            && now.saturating_sub(summary.started_at) > MAX_RUN_AGE_MS;
+        if expired || index + 1 >= MAX_RUNS_PER_VM {
+            for extension in ["jsonl", "json"] {
+                let path = dir.join(format!("{}.{}", summary.id, extension));
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct RunState {
+    /// `None` once the log could not be opened or written
+    file: Option<File>,
+    bytes: u64,
+    summary: RunSummary,
+}
+
+/// Writer of one run's log, shared by the threads reading its output
+pub struct RunLog {
+    dir: PathBuf,
+    secrets: Vec<String>,
+    state: Mutex<RunState>,
+}
+
+impl RunLog {
+    /// Redact `secret` from every line written to this log
+    pub fn secret(mut self, secret: Option<&str>) -> Self {
+        if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+            self.secrets.push(secret.to_string());
+        }
+        self
+    }
+
+    /// Append a timestamped line; past `MAX_RUN_LOG_BYTES` one note is
+    /// written instead and the rest of the output is dropped
+    pub fn write(&self, stream: LogStream, line: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.summary.truncated {
+            return;
+        }
+        let entry = if state.bytes < MAX_RUN_LOG_BYTES {
+            RunLogLine {
+                at: now_ms(),
+                stream,
+                line: redact(line, &self.secrets),
+            }
+        } else {
+            state.summary.truncated = true;
+            RunLogLine {
+                at: now_ms(),
+                stream: LogStream::App,
+                line: format!(
+                    "[WARN] Log reached {} MB; further output is not saved",
+                    MAX_RUN_LOG_BYTES / (1024 * 1024)
+                ),
+            }
+        };
+        let Some(file) = state.file.as_mut() else {
+            return;
+        };
+        let mut json = serde_json::to_string(&entry).unwrap_or_default();
+        json.push('\n');
+        if let Err(e) = file.write_all(json.as_bytes()) {
+            eprintln!("Failed to write run log: {}", e);
+            state.file = None;
+            return;
+        }
+        state.bytes += json.len() as u64;
+    }
+
+    /// Record the outcome of the run
+    pub fn finish(&self, status: RunStatus, error: Option<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.summary.status = status;
+        state.summary.ended_at = Some(now_ms());
+        state.summary.error = error.map(|e| redact(&e, &self.secrets));
+        if state.file.is_some() {
+            if let Err(e) = write_summary(&self.dir, &state.summary) {
+                eprintln!("Failed to write run summary: {}", e);
+            }
+        }
+    }
+
+    /// Record the outcome of a run that returned `result`
+    pub fn finish_with<T>(&self, result: &Result<T, HyperVError>) {
+        let status = match result {
+            Ok(_) => RunStatus::Succeeded,
+            Err(HyperVError::Cancelled(_)) => RunStatus::Cancelled,
+            Err(_) => RunStatus::Failed,
+        };
+        self.finish(
+            status,
+            result.as_ref().err().map(|e| e.message().to_string()),
+        );
+    }
+}
+
+/// Past `create_vm` and `update_vm_config` runs of a VM on the active host,
+/// newest first
+#[tauri::command]
+pub async fn list_vm_runs(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    name: String,
+) -> Result<Vec<RunSummary>, HyperVError> {
+    let store = RunLogStore::new(window.app_handle());
+    let host = ps.host().map(|h| h.name);
+    Ok(store.list(host.as_deref(), &name))
+}
+
+/// Persisted log of one run listed by `list_vm_runs`
+#[tauri::command]
+pub async fn get_vm_run_log(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    name: String,
+    run_id: String,
+) -> Result<Vec<RunLogLine>, HyperVError> {
+    let store = RunLogStore::new(window.app_handle());
+    let host = ps.host().map(|h| h.name);
+    store.read(host.as_deref(), &name, &run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Empty store in a directory of its own
+    fn store(name: &str) -> (RunLogStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("runlog-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        (RunLogStore::at(&dir), dir)
+    }
+
+    fn summary(id: &str, started_at: u64, status: RunStatus) -> RunSummary {
+        RunSummary {
+            id: id.to_string(),
+            vm: "Gaming".to_string(),
+            host: None,
+            operation: "create_vm".to_string(),
+            started_at,
+            ended_at: None,
+            status,
+            error: None,
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn runs_are_logged_and_listed() {
+        let (store, dir) = store("roundtrip");
+        let run = store
+            .start(None, "Gaming", "create_vm")
+            .secret(Some("hunter22"));
+        run.write(LogStream::Stdout, "Copying drivers");
+        run.write(LogStream::Stderr, "password is hunter22");
+        run.finish_with::<()>(&Err(HyperVError::powershell("hunter22 rejected")));
+
+        // VM names are case-insensitive, hosts are kept apart
+        let runs = store.list(None, "GAMING");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Failed);
+        assert_eq!(runs[0].error.as_deref(), Some("******** rejected"));
+        assert!(runs[0].ended_at.is_some());
+        assert!(store.list(Some("lab"), "Gaming").is_empty());
+
+        let lines = store.read(None, "gaming", &runs[0].id).unwrap();
+        let lines: Vec<_> = lines.iter().map(|l| (l.stream, l.line.as_str())).collect();
+        assert_eq!(
+            lines,
+            [
+                (LogStream::Stdout, "Copying drivers"),
+                (LogStream::Stderr, "password is ********"),
+            ]
+        );
+
+        for id in ["", "../x", "a/b", "a.b"] {
+            assert!(matches!(
+                store.read(None, "Gaming", id),
+                Err(HyperVError::InvalidInput(_))
+            ));
+        }
+        assert!(matches!(
+            store.read(None, "Gaming", "123-create_vm"),
+            Err(HyperVError::InvalidInput(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names_stay_inside_the_store() {
+        let (store, dir) = store("names");
+        let run = store.start(Some("../.."), "..\\../../escape", "create_vm");
+        run.finish(RunStatus::Succeeded, None);
+        let vm_dir = store.vm_dir(Some("../.."), "..\\../../escape");
+        assert_eq!(
+            vm_dir.parent().and_then(Path::parent),
+            Some(&*dir.join("hosts"))
+        );
+        assert_eq!(store.list(Some("../.."), "..\\../../escape").len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn running_runs_become_interrupted() {
+        let (store, dir) = store("interrupted");
+        let crashed = store.start(None, "Gaming", "create_vm");
+        crashed.write(LogStream::App, "Starting");
+        thread::sleep(Duration::from_millis(2));
+        let run = store.start(None, "Gaming", "update_vm_config");
+
+        let runs = store.list(None, "Gaming");
+        let statuses: Vec<_> = runs
+            .iter()
+            .map(|r| (r.operation.as_str(), r.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("update_vm_config", RunStatus::Running),
+                ("create_vm", RunStatus::Interrupted),
+            ]
+        );
+        run.finish(RunStatus::Succeeded, None);
+        assert_eq!(store.list(None, "Gaming")[0].status, RunStatus::Succeeded);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_applies_count_and_age_limits() {
+        let (_, dir) = store("prune");
+        fs::create_dir_all(&dir).unwrap();
+        let now = 100 * MAX_RUN_AGE_MS;
+        for i in 0..MAX_RUNS_PER_VM as u64 + 5 {
+            let summary = summary(&format!("run{}", i), now - 1000 + i, RunStatus::Succeeded);
+            write_summary(&dir, &summary).unwrap();
+            fs::write(dir.join(format!("run{}.jsonl", i)), "").unwrap();
+        }
+        prune(&dir, now).unwrap();
+        // The newest runs are kept, leaving room for one more
+        let ids: Vec<_> = read_summaries(&dir).into_iter().map(|s| s.id).collect();
+        let expected: Vec<_> = (6..MAX_RUNS_PER_VM as u64 + 5)
+            .rev()
+            .map(|i| format!("run{}", i))
+            .collect();
+        assert_eq!(ids, expected);
+        assert!(!dir.join("run5.jsonl").exists());
+        assert!(dir.join("run6.jsonl").exists());
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Age alone never removes a run still marked running
+        fs::create_dir_all(&dir).unwrap();
+        let old = now - MAX_RUN_AGE_MS - 1;
+        write_summary(&dir, &summary("recent", now - 1000, RunStatus::Failed)).unwrap();
+        write_summary(&dir, &summary("expired", old, RunStatus::Failed)).unwrap();
+        write_summary(&dir, &summary("stuck", old - 1, RunStatus::Running)).unwrap();
+        prune(&dir, now).unwrap();
+        let ids: Vec<_> = read_summaries(&dir).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, ["recent", "stuck"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_past_the_limit_is_dropped() {
+        let (store, dir) = store("truncated");
+        let run = store.start(None, "Gaming", "create_vm");
+        let chunk = "x".repeat(1024 * 1024);
+        for _ in 0..8 {
+            run.write(LogStream::Stdout, &chunk);
+        }
+        run.finish(RunStatus::Succeeded, None);
+
+        let summary = &store.list(None, "Gaming")[0];
+        assert!(summary.truncated);
+        let lines = store.read(None, "Gaming", &summary.id).unwrap();
+        assert_eq!(lines.len() as u64, MAX_RUN_LOG_BYTES / (1024 * 1024) + 1);
+        let last = lines.last().unwrap();
+        assert_eq!(last.stream, LogStream::App);
+        assert!(last.line.contains("further output is not saved"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
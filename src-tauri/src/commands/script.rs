@@ -0,0 +1,247 @@
+/// Characters PowerShell treats as a single quote inside a single-quoted string
+const SINGLE_QUOTES: [char; 5] = ['\'', '\u{2018}', '\u{2019}', '\u{201A}', '\u{201B}'];
+
+/// Quote a value as a PowerShell single-quoted (verbatim) string literal.
+/// Nothing is expanded inside single quotes, so only the quote characters
+/// themselves need escaping, which is done by doubling them.
+pub fn ps_literal(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        if SINGLE_QUOTES.contains(&c) {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Helper added by `ScriptBuilder::with_get_vm_exact` for exact-name VM lookups.
+/// `Get-VM -Name` treats its argument as a wildcard pattern, so it is escaped first.
+const GET_VM_EXACT: &str =
+    "function Get-VMExact { [CmdletBinding()] param([string]$Name) Get-VM -Name ([WildcardPattern]::Escape($Name)) }";
+
+/// Builds a script where user supplied values are bound to variables as
+/// escaped literals instead of being spliced into the command text.
+#[derive(Default)]
+pub struct ScriptBuilder {
+    get_vm_exact: bool,
+    bindings: Vec<String>,
+}
+
+impl ScriptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define `Get-VMExact`, a `Get-VM -Name` that takes the name literally
+    pub fn with_get_vm_exact(mut self) -> Self {
+        self.get_vm_exact = true;
+        self
+    }
+
+    /// Bind a string value to `$name`
+    pub fn arg(self, name: &str, value: &str) -> Self {
+        self.bind(name, ps_literal(value))
+    }
+
+    /// Bind an integer value to `$name`
+    pub fn int(self, name: &str, value: i64) -> Self {
+        self.bind(name, value.to_string())
+    }
+
+    /// Bind a boolean value to `$name`
+    pub fn bool(self, name: &str, value: bool) -> Self {
+        self.bind(name, if value { "$true" } else { "$false" }.to_string())
+    }
+
+    fn bind(mut self, name: &str, literal: String) -> Self {
+        assert!(
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "invalid PowerShell variable name: {}",
+            name
+        );
+        self.bindings.push(format!("${} = {}", name, literal));
+        self
+    }
+
+    /// Produce the final script: variable bindings followed by `body`.
+    /// `body` must only refer to bound values through their variables.
+    pub fn build(self, body: &str) -> String {
+        let mut script = String::new();
+        if self.get_vm_exact {
+            script.push_str(GET_VM_EXACT);
+            script.push('\n');
+        }
+        for binding in &self.bindings {
+            script.push_str(binding);
+            script.push('\n');
+        }
+        script.push_str(body);
+        script
+    }
+}
+
+/// Encode a script for `powershell -EncodedCommand` (Base64 of UTF-16LE).
+/// This keeps the script away from command-line quoting rules entirely.
+pub fn encode_command(script: &str) -> String {
+    let bytes: Vec<u8> = script
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    base64_encode(&bytes)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        encoded.push(ALPHABET[(n >> 18) as usize & 63] as char);
+        encoded.push(ALPHABET[(n >> 12) as usize & 63] as char);
+        encoded.push(if chunk.len() > 1 {
+            ALPHABET[(n >> 6) as usize & 63] as char
+        } else {
+            '='
+        });
+        encoded.push(if chunk.len() > 2 {
+            ALPHABET[n as usize & 63] as char
+        } else {
+            '='
+        });
+    }
+    encoded
+}
+
+/// Decode standard Base64 (with `=` padding); `None` on malformed input
+pub fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some(u32::from(c - b'A')),
+            b'a'..=b'z' => Some(u32::from(c - b'a') + 26),
+            b'0'..=b'9' => Some(u32::from(c - b'0') + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let bytes = encoded.as_bytes();
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(bytes.len() / 4 * 3);
+    for chunk in bytes.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+        let mut n = 0u32;
+        for &c in &chunk[..4 - padding] {
+            n = (n << 6) | value(c)?;
+        }
+        n <<= 6 * padding as u32;
+        decoded.push((n >> 16) as u8);
+        if padding < 2 {
+            decoded.push((n >> 8) as u8);
+        }
+        if padding < 1 {
+            decoded.push(n as u8);
+        }
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ps_literal_escapes_quotes_only() {
+        let cases = [
+            ("plain", "'plain'"),
+            ("", "''"),
+            ("it's", "'it''s'"),
+            ("it\u{2019}s", "'it\u{2019}\u{2019}s'"),
+            (
+                "\u{2018}\u{201A}\u{201B}",
+                "'\u{2018}\u{2018}\u{201A}\u{201A}\u{201B}\u{201B}'",
+            ),
+            // Nothing expands inside single quotes
+            ("a`b", "'a`b'"),
+            ("$(Remove-Item C:\\)", "'$(Remove-Item C:\\)'"),
+            ("$env:PATH", "'$env:PATH'"),
+            ("\"double\"", "'\"double\"'"),
+            ("vm*", "'vm*'"),
+            ("vm[1]?", "'vm[1]?'"),
+            ("line\nbreak", "'line\nbreak'"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(ps_literal(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn build_binds_values_before_body() {
+        let script = ScriptBuilder::new()
+            .arg("Name", "x'; Remove-VM *; '")
+            .arg("Path", "C:\\VMs\\`$(whoami)")
+            .int("Count", -3)
+            .bool("Force", true)
+            .bool("WhatIf", false)
+            .build("Do-Thing $Name $Path $Count");
+        let expected = [
+            "$Name = 'x''; Remove-VM *; '''",
+            "$Path = 'C:\\VMs\\`$(whoami)'",
+            "$Count = -3",
+            "$Force = $true",
+            "$WhatIf = $false",
+            "Do-Thing $Name $Path $Count",
+        ];
+        assert_eq!(script, expected.join("\n"));
+    }
+
+    #[test]
+    fn get_vm_exact_is_explicit() {
+        let body = "Start-VM -VM (Get-VMExact $VMName)";
+        let script = ScriptBuilder::new().arg("VMName", "vm").build(body);
+        assert!(!script.contains("function Get-VMExact"));
+
+        // Wildcard characters reach Get-VM escaped, so only `lab[1]*?` matches
+        let script = ScriptBuilder::new()
+            .with_get_vm_exact()
+            .arg("VMName", "lab[1]*?")
+            .build(body);
+        let lines: Vec<&str> = script.lines().collect();
+        assert_eq!(lines[0], GET_VM_EXACT);
+        assert!(GET_VM_EXACT.contains("Get-VM -Name ([WildcardPattern]::Escape($Name))"));
+        assert_eq!(lines[1], "$VMName = 'lab[1]*?'");
+        assert_eq!(lines[2], body);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid PowerShell variable name")]
+    fn bind_rejects_bad_names() {
+        let _ = ScriptBuilder::new().arg("Name; rm", "x");
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for value in ["", "a", "ab", "abc", "Get-VM | ConvertTo-Json"] {
+            let encoded = base64_encode(value.as_bytes());
+            assert_eq!(decode_base64(&encoded).unwrap(), value.as_bytes());
+        }
+        // UTF-16LE of "A"
+        assert_eq!(encode_command("A"), "QQA=");
+        assert_eq!(decode_base64("QQA"), None);
+        assert_eq!(decode_base64("Q!A="), None);
+    }
+}
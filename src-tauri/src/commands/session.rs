@@ -0,0 +1,515 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::error::HyperVError;
+use super::executor::{PowerShellExecutor, PowerShellProcess, RunOptions, SystemExecutor};
+use super::script::{decode_base64, encode_command};
+use super::utils::{kill_process_tree, powershell_command, powershell_result};
+
+/// Number of PowerShell hosts kept alive by default
+pub const DEFAULT_POOL_SIZE: usize = 2;
+
+/// Sessions idle for longer than this are pinged before being reused
+const HEALTH_CHECK_AFTER: Duration = Duration::from_secs(120);
+
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a running script is checked for completion, timeout or cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Most lines a host keeps from its own stderr for the current request
+const MAX_HOST_ERROR_LINES: usize = 1000;
+
+/// Request/response loop run by every pooled host.
+///
+/// A request is two lines on stdin: a per-request marker, then the script as
+/// Base64 UTF-16LE (the same encoding as `-EncodedCommand`). The reply is a
+/// single line `<marker> <ok> <stdout> <stderr>` with both streams Base64
+/// encoded, so nothing the script prints can be mistaken for the frame.
+/// Whatever the host writes to its own stderr meanwhile, e.g. native tools
+/// that bypass the redirection, is added to the reply's stderr.
+///
+/// Scripts run in a child scope. `exit` there would end the host, so a script
+/// containing one is declined with the reply `<marker> exit` and run in a
+/// one-shot process instead, where it behaves as usual.
+const HOST_SCRIPT: &str = r#"
+Import-Module Hyper-V -ErrorAction SilentlyContinue
+function ConvertTo-PoolBase64([string]$Text) { [Convert]::ToBase64String([Text.Encoding]::UTF8.GetBytes($Text)) }
+while ($true) {
+    $__poolMarker = [Console]::In.ReadLine()
+    $__poolPayload = [Console]::In.ReadLine()
+    if ($null -eq $__poolMarker -or $null -eq $__poolPayload) { break }
+
+    $__poolScript = [Text.Encoding]::Unicode.GetString([Convert]::FromBase64String($__poolPayload))
+    $__poolAst = [System.Management.Automation.Language.Parser]::ParseInput($__poolScript, [ref]$null, [ref]$null)
+    if ($__poolAst.Find({ $args[0] -is [System.Management.Automation.Language.ExitStatementAst] }, $true)) {
+        [Console]::Out.WriteLine()
+        [Console]::Out.WriteLine("$__poolMarker exit")
+        [Console]::Out.Flush()
+        continue
+    }
+
+    $__poolOutput = New-Object System.Collections.Generic.List[object]
+    $__poolErrors = New-Object System.Collections.Generic.List[string]
+    $__poolFailed = $false
+    try {
+        $global:LASTEXITCODE = 0
+        & ([scriptblock]::Create($__poolScript)) *>&1 | ForEach-Object {
+            if ($_ -is [System.Management.Automation.ErrorRecord]) {
+                $__poolErrors.Add(($_ | Out-String).TrimEnd())
+            } elseif ($_ -is [System.Management.Automation.InformationRecord]) {
+                $__poolOutput.Add("$_")
+            } elseif ($_ -isnot [System.Management.Automation.WarningRecord] -and
+                      $_ -isnot [System.Management.Automation.VerboseRecord] -and
+                      $_ -isnot [System.Management.Automation.DebugRecord]) {
+                $__poolOutput.Add($_)
+            }
+        }
+        if ($global:LASTEXITCODE -ne 0) { $__poolFailed = $true }
+    } catch {
+        $__poolErrors.Add(($_ | Out-String).TrimEnd())
+        $__poolFailed = $true
+    }
+    # Like a one-shot run, errors only fail the call when nothing else was produced
+    if ($__poolErrors.Count -gt 0 -and $__poolOutput.Count -eq 0) { $__poolFailed = $true }
+
+    $__poolStdout = ConvertTo-PoolBase64 ($__poolOutput | Out-String -Width 4096)
+    $__poolStderr = ConvertTo-PoolBase64 ($__poolErrors -join [Environment]::NewLine)
+    [Console]::Out.WriteLine()
+    [Console]::Out.WriteLine("$__poolMarker $([int](-not $__poolFailed)) $__poolStdout $__poolStderr")
+    [Console]::Out.Flush()
+}
+"#;
+
+/// Command starting a pooled host
+fn host_command() -> Command {
+    powershell_command(HOST_SCRIPT)
+}
+
+/// What a session made of a request
+enum Reply {
+    Done(Result<String, HyperVError>),
+    /// The script calls `exit`; it has to run somewhere else
+    Declined,
+}
+
+/// A persistent `powershell.exe` running `HOST_SCRIPT`
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// stderr lines of the host since the current request was sent
+    errors: Arc<Mutex<Vec<String>>>,
+    last_used: Instant,
+    broken: bool,
+    /// Random-ish per-session value making request markers unguessable
+    nonce: u64,
+    requests: u64,
+}
+
+impl Session {
+    fn start(launch: fn() -> Command) -> std::io::Result<Self> {
+        let mut child = launch()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+            ^ u64::from(child.id()).rotate_left(32);
+        let (tx, lines) = mpsc::channel();
+        // The channel disconnects when the host exits, which marks the session dead
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let host_errors = errors.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                let mut errors = host_errors.lock().unwrap();
+                if errors.len() < MAX_HOST_ERROR_LINES {
+                    errors.push(line);
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            errors,
+            last_used: Instant::now(),
+            broken: false,
+            nonce,
+            requests: 0,
+        })
+    }
+
+    fn next_marker(&mut self) -> String {
+        self.requests += 1;
+        format!("::pspool:{:x}:{}::", self.nonce, self.requests)
+    }
+
+    fn is_alive(&mut self) -> bool {
+        !self.broken && matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Whether the session can take another script, pinging it after a long idle
+    fn is_healthy(&mut self) -> bool {
+        if !self.is_alive() {
+            return false;
+        }
+        if self.last_used.elapsed() < HEALTH_CHECK_AFTER {
+            return true;
+        }
+        let marker = self.next_marker();
+        if !self.send("'pong'", &marker) {
+            return false;
+        }
+        let pong = self.receive(&RunOptions::with_timeout(PING_TIMEOUT), &marker);
+        matches!(pong, Reply::Done(Ok(ref out)) if out == "pong")
+    }
+
+    /// Write a request; `false` if the host is gone and the script never ran
+    fn send(&mut self, script: &str, marker: &str) -> bool {
+        self.last_used = Instant::now();
+        // Leftovers of the previous request belong to nobody
+        self.errors.lock().unwrap().clear();
+        let sent = writeln!(self.stdin, "{}\n{}", marker, encode_command(script))
+            .and_then(|_| self.stdin.flush())
+            .is_ok();
+        if !sent {
+            self.broken = true;
+        }
+        sent
+    }
+
+    /// Wait for the reply to the request sent with `marker`
+    fn receive(&mut self, options: &RunOptions, marker: &str) -> Reply {
+        let started = Instant::now();
+        let timeout = options.timeout();
+        // Anything written straight to the console bypasses the frame; keep it as output
+        let mut stray = Vec::new();
+        loop {
+            match self.lines.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => match line.strip_prefix(marker) {
+                    Some(" exit") => return Reply::Declined,
+                    Some(frame) => return Reply::Done(self.parse_frame(frame, &stray)),
+                    None => stray.push(line),
+                },
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.broken = true;
+                    return Reply::Done(Err(HyperVError::powershell(
+                        "PowerShell session exited while running the script",
+                    )));
+                }
+            }
+
+            if options.is_cancelled() {
+                self.kill();
+                return Reply::Done(Err(HyperVError::cancelled("PowerShell call was cancelled")));
+            }
+            if started.elapsed() >= timeout {
+                self.kill();
+                return Reply::Done(Err(HyperVError::timeout(format!(
+                    "PowerShell call did not finish within {}s",
+                    timeout.as_secs()
+                ))));
+            }
+        }
+    }
+
+    /// Decode ` <ok> <stdout> <stderr>` following the marker
+    fn parse_frame(&mut self, frame: &str, stray: &[String]) -> Result<String, HyperVError> {
+        let fields: Vec<&str> = frame.trim_start_matches(' ').split(' ').collect();
+        let decoded = match fields.as_slice() {
+            [ok, stdout, stderr] => decode_base64(stdout)
+                .zip(decode_base64(stderr))
+                .map(|(stdout, stderr)| (*ok == "1", stdout, stderr)),
+            _ => None,
+        };
+        let Some((success, stdout, mut stderr)) = decoded else {
+            self.broken = true;
+            return Err(HyperVError::internal(
+                "Malformed reply from PowerShell session",
+            ));
+        };
+
+        let mut output = stray
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into_bytes();
+        if !output.is_empty() {
+            output.push(b'\n');
+        }
+        output.extend_from_slice(&stdout);
+
+        let host_errors = std::mem::take(&mut *self.errors.lock().unwrap());
+        if !host_errors.is_empty() {
+            if !stderr.is_empty() {
+                stderr.push(b'\n');
+            }
+            stderr.extend_from_slice(host_errors.join("\n").as_bytes());
+        }
+        powershell_result(success, &output, &stderr)
+    }
+
+    fn kill(&mut self) {
+        self.broken = true;
+        let _ = kill_process_tree(self.child.id());
+        let _ = self.child.wait();
+    }
+}
+
+struct PoolState {
+    idle: Vec<Session>,
+    /// Sessions currently alive, idle or checked out
+    live: usize,
+}
+
+/// Executor that runs scripts on a small pool of long-lived PowerShell hosts,
+/// so the Hyper-V module is loaded once instead of on every call.
+/// Dead or hung sessions are discarded and replaced on the next call; when
+/// every session is busy, or the script calls `exit`, the call runs in a
+/// one-shot process instead.
+pub struct SessionPool {
+    size: usize,
+    state: Mutex<PoolState>,
+    /// Starts a host running `HOST_SCRIPT`
+    launch: fn() -> Command,
+    /// Runs the calls no session takes, and streamed scripts
+    one_shot: Arc<dyn PowerShellExecutor>,
+}
+
+impl SessionPool {
+    pub fn new(size: usize) -> Self {
+        Self::with_backends(size, host_command, Arc::new(SystemExecutor))
+    }
+
+    fn with_backends(
+        size: usize,
+        launch: fn() -> Command,
+        one_shot: Arc<dyn PowerShellExecutor>,
+    ) -> Self {
+        Self {
+            size,
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                live: 0,
+            }),
+            launch,
+            one_shot,
+        }
+    }
+
+    /// Take a healthy idle session or start a new one if the pool has room
+    fn checkout(&self) -> Option<Session> {
+        loop {
+            let idle = {
+                let mut state = self.state.lock().unwrap();
+                match state.idle.pop() {
+                    Some(session) => Some(session),
+                    None if state.live < self.size => {
+                        state.live += 1;
+                        None
+                    }
+                    None => return None,
+                }
+            };
+
+            match idle {
+                Some(mut session) => {
+                    if session.is_healthy() {
+                        return Some(session);
+                    }
+                    self.discard(session);
+                }
+                None => {
+                    return match Session::start(self.launch) {
+                        Ok(session) => Some(session),
+                        Err(e) => {
+                            eprintln!("Failed to start PowerShell session: {}", e);
+                            self.state.lock().unwrap().live -= 1;
+                            None
+                        }
+                    };
+                }
+            }
+        }
+    }
+
+    fn checkin(&self, mut session: Session) {
+        if session.is_alive() {
+            self.state.lock().unwrap().idle.push(session);
+        } else {
+            self.discard(session);
+        }
+    }
+
+    fn discard(&self, mut session: Session) {
+        if !session.broken {
+            session.kill();
+        }
+        self.state.lock().unwrap().live -= 1;
+    }
+}
+
+impl Default for SessionPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_POOL_SIZE)
+    }
+}
+
+impl PowerShellExecutor for SessionPool {
+    fn run_with(&self, script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+        loop {
+            let Some(mut session) = self.checkout() else {
+                return self.one_shot.run_with(script, options);
+            };
+
+            let marker = session.next_marker();
+            // A host that died since the health check never saw the script; use another one
+            if !session.send(script, &marker) {
+                self.discard(session);
+                continue;
+            }
+            let reply = session.receive(options, &marker);
+            self.checkin(session);
+            return match reply {
+                Reply::Done(result) => result,
+                Reply::Declined => self.one_shot.run_with(script, options),
+            };
+        }
+    }
+
+    fn spawn(&self, script: &str) -> std::io::Result<Box<dyn PowerShellProcess>> {
+        self.one_shot.spawn(script)
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), HyperVError> {
+        self.one_shot.kill(pid)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+
+    /// Host speaking the pool protocol: `reply` runs once per request with
+    /// the request marker in `$marker`
+    fn fake_host(reply: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!(
+            "while IFS= read -r marker && IFS= read -r payload; do {}; done",
+            reply
+        ));
+        command
+    }
+
+    fn pool(launch: fn() -> Command) -> (SessionPool, Arc<ScriptedExecutor>) {
+        let one_shot = Arc::new(ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Get-VM", "one-shot",
+        )]));
+        (
+            SessionPool::with_backends(1, launch, one_shot.clone()),
+            one_shot,
+        )
+    }
+
+    fn options() -> RunOptions {
+        RunOptions::with_timeout(Duration::from_secs(10))
+    }
+
+    #[test]
+    fn frame_ignores_marker_lookalikes() {
+        // "aGVsbG8=" is "hello"; the first line has the shape of a frame but another marker
+        let (pool, one_shot) = pool(|| {
+            fake_host(
+                "echo '::pspool:0:1:: 1 ZmFrZQ== '; echo 'written to console'; \
+                 echo \"$marker 1 aGVsbG8= \"",
+            )
+        });
+
+        let output = pool.run_with("Get-VM", &options()).unwrap();
+        assert_eq!(
+            output,
+            "::pspool:0:1:: 1 ZmFrZQ==\nwritten to console\nhello"
+        );
+        // The session survives and answers the next request too
+        assert!(pool
+            .run_with("Get-VM", &options())
+            .unwrap()
+            .ends_with("hello"));
+        assert_eq!(pool.state.lock().unwrap().live, 1);
+        assert!(one_shot.calls().is_empty());
+    }
+
+    #[test]
+    fn frame_carries_host_stderr() {
+        let (pool, _) =
+            pool(|| fake_host("echo 'native tool failed' >&2; sleep 0.2; echo \"$marker 0  \""));
+
+        let error = pool.run_with("Get-VM", &options()).unwrap_err();
+        assert!(error.message().contains("native tool failed"), "{}", error);
+    }
+
+    #[test]
+    fn malformed_frame_discards_session() {
+        let (pool, _) = pool(|| fake_host("echo \"$marker 1 not-base64\""));
+
+        let error = pool.run_with("Get-VM", &options()).unwrap_err();
+        assert!(matches!(error, HyperVError::Internal(_)));
+        assert_eq!(pool.state.lock().unwrap().live, 0);
+    }
+
+    #[test]
+    fn host_exit_fails_call_and_is_replaced() {
+        let (pool, _) = pool(|| fake_host("exit 3"));
+
+        let error = pool.run_with("Get-VM", &options()).unwrap_err();
+        assert!(error.message().contains("session exited"), "{}", error);
+        assert_eq!(pool.state.lock().unwrap().live, 0);
+    }
+
+    #[test]
+    fn declined_script_runs_one_shot() {
+        let (pool, one_shot) = pool(|| fake_host("echo \"$marker exit\""));
+
+        let output = pool.run_with("Get-VM; exit 1", &options()).unwrap();
+        assert_eq!(output, "one-shot");
+        assert_eq!(one_shot.calls(), vec!["Get-VM; exit 1".to_string()]);
+        assert_eq!(pool.state.lock().unwrap().live, 1);
+    }
+
+    #[test]
+    fn busy_pool_runs_one_shot() {
+        let (pool, one_shot) = pool(|| fake_host("echo \"$marker 1 aGVsbG8= \""));
+
+        let busy = pool.checkout().expect("pool has room for a session");
+        assert_eq!(pool.run_with("Get-VM", &options()).unwrap(), "one-shot");
+        assert_eq!(one_shot.calls().len(), 1);
+
+        pool.checkin(busy);
+        assert_eq!(pool.run_with("Get-VM", &options()).unwrap(), "hello");
+        assert_eq!(one_shot.calls().len(), 1);
+    }
+}
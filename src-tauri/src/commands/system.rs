@@ -1,6 +1,14 @@
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::State;
 
-use super::utils::run_powershell;
+use super::audit::AuditContext;
+use super::error::HyperVError;
+use super::executor::{ExecutorState, PowerShellExecutor};
+use super::utils::parse_json_list;
+
+/// Diagnostics run several queries (CIM, GPU partitioning) in one command
+const DIAGNOSTICS_TIMEOUT: Duration = Duration::from_secs(120);
 
 /// GPU information structure
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,14 +30,14 @@ pub struct SystemInfo {
 }
 
 /// Check if Hyper-V is enabled (using multiple methods, no admin required)
-fn check_hyper_v() -> bool {
+fn check_hyper_v(ps: &dyn PowerShellExecutor) -> bool {
     // Method 1: Check if Hyper-V service (vmms) exists and is running
     let service_script = r#"
         $vmms = Get-Service -Name 'vmms' -ErrorAction SilentlyContinue
         if ($vmms -and $vmms.Status -eq 'Running') { 'true' } else { 'false' }
     "#;
 
-    if let Ok(result) = run_powershell(service_script) {
+    if let Ok(result) = ps.run(service_script) {
         if result.to_lowercase() == "true" {
             return true;
         }
@@ -41,7 +49,7 @@ fn check_hyper_v() -> bool {
         if ($vmcompute) { 'true' } else { 'false' }
     "#;
 
-    if let Ok(result) = run_powershell(vmcompute_script) {
+    if let Ok(result) = ps.run(vmcompute_script) {
         if result.to_lowercase() == "true" {
             return true;
         }
@@ -53,63 +61,54 @@ fn check_hyper_v() -> bool {
         if ($key) { 'true' } else { 'false' }
     "#;
 
-    run_powershell(registry_script)
+    ps.run(registry_script)
         .map(|s| s.to_lowercase() == "true")
         .unwrap_or(false)
 }
 
 /// Get Windows version and edition
-fn get_os_info() -> (String, String) {
+fn get_os_info(ps: &dyn PowerShellExecutor) -> (String, String) {
     let version_script = "(Get-CimInstance Win32_OperatingSystem).Version";
     let edition_script = "(Get-CimInstance Win32_OperatingSystem).Caption";
 
-    let version = run_powershell(version_script).unwrap_or_else(|_| "Unknown".to_string());
-    let edition = run_powershell(edition_script).unwrap_or_else(|_| "Unknown".to_string());
+    let version = ps
+        .run(version_script)
+        .unwrap_or_else(|_| "Unknown".to_string());
+    let edition = ps
+        .run(edition_script)
+        .unwrap_or_else(|_| "Unknown".to_string());
 
     (version, edition)
 }
 
 /// Get available memory in GB
-fn get_available_memory() -> f64 {
+fn get_available_memory(ps: &dyn PowerShellExecutor) -> f64 {
     let script =
         "[math]::Round((Get-CimInstance Win32_ComputerSystem).TotalPhysicalMemory / 1GB, 2)";
-    run_powershell(script)
+    ps.run(script)
         .ok()
         .and_then(|s| s.parse::<f64>().ok())
         .unwrap_or(0.0)
 }
 
 /// Get list of GPUs that support partitioning
-fn get_gpu_list() -> Vec<GpuInfo> {
+fn get_gpu_list(ps: &dyn PowerShellExecutor) -> Result<Vec<GpuInfo>, HyperVError> {
     let script = r#"
-        Get-CimInstance Win32_VideoController | ForEach-Object {
-            $name = $_.Name
-            $driver = $_.DriverVersion
-            # Check if GPU supports partitioning (NVIDIA, AMD, or Intel)
-            $supports = $name -match 'NVIDIA|AMD|Intel'
-            "$name|$driver|$supports"
-        }
+        $gpus = @(Get-CimInstance Win32_VideoController | ForEach-Object {
+            $name = "$($_.Name)"
+            [PSCustomObject]@{
+                name = $name
+                driver_version = "$($_.DriverVersion)"
+                # Check if GPU supports partitioning (NVIDIA, AMD, or Intel)
+                supports_partitioning = $name -match 'NVIDIA|AMD|Intel'
+            }
+        })
+        ConvertTo-Json -InputObject $gpus -Compress
     "#;
 
-    run_powershell(script)
-        .map(|output| {
-            output
-                .lines()
-                .filter_map(|line| {
-                    let parts: Vec<&str> = line.split('|').collect();
-                    if parts.len() >= 3 {
-                        Some(GpuInfo {
-                            name: parts[0].to_string(),
-                            driver_version: parts[1].to_string(),
-                            supports_partitioning: parts[2].to_lowercase() == "true",
-                        })
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        })
-        .unwrap_or_default()
+    let output = ps.run(script)?;
+    parse_json_list(&output)
+        .map_err(|e| HyperVError::internal(format!("Failed to read GPU list: {}", e)))
 }
 
 /// Validate system requirements and return issues
@@ -140,11 +139,11 @@ fn validate_system(os_edition: &str, hyper_v_enabled: bool, gpu_list: &[GpuInfo]
 }
 
 /// Internal sync function to check system
-fn check_system_sync() -> Result<SystemInfo, String> {
-    let (os_version, os_edition) = get_os_info();
-    let hyper_v_enabled = check_hyper_v();
-    let gpu_list = get_gpu_list();
-    let available_memory_gb = get_available_memory();
+pub fn check_system_sync(ps: &dyn PowerShellExecutor) -> Result<SystemInfo, HyperVError> {
+    let (os_version, os_edition) = get_os_info(ps);
+    let hyper_v_enabled = check_hyper_v(ps);
+    let gpu_list = get_gpu_list(ps)?;
+    let available_memory_gb = get_available_memory(ps);
     let issues = validate_system(&os_edition, hyper_v_enabled, &gpu_list);
 
     Ok(SystemInfo {
@@ -159,14 +158,17 @@ fn check_system_sync() -> Result<SystemInfo, String> {
 
 /// Tauri command to check system requirements (async to not block UI)
 #[tauri::command]
-pub async fn check_system() -> Result<SystemInfo, String> {
-    tokio::task::spawn_blocking(check_system_sync)
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
+pub async fn check_system(ps: State<'_, ExecutorState>) -> Result<SystemInfo, HyperVError> {
+    ps.run_blocking(
+        AuditContext::new("check_system"),
+        Some(DIAGNOSTICS_TIMEOUT),
+        check_system_sync,
+    )
+    .await
 }
 
 /// Test GPU partitioning in detail
-fn test_gpu_partitioning_sync() -> Result<String, String> {
+pub fn test_gpu_partitioning_sync(ps: &dyn PowerShellExecutor) -> Result<String, HyperVError> {
     let mut output = Vec::new();
 
     // Check for partitionable GPUs
@@ -191,7 +193,7 @@ fn test_gpu_partitioning_sync() -> Result<String, String> {
         }
     "#;
 
-    match run_powershell(gpu_script) {
+    match ps.run(gpu_script) {
         Ok(result) => {
             for line in result.lines() {
                 output.push(line.to_string());
@@ -222,7 +224,7 @@ fn test_gpu_partitioning_sync() -> Result<String, String> {
         }
     "#;
 
-    match run_powershell(hyperv_gpu_script) {
+    match ps.run(hyperv_gpu_script) {
         Ok(result) => {
             for line in result.lines() {
                 output.push(line.to_string());
@@ -251,7 +253,7 @@ fn test_gpu_partitioning_sync() -> Result<String, String> {
         }
     "#;
 
-    match run_powershell(vm_gpu_script) {
+    match ps.run(vm_gpu_script) {
         Ok(result) => {
             for line in result.lines() {
                 if !line.trim().is_empty() {
@@ -269,8 +271,90 @@ fn test_gpu_partitioning_sync() -> Result<String, String> {
 
 /// Tauri command to test GPU partitioning
 #[tauri::command]
-pub async fn test_gpu_partitioning() -> Result<String, String> {
-    tokio::task::spawn_blocking(test_gpu_partitioning_sync)
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
+pub async fn test_gpu_partitioning(ps: State<'_, ExecutorState>) -> Result<String, HyperVError> {
+    ps.run_blocking(
+        AuditContext::new("test_gpu_partitioning"),
+        Some(DIAGNOSTICS_TIMEOUT),
+        test_gpu_partitioning_sync,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+
+    fn host(gpus: &str) -> ScriptedExecutor {
+        ScriptedExecutor::new(vec![
+            ScriptRule::ok("Get-Service -Name 'vmms'", "true"),
+            ScriptRule::ok("Win32_OperatingSystem).Version", "10.0.22631"),
+            ScriptRule::ok("Win32_OperatingSystem).Caption", "Microsoft Windows 11 Pro"),
+            ScriptRule::ok("Win32_VideoController", gpus),
+            ScriptRule::ok("TotalPhysicalMemory", "31.84"),
+        ])
+    }
+
+    #[test]
+    fn check_system_reads_host() {
+        let ps = host(
+            r#"[{"name":"NVIDIA GeForce RTX 4070","driver_version":"32.0.15.6094","supports_partitioning":true}]"#,
+        );
+        let info = check_system_sync(&ps).unwrap();
+        assert_eq!(info.os_version, "10.0.22631");
+        assert_eq!(info.os_edition, "Microsoft Windows 11 Pro");
+        assert!(info.hyper_v_enabled);
+        assert_eq!(info.gpu_list.len(), 1);
+        assert_eq!(info.gpu_list[0].name, "NVIDIA GeForce RTX 4070");
+        assert!(info.gpu_list[0].supports_partitioning);
+        assert_eq!(info.available_memory_gb, 31.84);
+        assert!(info.issues.is_empty(), "{:?}", info.issues);
+    }
+
+    #[test]
+    fn check_system_reports_issues() {
+        // A single GPU is a bare object, not an array
+        let ps = ScriptedExecutor::new(vec![
+            ScriptRule::ok("Get-Service -Name 'vmms'", "false"),
+            ScriptRule::ok("Get-Service -Name 'vmcompute'", "false"),
+            ScriptRule::ok("Virtualization", "false"),
+            ScriptRule::ok(
+                "Win32_OperatingSystem).Caption",
+                "Microsoft Windows 11 Home",
+            ),
+            ScriptRule::ok(
+                "Win32_VideoController",
+                r#"{"name":"Microsoft Basic Display Adapter","driver_version":"10.0","supports_partitioning":false}"#,
+            ),
+        ]);
+        let info = check_system_sync(&ps).unwrap();
+        // Queries without a response fall back to defaults
+        assert_eq!(info.os_version, "Unknown");
+        assert_eq!(info.available_memory_gb, 0.0);
+        assert!(!info.hyper_v_enabled);
+        assert_eq!(info.gpu_list.len(), 1);
+        assert_eq!(info.issues.len(), 3, "{:?}", info.issues);
+    }
+
+    #[test]
+    fn check_system_errors() {
+        let ps = host("");
+        assert!(check_system_sync(&ps).unwrap().gpu_list.is_empty());
+
+        let ps = ScriptedExecutor::new(vec![ScriptRule::fail(
+            "Win32_VideoController",
+            "Get-CimInstance : Access is denied.",
+            1,
+        )]);
+        assert!(matches!(
+            check_system_sync(&ps),
+            Err(HyperVError::NotElevated(_))
+        ));
+
+        let ps = host("not json");
+        assert!(matches!(
+            check_system_sync(&ps),
+            Err(HyperVError::Internal(_))
+        ));
+    }
 }
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+/// How a placeholder value is escaped for the spot it is substituted into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Escape {
+    /// Inside a PowerShell double-quoted string (`"__NAME__"`)
+    PsDoubleQuoted,
+    /// Bare number in the template; the value must be all digits
+    Integer,
+}
+
+impl Escape {
+    fn apply(self, name: &str, value: &str) -> Result<String, String> {
+        match self {
+            Escape::PsDoubleQuoted => Ok(ps_double_quoted(value)),
+            Escape::Integer if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) => {
+                Ok(value.to_string())
+            }
+            Escape::Integer => Err(format!(
+                "__{}__ must be a whole number, got '{}'",
+                name, value
+            )),
+        }
+    }
+}
+
+/// A template file and the placeholders it may contain
+pub struct Template {
+    pub file_name: &'static str,
+    pub placeholders: &'static [(&'static str, Escape)],
+}
+
+/// Stands in for the VM password in the staged `autounattend.xml`.
+/// CopyFilesToVM.ps1 is passed the password as `-Password` and fills it in
+/// when it copies the answer file into the VM's disk.
+pub const UNATTEND_PASSWORD_TOKEN: &str = "%PROVISION_PASSWORD%";
+
+/// `CopyFilesToVM.template.ps1`: the `$params` block of the provisioning script
+pub const PROVISION_SCRIPT: Template = Template {
+    file_name: "CopyFilesToVM.template.ps1",
+    placeholders: &[
+        ("VM_NAME", Escape::PsDoubleQuoted),
+        ("ISO_PATH", Escape::PsDoubleQuoted),
+        ("EDITION", Escape::Integer),
+        ("SOURCE_VHD", Escape::PsDoubleQuoted),
+        ("DIFFERENCING", Escape::PsDoubleQuoted),
+        ("VHD_PATH", Escape::PsDoubleQuoted),
+        ("VHD_FORMAT", Escape::PsDoubleQuoted),
+        ("DISK_LAYOUT", Escape::PsDoubleQuoted),
+        ("VM_GENERATION", Escape::Integer),
+        ("FIXED_DISK", Escape::PsDoubleQuoted),
+        ("BLOCK_SIZE_KB", Escape::Integer),
+        ("LOGICAL_SECTOR_BYTES", Escape::Integer),
+        ("PHYSICAL_SECTOR_BYTES", Escape::Integer),
+        ("DATA_DISK_GB", Escape::Integer),
+        ("DISK_SIZE_GB", Escape::Integer),
+        ("MEMORY_GB", Escape::Integer),
+        ("CPU_COUNT", Escape::Integer),
+        ("GPU_NAME", Escape::PsDoubleQuoted),
+        ("SWITCH_NAME", Escape::PsDoubleQuoted),
+        ("USERNAME", Escape::PsDoubleQuoted),
+        ("AUTO_LOGON", Escape::PsDoubleQuoted),
+        ("GPU_ALLOCATION_PERCENT", Escape::Integer),
+        ("RESUME_CHECKPOINTS", Escape::PsDoubleQuoted),
+        ("OPEN_CONSOLE", Escape::PsDoubleQuoted),
+    ],
+};
+
+impl Template {
+    /// Substitute every `__NAME__` token in `source` with its escaped value.
+    /// Tokens the template does not declare, and declared placeholders used
+    /// without a value, are errors rather than being left in the output.
+    pub fn render(&self, source: &str, values: &[(&str, String)]) -> Result<String, String> {
+        let values: HashMap<&str, &str> = values.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        if let Some((name, _)) = values
+            .iter()
+            .find(|(name, _)| self.escape_for(name).is_none())
+        {
+            return Err(format!(
+                "{} has no placeholder __{}__",
+                self.file_name, name
+            ));
+        }
+
+        let mut output = String::with_capacity(source.len());
+        let mut rest = source;
+        while let Some(start) = rest.find("__") {
+            output.push_str(&rest[..start]);
+            let Some(name) = token_at(&rest[start..]) else {
+                output.push_str("__");
+                rest = &rest[start + 2..];
+                continue;
+            };
+            let escape = self.escape_for(name).ok_or_else(|| {
+                format!(
+                    "{} contains undeclared placeholder __{}__",
+                    self.file_name, name
+                )
+            })?;
+            let value = values
+                .get(name)
+                .ok_or_else(|| format!("No value given for __{}__ in {}", name, self.file_name))?;
+            output.push_str(&escape.apply(name, value)?);
+            rest = &rest[start + name.len() + 4..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn escape_for(&self, name: &str) -> Option<Escape> {
+        self.placeholders
+            .iter()
+            .find(|(placeholder, _)| *placeholder == name)
+            .map(|(_, escape)| *escape)
+    }
+}
+
+/// Name of the `__NAME__` token `text` starts with. Names are upper case
+/// letters, digits and single underscores, so `__DISK_SIZE_GB__GB` is
+/// `DISK_SIZE_GB` and PowerShell's `__AllParameterSets` is not a token.
+fn token_at(text: &str) -> Option<&str> {
+    let body = text.strip_prefix("__")?;
+    let len = body
+        .find(|c: char| !(c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'))
+        .unwrap_or(body.len());
+    let end = body[..len].find("__")?;
+    let name = &body[..end];
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        .then_some(name)
+}
+
+/// Escape a value for a PowerShell double-quoted string. The backtick is the
+/// escape character; `$` would start a subexpression and any double quote
+/// (including the typographic ones PowerShell accepts) would end the string.
+pub fn ps_double_quoted(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '`' | '$' | '"' | '\u{201C}' | '\u{201D}' | '\u{201E}') {
+            escaped.push('`');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape a value for XML text or a quoted attribute value
+pub fn xml_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROVISION_SOURCE: &str = include_str!("easy-gpu-pv/CopyFilesToVM.template.ps1");
+
+    /// Tries every way out of a PowerShell double-quoted string
+    const HOSTILE: &str =
+        "x\"; Remove-Item C:\\ -Recurse; \"`$(calc)$env:TEMP\u{201C}a\u{201D}b\u{201E}``";
+
+    fn hostile_values(template: &Template) -> Vec<(&'static str, String)> {
+        template
+            .placeholders
+            .iter()
+            .map(|(name, escape)| match escape {
+                Escape::PsDoubleQuoted => (*name, HOSTILE.to_string()),
+                Escape::Integer => (*name, "42".to_string()),
+            })
+            .collect()
+    }
+
+    /// Value of the double-quoted string `text` starts with and what follows
+    /// it; `None` if a `$` could expand inside or the string never ends
+    fn read_double_quoted(text: &str) -> Option<(String, &str)> {
+        let rest = text.strip_prefix('"')?;
+        let mut chars = rest.char_indices();
+        let mut value = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '`' => value.push(chars.next()?.1),
+                '$' => return None,
+                '"' | '\u{201C}' | '\u{201D}' | '\u{201E}' => {
+                    return Some((value, &rest[i + c.len_utf8()..]))
+                }
+                _ => value.push(c),
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn provision_script_declares_every_token() {
+        for (name, _) in PROVISION_SCRIPT.placeholders {
+            assert!(
+                PROVISION_SOURCE.contains(&format!("__{}__", name)),
+                "__{}__ is declared but unused",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn provision_script_contains_hostile_values() {
+        let rendered = PROVISION_SCRIPT
+            .render(PROVISION_SOURCE, &hostile_values(&PROVISION_SCRIPT))
+            .unwrap();
+        for (name, _) in PROVISION_SCRIPT.placeholders {
+            assert!(
+                !rendered.contains(&format!("__{}__", name)),
+                "__{}__ left",
+                name
+            );
+        }
+
+        // Values hold no newline, so rendering keeps the template's lines
+        let source_lines: Vec<&str> = PROVISION_SOURCE.lines().collect();
+        let rendered_lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(source_lines.len(), rendered_lines.len());
+        for (name, escape) in PROVISION_SCRIPT.placeholders {
+            let token = format!("\"__{}__\"", name);
+            for (source, rendered) in source_lines.iter().zip(&rendered_lines) {
+                let Some(at) = source.find(&token) else {
+                    continue;
+                };
+                assert_eq!(*escape, Escape::PsDoubleQuoted, "{}", source);
+                assert_eq!(rendered[..at], source[..at]);
+                let (value, after) = read_double_quoted(&rendered[at..])
+                    .unwrap_or_else(|| panic!("__{}__ escapes its string: {}", name, rendered));
+                assert_eq!(value, HOSTILE);
+                assert_eq!(after, &source[at + token.len()..]);
+            }
+        }
+    }
+
+    #[test]
+    fn integer_placeholders_reject_non_digits() {
+        for (name, escape) in PROVISION_SCRIPT.placeholders {
+            if *escape != Escape::Integer {
+                continue;
+            }
+            let source = format!("Value = __{}__", name);
+            for value in ["", "-1", "1; calc", "1e3", "$env:X"] {
+                assert!(
+                    PROVISION_SCRIPT
+                        .render(&source, &[(name, value.to_string())])
+                        .is_err(),
+                    "__{}__ accepted '{}'",
+                    name,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn render_substitutes_once() {
+        let rendered = PROVISION_SCRIPT
+            .render(
+                "\"__VM_NAME__\" __CPU_COUNT__GB __AllParameterSets ____",
+                &[
+                    ("VM_NAME", "__CPU_COUNT__".to_string()),
+                    ("CPU_COUNT", "4".to_string()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(rendered, "\"__CPU_COUNT__\" 4GB __AllParameterSets ____");
+    }
+
+    #[test]
+    fn render_rejects_unknown_and_missing_tokens() {
+        let unknown = PROVISION_SCRIPT.render("\"__VM_PASSWORD__\"", &[]);
+        assert!(unknown
+            .unwrap_err()
+            .contains("undeclared placeholder __VM_PASSWORD__"));
+
+        let missing = PROVISION_SCRIPT.render("\"__VM_NAME__\"", &[]);
+        assert!(missing
+            .unwrap_err()
+            .contains("No value given for __VM_NAME__"));
+
+        let unused = PROVISION_SCRIPT.render("", &[("PASSWORD", "x".to_string())]);
+        assert!(unused
+            .unwrap_err()
+            .contains("has no placeholder __PASSWORD__"));
+    }
+
+    #[test]
+    fn xml_text_escapes_markup() {
+        assert_eq!(
+            xml_text("<a href=\"x\">Tom & 'Jerry'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;"
+        );
+    }
+}
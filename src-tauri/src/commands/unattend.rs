@@ -0,0 +1,750 @@
+use serde::{Deserialize, Serialize};
+
+use super::template::xml_text;
+
+/// Generic KMS client key for Windows 10/11 Pro. It only selects the edition
+/// and skips the key prompt; the VM still needs a real license to activate.
+pub const GENERIC_PRO_KEY: &str = "W269N-WFGWX-YVC9B-4J6C9-T83GX";
+
+/// Attributes every unattend component carries
+const COMPONENT_ATTRS: &str = r#"processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance""#;
+
+/// GPT layout for disk 0: WinRE, ESP, MSR and the Windows partition
+const DISK_CONFIGURATION: &str = r#"      <DiskConfiguration>
+        <Disk wcm:action="add">
+          <DiskID>0</DiskID>
+          <WillWipeDisk>true</WillWipeDisk>
+          <CreatePartitions>
+            <CreatePartition wcm:action="add">
+              <Order>1</Order>
+              <Type>Primary</Type>
+              <Size>300</Size>
+            </CreatePartition>
+            <CreatePartition wcm:action="add">
+              <Order>2</Order>
+              <Type>EFI</Type>
+              <Size>100</Size>
+            </CreatePartition>
+            <CreatePartition wcm:action="add">
+              <Order>3</Order>
+              <Type>MSR</Type>
+              <Size>128</Size>
+            </CreatePartition>
+            <CreatePartition wcm:action="add">
+              <Order>4</Order>
+              <Type>Primary</Type>
+              <Extend>true</Extend>
+            </CreatePartition>
+          </CreatePartitions>
+          <ModifyPartitions>
+            <ModifyPartition wcm:action="add">
+              <Order>1</Order>
+              <PartitionID>1</PartitionID>
+              <Label>WINRE</Label>
+              <Format>NTFS</Format>
+              <TypeID>DE94BBA4-06D1-4D40-A16A-BFD50179D6AC</TypeID>
+            </ModifyPartition>
+            <ModifyPartition wcm:action="add">
+              <Order>2</Order>
+              <PartitionID>2</PartitionID>
+              <Label>System</Label>
+              <Format>FAT32</Format>
+            </ModifyPartition>
+            <ModifyPartition wcm:action="add">
+              <Order>3</Order>
+              <PartitionID>3</PartitionID>
+            </ModifyPartition>
+            <ModifyPartition wcm:action="add">
+              <Order>4</Order>
+              <PartitionID>4</PartitionID>
+              <Label>OS</Label>
+              <Letter>C</Letter>
+              <Format>NTFS</Format>
+            </ModifyPartition>
+          </ModifyPartitions>
+        </Disk>
+      </DiskConfiguration>
+      <ImageInstall>
+        <OSImage>
+          <InstallTo>
+            <DiskID>0</DiskID>
+            <PartitionID>4</PartitionID>
+          </InstallTo>
+          <InstallToAvailablePartition>false</InstallToAvailablePartition>
+        </OSImage>
+      </ImageInstall>
+"#;
+
+/// Unattend settings the user can override from the create form.
+/// Anything left out falls back to the `UnattendBuilder` defaults.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UnattendOptions {
+    /// Defaults to a NetBIOS-safe form of the VM name
+    pub computer_name: Option<String>,
+    /// Language tag used for UI, system and user locale, e.g. `en-US`
+    pub locale: Option<String>,
+    /// Keyboard layout, e.g. `0409:00000409` or `en-US`
+    pub keyboard: Option<String>,
+    /// Windows time zone id, e.g. `Pacific Standard Time`
+    pub time_zone: Option<String>,
+    pub product_key: Option<String>,
+}
+
+/// Language and keyboard settings, applied in windowsPE and specialize
+#[derive(Clone, Debug)]
+pub struct Locale {
+    pub ui_language: String,
+    pub system_locale: String,
+    pub user_locale: String,
+    pub input_locale: String,
+}
+
+impl Locale {
+    /// UI, system and user locale all set to `tag`, with its default keyboard
+    pub fn new(tag: &str) -> Self {
+        Self {
+            ui_language: tag.to_string(),
+            system_locale: tag.to_string(),
+            user_locale: tag.to_string(),
+            input_locale: tag.to_string(),
+        }
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            input_locale: "0409:00000409".to_string(),
+            ..Self::new("en-US")
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkLocation {
+    Home,
+    Work,
+    Other,
+}
+
+impl NetworkLocation {
+    fn as_str(self) -> &'static str {
+        match self {
+            NetworkLocation::Home => "Home",
+            NetworkLocation::Work => "Work",
+            NetworkLocation::Other => "Other",
+        }
+    }
+}
+
+/// Out-of-box experience screens to skip
+#[derive(Clone, Debug)]
+pub struct OobeSettings {
+    pub hide_eula_page: bool,
+    pub hide_oem_registration_screen: bool,
+    pub hide_online_account_screens: bool,
+    pub hide_wireless_setup: bool,
+    pub network_location: NetworkLocation,
+    pub skip_user_oobe: bool,
+    pub skip_machine_oobe: bool,
+    /// 1 = recommended, 2 = updates only, 3 = off
+    pub protect_your_pc: u8,
+}
+
+impl Default for OobeSettings {
+    fn default() -> Self {
+        Self {
+            hide_eula_page: true,
+            hide_oem_registration_screen: true,
+            hide_online_account_screens: true,
+            hide_wireless_setup: true,
+            network_location: NetworkLocation::Home,
+            skip_user_oobe: true,
+            skip_machine_oobe: true,
+            protect_your_pc: 1,
+        }
+    }
+}
+
+/// Command run once, in order, when the account first logs on
+#[derive(Clone, Debug)]
+pub struct FirstLogonCommand {
+    pub description: String,
+    pub command_line: String,
+}
+
+impl FirstLogonCommand {
+    pub fn new(description: &str, command_line: &str) -> Self {
+        Self {
+            description: description.to_string(),
+            command_line: command_line.to_string(),
+        }
+    }
+}
+
+/// Builds `autounattend.xml` for a GPU-PV guest: a local administrator,
+/// an optional auto logon, and first logon commands that allow scripts and
+/// enable RDP.
+#[derive(Clone, Debug)]
+pub struct UnattendBuilder {
+    computer_name: String,
+    username: String,
+    password: String,
+    auto_logon: bool,
+    locale: Locale,
+    time_zone: String,
+    product_key: Option<String>,
+    registered_owner: String,
+    oobe: OobeSettings,
+    /// Width, height
+    display: Option<(u32, u32)>,
+    first_logon_commands: Vec<FirstLogonCommand>,
+}
+
+impl UnattendBuilder {
+    pub fn new(computer_name: &str, username: &str, password: &str) -> Self {
+        let first_logon_commands = vec![
+            FirstLogonCommand::new(
+                "Allow Scripts",
+                r"reg add HKLM\Software\Policies\Microsoft\Windows\Powershell",
+            ),
+            FirstLogonCommand::new(
+                "Allow Scripts",
+                r"reg add HKLM\Software\Policies\Microsoft\Windows\Powershell /v ExecutionPolicy /t REG_SZ /d Unrestricted",
+            ),
+            FirstLogonCommand::new(
+                "Allow Scripts",
+                r"reg add HKLM\Software\Policies\Microsoft\Windows\Powershell /v EnableScripts /t REG_DWORD /d 1",
+            ),
+            FirstLogonCommand::new(
+                "Password Never Expires",
+                &format!(
+                    r#"cmd /C wmic useraccount where name="{}" set PasswordExpires=false"#,
+                    username
+                ),
+            ),
+            FirstLogonCommand::new(
+                "Enable RDP",
+                r#"cmd /c reg add "HKLM\SYSTEM\CurrentControlSet\Control\Terminal Server" /v fDenyTSConnections /t REG_DWORD /d 0 /f"#,
+            ),
+            FirstLogonCommand::new(
+                "Allow RDP through Firewall",
+                r#"powershell -Command "Enable-NetFirewallRule -DisplayGroup 'Remote Desktop'""#,
+            ),
+        ];
+        Self {
+            computer_name: computer_name.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            auto_logon: true,
+            locale: Locale::default(),
+            time_zone: "UTC".to_string(),
+            product_key: Some(GENERIC_PRO_KEY.to_string()),
+            registered_owner: "GPU-P".to_string(),
+            oobe: OobeSettings::default(),
+            display: Some((1920, 1080)),
+            first_logon_commands,
+        }
+    }
+
+    /// Builder for `vm_name` with `options` laid over the defaults
+    pub fn for_vm(
+        vm_name: &str,
+        username: &str,
+        password: &str,
+        auto_logon: bool,
+        options: &UnattendOptions,
+    ) -> Self {
+        let computer_name = options
+            .computer_name
+            .clone()
+            .unwrap_or_else(|| computer_name_for(vm_name));
+        let mut builder = Self::new(&computer_name, username, password).auto_logon(auto_logon);
+        if let Some(tag) = &options.locale {
+            builder = builder.locale(Locale::new(tag));
+        }
+        if let Some(keyboard) = &options.keyboard {
+            builder.locale.input_locale = keyboard.clone();
+        }
+        if let Some(time_zone) = &options.time_zone {
+            builder = builder.time_zone(time_zone);
+        }
+        if let Some(key) = &options.product_key {
+            builder = builder.product_key(Some(key));
+        }
+        builder
+    }
+
+    pub fn auto_logon(mut self, enabled: bool) -> Self {
+        self.auto_logon = enabled;
+        self
+    }
+
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub fn time_zone(mut self, time_zone: &str) -> Self {
+        self.time_zone = time_zone.to_string();
+        self
+    }
+
+    /// `None` leaves the key out, for ISOs that pick the edition themselves
+    pub fn product_key(mut self, key: Option<&str>) -> Self {
+        self.product_key = key.map(|k| k.trim().to_uppercase());
+        self
+    }
+
+    pub fn oobe(mut self, oobe: OobeSettings) -> Self {
+        self.oobe = oobe;
+        self
+    }
+
+    pub fn display(mut self, resolution: Option<(u32, u32)>) -> Self {
+        self.display = resolution;
+        self
+    }
+
+    pub fn first_logon_command(mut self, command: FirstLogonCommand) -> Self {
+        self.first_logon_commands.push(command);
+        self
+    }
+
+    /// Reject values Windows Setup would refuse or silently ignore
+    pub fn validate(&self) -> Result<(), String> {
+        validate_computer_name(&self.computer_name)?;
+        validate_username(&self.username)?;
+        for (field, tag) in [
+            ("UI language", &self.locale.ui_language),
+            ("System locale", &self.locale.system_locale),
+            ("User locale", &self.locale.user_locale),
+        ] {
+            if !is_language_tag(tag) {
+                return Err(format!(
+                    "{} '{}' is not a language tag like en-US",
+                    field, tag
+                ));
+            }
+        }
+        if !self
+            .locale
+            .input_locale
+            .split(';')
+            .all(|layout| is_language_tag(layout) || is_keyboard_id(layout))
+        {
+            return Err(format!(
+                "Keyboard '{}' must be a language tag or a layout id like 0409:00000409",
+                self.locale.input_locale
+            ));
+        }
+        if self.time_zone.trim().is_empty()
+            || self.time_zone.len() > 64
+            || self.time_zone.chars().any(char::is_control)
+        {
+            return Err(format!("Invalid time zone '{}'", self.time_zone));
+        }
+        if let Some(key) = &self.product_key {
+            if !is_product_key(key) {
+                return Err("Product key must look like XXXXX-XXXXX-XXXXX-XXXXX-XXXXX".to_string());
+            }
+        }
+        if !(1..=3).contains(&self.oobe.protect_your_pc) {
+            return Err("ProtectYourPC must be 1, 2 or 3".to_string());
+        }
+        if let Some((width, height)) = self.display {
+            if width == 0 || height == 0 {
+                return Err("Display resolution must be non-zero".to_string());
+            }
+        }
+        if let Some(command) = self
+            .first_logon_commands
+            .iter()
+            .find(|c| c.command_line.trim().is_empty() || c.command_line.len() > 1024)
+        {
+            return Err(format!(
+                "First logon command '{}' must be 1 to 1024 characters",
+                command.description
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate and render the answer file
+    pub fn build(&self) -> Result<String, String> {
+        self.validate()?;
+
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<unattend xmlns=\"urn:schemas-microsoft-com:unattend\">\n");
+        self.windows_pe(&mut xml);
+        settings(&mut xml, "offlineServicing", |xml| {
+            component(xml, "Microsoft-Windows-LUA-Settings", |xml| {
+                element(xml, 3, "EnableLUA", "true");
+            });
+        });
+        settings(&mut xml, "generalize", |xml| {
+            component(xml, "Microsoft-Windows-Security-SPP", |xml| {
+                element(xml, 3, "SkipRearm", "1");
+            });
+        });
+        self.specialize(&mut xml);
+        self.oobe_system(&mut xml);
+        xml.push_str("</unattend>\n");
+        Ok(xml)
+    }
+
+    fn windows_pe(&self, xml: &mut String) {
+        settings(xml, "windowsPE", |xml| {
+            component(xml, "Microsoft-Windows-International-Core-WinPE", |xml| {
+                open(xml, 3, "SetupUILanguage");
+                element(xml, 4, "UILanguage", &self.locale.ui_language);
+                close(xml, 3, "SetupUILanguage");
+                self.international(xml);
+            });
+            component(xml, "Microsoft-Windows-Setup", |xml| {
+                xml.push_str(DISK_CONFIGURATION);
+                open(xml, 3, "UserData");
+                if let Some(key) = &self.product_key {
+                    open(xml, 4, "ProductKey");
+                    element(xml, 5, "Key", key);
+                    element(xml, 5, "WillShowUI", "Never");
+                    close(xml, 4, "ProductKey");
+                }
+                element(xml, 4, "AcceptEula", "true");
+                element(xml, 4, "FullName", &self.registered_owner);
+                close(xml, 3, "UserData");
+            });
+        });
+    }
+
+    fn specialize(&self, xml: &mut String) {
+        settings(xml, "specialize", |xml| {
+            component(xml, "Microsoft-Windows-International-Core", |xml| {
+                self.international(xml);
+            });
+            component(xml, "Microsoft-Windows-Security-SPP-UX", |xml| {
+                element(xml, 3, "SkipAutoActivation", "true");
+            });
+            component(xml, "Microsoft-Windows-SQMApi", |xml| {
+                element(xml, 3, "CEIPEnabled", "0");
+            });
+            component(xml, "Microsoft-Windows-Shell-Setup", |xml| {
+                element(xml, 3, "ComputerName", &self.computer_name);
+                if let Some(key) = &self.product_key {
+                    element(xml, 3, "ProductKey", key);
+                }
+                element(xml, 3, "TimeZone", &self.time_zone);
+            });
+        });
+    }
+
+    fn oobe_system(&self, xml: &mut String) {
+        settings(xml, "oobeSystem", |xml| {
+            component(xml, "Microsoft-Windows-International-Core", |xml| {
+                self.international(xml);
+            });
+            component(xml, "Microsoft-Windows-Shell-Setup", |xml| {
+                open(xml, 3, "AutoLogon");
+                self.password(xml, 4);
+                element(xml, 4, "Enabled", bool_str(self.auto_logon));
+                element(xml, 4, "Username", &self.username);
+                close(xml, 3, "AutoLogon");
+
+                let oobe = &self.oobe;
+                open(xml, 3, "OOBE");
+                element(xml, 4, "HideEULAPage", bool_str(oobe.hide_eula_page));
+                element(
+                    xml,
+                    4,
+                    "HideOEMRegistrationScreen",
+                    bool_str(oobe.hide_oem_registration_screen),
+                );
+                element(
+                    xml,
+                    4,
+                    "HideOnlineAccountScreens",
+                    bool_str(oobe.hide_online_account_screens),
+                );
+                element(
+                    xml,
+                    4,
+                    "HideWirelessSetupInOOBE",
+                    bool_str(oobe.hide_wireless_setup),
+                );
+                element(xml, 4, "NetworkLocation", oobe.network_location.as_str());
+                element(xml, 4, "SkipUserOOBE", bool_str(oobe.skip_user_oobe));
+                element(xml, 4, "SkipMachineOOBE", bool_str(oobe.skip_machine_oobe));
+                element(xml, 4, "ProtectYourPC", &oobe.protect_your_pc.to_string());
+                close(xml, 3, "OOBE");
+
+                if let Some((width, height)) = self.display {
+                    open(xml, 3, "Display");
+                    element(xml, 4, "ColorDepth", "32");
+                    element(xml, 4, "HorizontalResolution", &width.to_string());
+                    element(xml, 4, "RefreshRate", "60");
+                    element(xml, 4, "VerticalResolution", &height.to_string());
+                    close(xml, 3, "Display");
+                }
+
+                open(xml, 3, "UserAccounts");
+                open(xml, 4, "LocalAccounts");
+                open_add(xml, 5, "LocalAccount");
+                self.password(xml, 6);
+                element(xml, 6, "DisplayName", &self.username);
+                element(xml, 6, "Group", "Administrators");
+                element(xml, 6, "Name", &self.username);
+                close(xml, 5, "LocalAccount");
+                close(xml, 4, "LocalAccounts");
+                close(xml, 3, "UserAccounts");
+
+                element(xml, 3, "RegisteredOwner", &self.registered_owner);
+                element(xml, 3, "DisableAutoDaylightTimeSet", "false");
+
+                open(xml, 3, "FirstLogonCommands");
+                for (order, command) in self.first_logon_commands.iter().enumerate() {
+                    open_add(xml, 4, "SynchronousCommand");
+                    element(xml, 5, "Order", &(order + 1).to_string());
+                    element(xml, 5, "Description", &command.description);
+                    element(xml, 5, "CommandLine", &command.command_line);
+                    element(xml, 5, "RequiresUserInput", "false");
+                    close(xml, 4, "SynchronousCommand");
+                }
+                close(xml, 3, "FirstLogonCommands");
+                element(xml, 3, "TimeZone", &self.time_zone);
+            });
+        });
+    }
+
+    fn international(&self, xml: &mut String) {
+        element(xml, 3, "InputLocale", &self.locale.input_locale);
+        element(xml, 3, "SystemLocale", &self.locale.system_locale);
+        element(xml, 3, "UILanguage", &self.locale.ui_language);
+        element(xml, 3, "UILanguageFallback", &self.locale.ui_language);
+        element(xml, 3, "UserLocale", &self.locale.user_locale);
+    }
+
+    fn password(&self, xml: &mut String, depth: usize) {
+        open(xml, depth, "Password");
+        element(xml, depth + 1, "Value", &self.password);
+        element(xml, depth + 1, "PlainText", "true");
+        close(xml, depth, "Password");
+    }
+}
+
+/// NetBIOS-safe computer name derived from a VM name
+pub fn computer_name_for(vm_name: &str) -> String {
+    let cleaned: String = vm_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let mut name: String = cleaned.trim_matches('-').chars().take(15).collect();
+    name = name.trim_end_matches('-').to_string();
+    if name.is_empty() {
+        return "GPU-PV".to_string();
+    }
+    if name.chars().all(|c| c.is_ascii_digit()) {
+        name = format!("VM-{}", name).chars().take(15).collect();
+    }
+    name
+}
+
+fn validate_computer_name(name: &str) -> Result<(), String> {
+    let valid = (1..=15).contains(&name.len())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && !name.chars().all(|c| c.is_ascii_digit());
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Computer name '{}' must be 1-15 letters, digits or hyphens and not only digits",
+            name
+        ))
+    }
+}
+
+fn validate_username(name: &str) -> Result<(), String> {
+    const FORBIDDEN: &str = "\"/\\[]:;|=,+*?<>@";
+    if name.trim().is_empty() {
+        return Err("Username is required".to_string());
+    }
+    if name.chars().count() > 20
+        || name.ends_with('.')
+        || name
+            .chars()
+            .any(|c| FORBIDDEN.contains(c) || c.is_control())
+    {
+        return Err(format!(
+            "Username '{}' must be at most 20 characters, not end with '.' and not contain {}",
+            name, FORBIDDEN
+        ));
+    }
+    Ok(())
+}
+
+/// `en-US`, `zh-Hans-CN` and similar
+fn is_language_tag(tag: &str) -> bool {
+    let mut parts = tag.split('-');
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|p| (2..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// `0409:00000409`: language id and keyboard layout id in hex
+fn is_keyboard_id(id: &str) -> bool {
+    let is_hex = |s: &str, len: usize| s.len() == len && s.chars().all(|c| c.is_ascii_hexdigit());
+    matches!(id.split_once(':'), Some((lang, layout)) if is_hex(lang, 4) && is_hex(layout, 8))
+}
+
+fn is_product_key(key: &str) -> bool {
+    let groups: Vec<&str> = key.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .all(|g| g.len() == 5 && g.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn bool_str(value: bool) -> &'static str {
+    if value {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+fn settings(xml: &mut String, pass: &str, body: impl FnOnce(&mut String)) {
+    xml.push_str(&format!("  <settings pass=\"{}\">\n", pass));
+    body(xml);
+    xml.push_str("  </settings>\n");
+}
+
+fn component(xml: &mut String, name: &str, body: impl FnOnce(&mut String)) {
+    xml.push_str(&format!(
+        "    <component name=\"{}\" {}>\n",
+        name, COMPONENT_ATTRS
+    ));
+    body(xml);
+    xml.push_str("    </component>\n");
+}
+
+fn indent(xml: &mut String, depth: usize) {
+    xml.push_str(&"  ".repeat(depth));
+}
+
+fn open(xml: &mut String, depth: usize, tag: &str) {
+    indent(xml, depth);
+    xml.push_str(&format!("<{}>\n", tag));
+}
+
+/// Open a list item (`wcm:action="add"`)
+fn open_add(xml: &mut String, depth: usize, tag: &str) {
+    indent(xml, depth);
+    xml.push_str(&format!("<{} wcm:action=\"add\">\n", tag));
+}
+
+fn close(xml: &mut String, depth: usize, tag: &str) {
+    indent(xml, depth);
+    xml.push_str(&format!("</{}>\n", tag));
+}
+
+fn element(xml: &mut String, depth: usize, tag: &str, text: &str) {
+    indent(xml, depth);
+    xml.push_str(&format!("<{0}>{1}</{0}>\n", tag, xml_text(text)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::template::UNATTEND_PASSWORD_TOKEN;
+
+    fn answer_file(auto_logon: bool, username: &str, options: UnattendOptions) -> String {
+        UnattendBuilder::for_vm(
+            "Win11 GPU",
+            username,
+            UNATTEND_PASSWORD_TOKEN,
+            auto_logon,
+            &options,
+        )
+        .build()
+        .unwrap()
+    }
+
+    /// Byte-for-byte comparison, reporting the first differing line
+    fn assert_golden(actual: &str, expected: &str) {
+        if actual == expected {
+            return;
+        }
+        let line = actual
+            .lines()
+            .zip(expected.lines())
+            .position(|(a, e)| a != e)
+            .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+        panic!(
+            "answer file differs from its fixture at line {}:\n  actual:   {:?}\n  expected: {:?}",
+            line + 1,
+            actual.lines().nth(line),
+            expected.lines().nth(line)
+        );
+    }
+
+    #[test]
+    fn default_answer_file() {
+        assert_golden(
+            &answer_file(true, "gpu", UnattendOptions::default()),
+            include_str!("testdata/unattend/default.xml"),
+        );
+    }
+
+    #[test]
+    fn edition_answer_files() {
+        let enterprise = UnattendOptions {
+            product_key: Some(" nppr9-fwdcx-d2c8j-h872k-2yt43 ".to_string()),
+            ..Default::default()
+        };
+        assert_golden(
+            &answer_file(true, "gpu", enterprise),
+            include_str!("testdata/unattend/edition_enterprise.xml"),
+        );
+
+        let from_media = UnattendBuilder::for_vm(
+            "Win11 GPU",
+            "gpu",
+            UNATTEND_PASSWORD_TOKEN,
+            true,
+            &UnattendOptions::default(),
+        )
+        .product_key(None)
+        .build()
+        .unwrap();
+        assert_golden(
+            &from_media,
+            include_str!("testdata/unattend/edition_from_media.xml"),
+        );
+    }
+
+    #[test]
+    fn locale_answer_file() {
+        let german = UnattendOptions {
+            computer_name: Some("LAB-GPU-01".to_string()),
+            locale: Some("de-DE".to_string()),
+            keyboard: Some("0407:00000407".to_string()),
+            time_zone: Some("W. Europe Standard Time".to_string()),
+            product_key: None,
+        };
+        assert_golden(
+            &answer_file(true, "gpu", german),
+            include_str!("testdata/unattend/locale_de.xml"),
+        );
+    }
+
+    #[test]
+    fn autologon_off_answer_file() {
+        assert_golden(
+            &answer_file(false, "R&D Ops", UnattendOptions::default()),
+            include_str!("testdata/unattend/autologon_off.xml"),
+        );
+    }
+}
@@ -1,101 +1,284 @@
+use serde::de::DeserializeOwned;
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt as _;
+#[cfg(windows)]
 use std::os::windows::process::CommandExt;
-use std::process::Command;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::State;
 
-/// Execute a PowerShell command and return the output
+use super::audit::AuditContext;
+use super::error::{classify_powershell_error, HyperVError};
+use super::executor::{ExecutorState, PowerShellExecutor, RunOptions};
+use super::script::{encode_command, ScriptBuilder};
+
+/// CREATE_NO_WINDOW process creation flag
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Runs the script sent by `send_script`. Other processes can read a command
+/// line, and scripts carry bound secrets such as the VM password and host
+/// credentials, so one-shot runs take their script from stdin instead.
+const STDIN_SCRIPT: &str = ". ([scriptblock]::Create([Text.Encoding]::Unicode.GetString([Convert]::FromBase64String([Console]::In.ReadLine()))))";
+
+/// How often a running script is checked for completion, timeout or cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Build a PowerShell command for the given script
 /// UTF-8 encoding is automatically set for proper character handling
-pub fn run_powershell(script: &str) -> Result<String, String> {
-    // Wrap script with UTF-8 encoding setup
+pub fn powershell_command(script: &str) -> Command {
+    // Wrap script with UTF-8 encoding setup; progress records would otherwise end up on stderr
     let utf8_script = format!(
-        "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8; {}",
+        "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8; $ProgressPreference = 'SilentlyContinue'; {}",
         script
     );
 
-    let output = Command::new("powershell")
-        .args([
-            "-NoProfile",
-            "-ExecutionPolicy",
-            "Bypass",
-            "-Command",
-            &utf8_script,
-        ])
-        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-        .output()
-        .map_err(|e| format!("Failed to execute PowerShell: {}", e))?;
+    // Pass the script encoded so quotes in it survive command-line parsing
+    let mut command = Command::new("powershell");
+    command.args([
+        "-NoProfile",
+        "-ExecutionPolicy",
+        "Bypass",
+        "-EncodedCommand",
+        &encode_command(&utf8_script),
+    ]);
+    #[cfg(windows)]
+    command.creation_flags(CREATE_NO_WINDOW);
+    // Lead a process group so `kill_process_tree` reaches its children too
+    #[cfg(unix)]
+    command.process_group(0);
+    command
+}
 
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+/// Map the captured output of a finished PowerShell invocation to a result:
+/// trimmed stdout on success, classified stderr (or stdout if stderr is empty) on failure
+pub fn powershell_result(
+    success: bool,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> Result<String, HyperVError> {
+    if success {
+        Ok(String::from_utf8_lossy(stdout).trim().to_string())
     } else {
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stderr = String::from_utf8_lossy(stderr).trim().to_string();
         if stderr.is_empty() {
-            Err(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            Err(classify_powershell_error(&String::from_utf8_lossy(stdout)))
         } else {
-            Err(stderr)
+            Err(classify_powershell_error(&stderr))
+        }
+    }
+}
+
+/// Parse the output of `ConvertTo-Json` into a list of items.
+/// PowerShell emits nothing for an empty pipeline and a bare object (not an array)
+/// for a single item, so both shapes are accepted.
+pub fn parse_json_list<T: DeserializeOwned>(output: &str) -> Result<Vec<T>, String> {
+    let output = output.trim();
+    if output.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let value: serde_json::Value =
+        serde_json::from_str(output).map_err(|e| format!("Invalid JSON from PowerShell: {}", e))?;
+    let items = match value {
+        serde_json::Value::Null => Vec::new(),
+        serde_json::Value::Array(items) => items,
+        item => vec![item],
+    };
+
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            serde_json::from_value(item)
+                .map_err(|e| format!("Unexpected PowerShell output (item {}): {}", i, e))
+        })
+        .collect()
+}
+
+/// Execute a PowerShell command and return the output
+pub fn run_powershell(script: &str) -> Result<String, HyperVError> {
+    run_powershell_with(script, &RunOptions::default())
+}
+
+/// Execute a PowerShell command, killing it (and its child tree) when the
+/// timeout elapses or the cancellation token fires
+pub fn run_powershell_with(script: &str, options: &RunOptions) -> Result<String, HyperVError> {
+    let mut child =
+        spawn_powershell(script).map_err(|e| format!("Failed to execute PowerShell: {}", e))?;
+
+    // Drain both pipes on threads so a chatty script cannot block on a full pipe
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let started = Instant::now();
+    let timeout = options.timeout();
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| format!("Failed to wait on PowerShell: {}", e))?
+        {
+            break status;
+        }
+        if options.is_cancelled() {
+            let _ = kill_process_tree(child.id());
+            let _ = child.wait();
+            return Err(HyperVError::cancelled("PowerShell call was cancelled"));
+        }
+        if started.elapsed() >= timeout {
+            let _ = kill_process_tree(child.id());
+            let _ = child.wait();
+            return Err(HyperVError::timeout(format!(
+                "PowerShell call did not finish within {}s",
+                timeout.as_secs()
+            )));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    powershell_result(status.success(), &stdout, &stderr)
+}
+
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Kill a process together with all of its children. Elsewhere than on
+/// Windows the children are those in the process group `powershell_command`
+/// starts; a process leading no group is killed on its own.
+pub fn kill_process_tree(pid: u32) -> Result<(), HyperVError> {
+    let kill = |args: &[&str]| {
+        let mut command = Command::new(if cfg!(windows) { "taskkill" } else { "kill" });
+        command.args(args);
+        #[cfg(windows)]
+        command.creation_flags(CREATE_NO_WINDOW);
+        command
+            .output()
+            .map_err(|e| format!("Failed to kill process {}: {}", pid, e))
+    };
+
+    let pid_arg = pid.to_string();
+    let output = if cfg!(windows) {
+        kill(&["/PID", &pid_arg, "/T", "/F"])?
+    } else {
+        match kill(&["-9", "--", &format!("-{}", pid)])? {
+            group if group.status.success() => group,
+            _ => kill(&["-9", &pid_arg])?,
         }
+    };
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(HyperVError::internal(format!(
+            "Failed to kill process {}: {}",
+            pid,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
     }
 }
 
+/// `name` as a single file name: the hex of its UTF-8 bytes, so no VM or host
+/// name can reach outside the directory it is joined to
+pub fn hex_file_name(name: &str) -> String {
+    name.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Unix time in milliseconds
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 /// Spawn a PowerShell command and return the Child process
 /// This allows for streaming output and cancellation
-pub fn spawn_powershell(script: &str) -> std::io::Result<std::process::Child> {
-    // Wrap script with UTF-8 encoding setup
-    let utf8_script = format!(
-        "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8; {}",
-        script
-    );
+pub fn spawn_powershell(script: &str) -> std::io::Result<Child> {
+    let mut child = powershell_command(STDIN_SCRIPT)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Err(e) = send_script(&mut child, script) {
+        let _ = kill_process_tree(child.id());
+        let _ = child.wait();
+        return Err(e);
+    }
+    Ok(child)
+}
+
+/// Write `script` to the stdin of a child running `STDIN_SCRIPT` as one line
+/// of Base64 UTF-16LE, then close it
+fn send_script(child: &mut Child, script: &str) -> std::io::Result<()> {
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    writeln!(stdin, "{}", encode_command(script))?;
+    stdin.flush()
+}
 
-    Command::new("powershell")
-        .args([
-            "-NoProfile",
-            "-ExecutionPolicy",
-            "Bypass",
-            "-Command",
-            &utf8_script,
-        ])
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-        .spawn()
-}
-
-pub fn is_admin_sync() -> bool {
+pub fn is_admin_sync(ps: &dyn PowerShellExecutor) -> bool {
     // Check if running as admin using PowerShell
     let script = "([Security.Principal.WindowsPrincipal] [Security.Principal.WindowsIdentity]::GetCurrent()).IsInRole([Security.Principal.WindowsBuiltInRole]::Administrator)";
-    match run_powershell(script) {
+    match ps.run(script) {
         Ok(output) => output.trim().to_lowercase() == "true",
         Err(_) => false,
     }
 }
 
-pub fn restart_as_admin_sync() -> Result<(), String> {
+pub fn restart_as_admin_sync(ps: &dyn PowerShellExecutor) -> Result<(), HyperVError> {
     let current_exe = std::env::current_exe().map_err(|e| e.to_string())?;
 
     // Use PowerShell Start-Process -Verb RunAs to relaunch
-    let script = format!(
-        "Start-Process -FilePath '{}' -Verb RunAs",
-        current_exe.to_string_lossy()
-    );
+    let script = ScriptBuilder::new()
+        .arg("ExePath", &current_exe.to_string_lossy())
+        .build("Start-Process -FilePath $ExePath -Verb RunAs");
 
     // We run this and don't wait for output, as the current process should exit shortly after
-    let _ = run_powershell(&script);
+    let _ = ps.run(&script);
 
     std::process::exit(0);
 }
 
 #[tauri::command]
-pub async fn is_admin() -> bool {
-    is_admin_sync()
+pub async fn is_admin(ps: State<'_, ExecutorState>) -> Result<bool, HyperVError> {
+    Ok(is_admin_sync(
+        ps.local_executor(AuditContext::new("is_admin")).as_ref(),
+    ))
 }
 
 #[tauri::command]
-pub async fn restart_as_admin() -> Result<(), String> {
-    restart_as_admin_sync()
+pub async fn restart_as_admin(ps: State<'_, ExecutorState>) -> Result<(), HyperVError> {
+    restart_as_admin_sync(
+        ps.local_executor(AuditContext::new("restart_as_admin"))
+            .as_ref(),
+    )
 }
 
 #[tauri::command]
-pub async fn get_host_drives() -> Result<Vec<String>, String> {
+pub async fn get_host_drives(ps: State<'_, ExecutorState>) -> Result<Vec<String>, HyperVError> {
+    // Drives are redirected from this machine into RDP sessions, even for remote hosts
+    ps.run_blocking_local(
+        AuditContext::new("get_host_drives"),
+        None,
+        get_host_drives_sync,
+    )
+    .await
+}
+
+/// List local fixed disks using the given executor
+pub fn get_host_drives_sync(ps: &dyn PowerShellExecutor) -> Result<Vec<String>, HyperVError> {
     // Get list of local fixed disks (DriveType = 3)
     let script = "Get-CimInstance -ClassName Win32_LogicalDisk | Where-Object { $_.DriveType -eq 3 } | Select-Object -ExpandProperty DeviceID";
-    let output = run_powershell(script)?;
+    let output = ps.run(script)?;
 
     // Parse output lines into vector
     let drives = output
@@ -106,3 +289,72 @@ pub async fn get_host_drives() -> Result<Vec<String>, String> {
 
     Ok(drives)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn kill_process_tree_reaches_children() {
+        let mut child = group_shell("sleep 30 & echo $!; wait")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        std::io::BufRead::read_line(
+            &mut std::io::BufReader::new(child.stdout.as_mut().unwrap()),
+            &mut line,
+        )
+        .unwrap();
+        let grandchild = line.trim().to_string();
+
+        kill_process_tree(child.id()).unwrap();
+        child.wait().unwrap();
+        // Orphans may linger as zombies until reaped; those are dead too
+        let started = Instant::now();
+        let running = loop {
+            let running = std::fs::read_to_string(format!("/proc/{}/stat", grandchild))
+                .is_ok_and(|stat| !stat.contains(") Z "));
+            if !running || started.elapsed() > Duration::from_secs(2) {
+                break running;
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        assert!(!running, "child {} survived", grandchild);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn scripts_are_sent_on_stdin() {
+        let script = "$Password = 'hunter2'\nWrite-Output 'é'";
+        let mut child = group_shell("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        send_script(&mut child, script).unwrap();
+        let output = child.wait_with_output().unwrap();
+        let line = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(line, format!("{}\n", encode_command(script)));
+    }
+
+    /// A shell in its own process group, like `powershell_command` starts
+    #[cfg(target_os = "linux")]
+    fn group_shell(script: &str) -> Command {
+        use std::os::unix::process::CommandExt;
+
+        let mut command = Command::new("sh");
+        command.args(["-c", script]).process_group(0);
+        command
+    }
+
+    #[test]
+    fn parse_json_list_accepts_both_shapes() {
+        assert_eq!(parse_json_list::<u32>("").unwrap(), Vec::<u32>::new());
+        assert_eq!(parse_json_list::<u32>("null").unwrap(), Vec::<u32>::new());
+        assert_eq!(parse_json_list::<u32>("7").unwrap(), vec![7]);
+        assert_eq!(parse_json_list::<u32>("[1,2]").unwrap(), vec![1, 2]);
+        assert!(parse_json_list::<u32>("[1,\"x\"]").is_err());
+    }
+}
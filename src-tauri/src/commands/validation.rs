@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use super::error::{ErrorDetails, HyperVError};
+use super::executor::PowerShellExecutor;
+use super::plan::{ResourceCheck, Severity};
+use super::script::ScriptBuilder;
+
+/// Paths and names on the Hyper-V host a VM config refers to
+pub struct HostProbe<'a> {
+    pub vm_name: &'a str,
+    /// Virtual disk the VM would be created with
+    pub vhd_file: &'a str,
+    pub data_disk_file: Option<&'a str>,
+    /// Directory the disks are created in
+    pub vhd_dir: &'a str,
+    /// ISO or golden image the disk is created from
+    pub source_path: &'a str,
+}
+
+/// State of the Hyper-V host that VM configs are validated against
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct HostFacts {
+    pub vm_exists: bool,
+    pub vhd_exists: bool,
+    pub data_disk_exists: bool,
+    pub vhd_dir_exists: bool,
+    pub source_exists: bool,
+    /// Size of the source file, if it exists
+    pub source_bytes: Option<u64>,
+    /// Free space on the volume holding the VHD directory, if it could be read
+    pub free_disk_bytes: Option<u64>,
+    pub free_memory_bytes: u64,
+    pub total_memory_bytes: u64,
+    pub logical_processors: u32,
+    pub switches: Vec<String>,
+    /// Names of the host's display adapters
+    pub gpus: Vec<String>,
+    /// Number of GPUs Hyper-V can partition
+    pub partitionable_gpus: u32,
+    /// PROCESSOR_ARCHITECTURE of the host, e.g. AMD64
+    pub architecture: String,
+}
+
+/// Gather everything `probe` needs checked in one PowerShell call
+pub fn host_facts(
+    ps: &dyn PowerShellExecutor,
+    probe: &HostProbe,
+) -> Result<HostFacts, HyperVError> {
+    let script = ScriptBuilder::new()
+        .with_get_vm_exact()
+        .arg("VMName", probe.vm_name)
+        .arg("VhdFile", probe.vhd_file)
+        .arg("DataDiskFile", probe.data_disk_file.unwrap_or(""))
+        .arg("VhdDir", probe.vhd_dir)
+        .arg("SourcePath", probe.source_path)
+        .build(
+            r#"
+            function Test-LiteralPath($Path) { [bool]($Path -and (Test-Path -LiteralPath $Path)) }
+            $os = Get-CimInstance Win32_OperatingSystem
+            $cs = Get-CimInstance Win32_ComputerSystem
+            $free = $null
+            if (Test-LiteralPath $VhdDir) {
+                $drive = (Get-Item -LiteralPath $VhdDir).PSDrive
+                if ($drive -and $drive.Free -ne $null) { $free = [uint64]$drive.Free }
+            }
+            $sourceBytes = $null
+            if (Test-LiteralPath $SourcePath) { $sourceBytes = [uint64](Get-Item -LiteralPath $SourcePath).Length }
+            # Renamed in Windows 11 / Server 2022
+            if (Get-Command Get-VMHostPartitionableGpu -ErrorAction SilentlyContinue) {
+                $gpus = @(Get-VMHostPartitionableGpu)
+            } else {
+                $gpus = @(Get-VMPartitionableGpu -ErrorAction SilentlyContinue)
+            }
+            [PSCustomObject]@{
+                vm_exists = [bool]($VMName -and (Get-VMExact $VMName -ErrorAction SilentlyContinue))
+                vhd_exists = Test-LiteralPath $VhdFile
+                data_disk_exists = Test-LiteralPath $DataDiskFile
+                vhd_dir_exists = Test-LiteralPath $VhdDir
+                source_exists = Test-LiteralPath $SourcePath
+                source_bytes = $sourceBytes
+                free_disk_bytes = $free
+                free_memory_bytes = [uint64]$os.FreePhysicalMemory * 1KB
+                total_memory_bytes = [uint64]$cs.TotalPhysicalMemory
+                logical_processors = [int]$cs.NumberOfLogicalProcessors
+                switches = @(Get-VMSwitch | ForEach-Object { "$($_.Name)" })
+                gpus = @(Get-CimInstance Win32_VideoController | ForEach-Object { "$($_.Name)" })
+                partitionable_gpus = $gpus.Count
+                architecture = "$env:PROCESSOR_ARCHITECTURE"
+            } | ConvertTo-Json -Compress
+            "#,
+        );
+    let output = ps.run(&script)?;
+    serde_json::from_str(output.trim())
+        .map_err(|e| HyperVError::internal(format!("Failed to read host state: {}", e)))
+}
+
+/// A failed check, attributed to the config field it is about
+#[derive(Serialize, Clone, Debug)]
+pub struct FieldIssue {
+    pub field: String,
+    /// Name of the check that failed
+    pub check: String,
+    pub message: String,
+}
+
+/// Field-level outcome of validating a VM config
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ValidationReport {
+    /// `true` when there are no errors; warnings do not block creation
+    pub valid: bool,
+    pub errors: Vec<FieldIssue>,
+    pub warnings: Vec<FieldIssue>,
+}
+
+impl ValidationReport {
+    pub fn from_checks(checks: &[ResourceCheck]) -> Self {
+        let mut report = ValidationReport::default();
+        for check in checks.iter().filter(|c| !c.passed) {
+            let issue = FieldIssue {
+                field: check.field.clone(),
+                check: check.name.clone(),
+                message: check.message.clone(),
+            };
+            match check.severity {
+                Severity::Error => report.errors.push(issue),
+                Severity::Warning => report.warnings.push(issue),
+            }
+        }
+        report.valid = report.errors.is_empty();
+        report
+    }
+
+    /// The report, or an `InvalidInput` error naming the first error and
+    /// listing the rest in its hint
+    pub fn into_result(self) -> Result<Self, HyperVError> {
+        let Some((first, rest)) = self.errors.split_first() else {
+            return Ok(self);
+        };
+        Err(HyperVError::InvalidInput(ErrorDetails {
+            message: first.message.clone(),
+            hint: (!rest.is_empty()).then(|| {
+                let rest: Vec<&str> = rest.iter().map(|i| i.message.as_str()).collect();
+                format!("Also: {}.", rest.join("; "))
+            }),
+            record: None,
+        }))
+    }
+}
@@ -1,34 +1,51 @@
+use super::audit::redact;
+use super::audit::AuditContext;
+use super::checkpoint::{verify, Checkpoint, PhaseState, PhaseStateStore, CHECKPOINT_MARKER};
 use super::config::{VMConnectionSettings, VMSettingsStore};
-use super::utils::{run_powershell, spawn_powershell};
+use super::disk::{DiskAllocation, DiskOptions};
+use super::error::{ErrorDetails, HyperVError};
+use super::executor::{ExecutorState, PowerShellExecutor, PowerShellProcess};
+use super::host::{restrict_dir_acl, stage_on_host};
+use super::iso::{inspect_iso_file, normalize_architecture, IsoInfo};
+use super::jobs::{JobLogLine, JobRegistry, ProvisioningJob, JOB_LOG_EVENT};
+use super::lock::{LockPolicy, VmLock, VmLocks};
+use super::plan::{hyperv_cmdlets, ExecutionPlan, PlannedFile, ResourceCheck, RunOutcome};
+use super::progress::{
+    parse_marker, ProgressEvent, ProgressTracker, PROGRESS_EVENT, PROGRESS_MARKER, PROVISION_PHASES,
+};
+use super::rollback::{
+    rollback, scrub_staging_dir, staging_root, undo, ProvisionResource, RollbackReport,
+    RESOURCE_MARKER,
+};
+use super::runlog::{LogStream, RunLogStore};
+use super::script::ScriptBuilder;
+use super::template::{PROVISION_SCRIPT, UNATTEND_PASSWORD_TOKEN};
+use super::unattend::{UnattendBuilder, UnattendOptions};
+use super::utils::parse_json_list;
+use super::validation::{host_facts, HostFacts, HostProbe, ValidationReport};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use tauri::{Emitter, Manager, State, Window};
 
-/// Shared state for tracking VM provisioning process
-pub struct ProvisioningState {
-    // Current running process ID for cancellation
-    pub current_pid: Arc<Mutex<Option<u32>>>,
-}
-
-impl Default for ProvisioningState {
-    fn default() -> Self {
-        Self {
-            current_pid: Arc::new(Mutex::new(None)),
-        }
-    }
-}
+/// Start/stop/remove can wait on guest shutdown or merging checkpoints
+const LIFECYCLE_TIMEOUT: Duration = Duration::from_secs(300);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct VMConfig {
     name: String,
     disk_size_gb: u32,
     memory_gb: u32,
     cpu_cores: u32,
     iso_path: String,
+    /// Index of the image in the ISO's install.wim/install.esd; ISO
+    /// sources must pick one, golden images have none
+    #[serde(default)]
+    edition: Option<u32>,
     tpm_enabled: bool,
     secure_boot: bool,
     network_switch: String,
@@ -38,6 +55,38 @@ pub struct VMConfig {
     username: String,
     password: String,
     auto_logon: bool,
+    #[serde(default)]
+    unattend: UnattendOptions,
+    #[serde(default)]
+    source: ProvisionSource,
+    #[serde(default)]
+    disk: DiskOptions,
+}
+
+/// Where the disk of a new VM comes from
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProvisionSource {
+    /// Install Windows from `iso_path`
+    #[default]
+    Iso,
+    /// Start from a sysprepped VHDX, copied or as the parent of a
+    /// differencing disk. The disk size is the golden image's.
+    GoldenImage {
+        path: String,
+        #[serde(default)]
+        differencing: bool,
+    },
+}
+
+impl VMConfig {
+    /// ISO or golden image the VM's disk is created from
+    fn source_path(&self) -> &str {
+        match &self.source {
+            ProvisionSource::Iso => &self.iso_path,
+            ProvisionSource::GoldenImage { path, .. } => path,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -49,7 +98,7 @@ pub struct VMProgress {
     error: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct VMInfo {
     name: String,
     state: String,
@@ -62,42 +111,49 @@ pub struct VMInfo {
     ip_address: Option<String>,
 }
 
-#[derive(serde::Serialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct NetworkSwitch {
     name: String,
     switch_type: String,
 }
 
 #[tauri::command]
-pub async fn get_network_switches() -> Result<Vec<NetworkSwitch>, String> {
+pub async fn get_network_switches(
+    ps: State<'_, ExecutorState>,
+) -> Result<Vec<NetworkSwitch>, HyperVError> {
+    ps.run_blocking(
+        AuditContext::new("get_network_switches"),
+        None,
+        get_network_switches_sync,
+    )
+    .await
+}
+
+pub fn get_network_switches_sync(
+    ps: &dyn PowerShellExecutor,
+) -> Result<Vec<NetworkSwitch>, HyperVError> {
     let script = r#"
-    Get-VMSwitch | Select-Object Name, SwitchType | ForEach-Object {
-        "$($_.Name)|$($_.SwitchType)"
-    }
+    $switches = @(Get-VMSwitch | ForEach-Object {
+        [PSCustomObject]@{
+            name = "$($_.Name)"
+            switch_type = "$($_.SwitchType)"
+        }
+    })
+    ConvertTo-Json -InputObject $switches -Compress
     "#;
-    let output = run_powershell(script)?;
-
-    let switches = output
-        .lines()
-        .filter_map(|line| {
-            let parts: Vec<&str> = line.split('|').collect();
-            if parts.len() >= 2 {
-                Some(NetworkSwitch {
-                    name: parts[0].trim().to_string(),
-                    switch_type: parts[1].trim().to_string(),
-                })
-            } else {
-                None
-            }
-        })
-        .collect();
+    let output = ps.run(script)?;
 
-    Ok(switches)
+    parse_json_list(&output)
+        .map_err(|e| HyperVError::internal(format!("Failed to read network switches: {}", e)))
 }
 
 #[tauri::command]
-pub async fn get_default_vhd_path() -> Result<String, String> {
-    let output = run_powershell("Get-VMHost | Select-Object -ExpandProperty VirtualHardDiskPath")?;
+pub async fn get_default_vhd_path(ps: State<'_, ExecutorState>) -> Result<String, HyperVError> {
+    let output = ps
+        .run_blocking(AuditContext::new("get_default_vhd_path"), None, |ps| {
+            ps.run("Get-VMHost | Select-Object -ExpandProperty VirtualHardDiskPath")
+        })
+        .await?;
     let path = output.trim().to_string();
     if path.is_empty() {
         Ok("C:\\Users\\Public\\Documents\\Hyper-V\\Virtual Hard Disks\\".to_string())
@@ -106,178 +162,1119 @@ pub async fn get_default_vhd_path() -> Result<String, String> {
     }
 }
 
+/// Field-level errors and warnings for creating `config` on the active host
 #[tauri::command]
-pub async fn validate_vm_config(config: VMConfig) -> Result<(), String> {
-    if config.name.is_empty() {
-        return Err("VM Name is required".to_string());
+pub async fn validate_vm_config(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    config: VMConfig,
+) -> Result<ValidationReport, HyperVError> {
+    let mut checks = host_resource_checks(&ps, &config, false).await?;
+    if has_interrupted_run(window.app_handle(), &ps, &config.name) {
+        checks.push(
+            ResourceCheck::new(
+                "interrupted_run",
+                false,
+                interrupted_run_error(&config.name).message(),
+            )
+            .field("name"),
+        );
     }
-    // Basic validation
-    Ok(())
+    Ok(ValidationReport::from_checks(&checks))
 }
 
 #[tauri::command]
-pub async fn update_vm(_name: String, _config: VMConfig) -> Result<(), String> {
+pub async fn update_vm(_name: String, _config: VMConfig) -> Result<(), HyperVError> {
     // Placeholder for update logic if needed
     Ok(())
 }
 
-// Internal helper for checking resources before VM creation
-fn check_vm_resources(
-    name: &str,
-    memory_mb: u32,
-    disk_size_gb: u32,
-    vhd_path: &str,
-    iso_path: &str,
-) -> Result<(), String> {
+/// Refuse to create a VM while any check reports an error
+fn check_vm_resources(checks: &[ResourceCheck]) -> Result<ValidationReport, HyperVError> {
+    ValidationReport::from_checks(checks).into_result()
+}
+
+/// Whether an interrupted provisioning run of VM `name` exists on the active
+/// host. Its VM and disks are reused on resume, or rolled back by
+/// `discard_provisioning_run`.
+fn has_interrupted_run(app: &tauri::AppHandle, ps: &ExecutorState, name: &str) -> bool {
+    let host = ps.host().map(|h| h.name);
+    PhaseStateStore::new(app)
+        .load(host.as_deref(), name)
+        .is_some()
+}
+
+/// Refusal to start a fresh run of `name` over an interrupted one, whose VM
+/// and disks are only removed when the user asks for it
+fn interrupted_run_error(name: &str) -> HyperVError {
+    HyperVError::InvalidInput(ErrorDetails {
+        message: format!("VM '{}' has an interrupted provisioning run", name),
+        hint: Some("Resume it, or discard it to remove the VM and disks it created.".to_string()),
+        record: None,
+    })
+}
+
+/// Resource checks for `config` against the state of the active host
+async fn host_resource_checks(
+    ps: &ExecutorState,
+    config: &VMConfig,
+    interrupted: bool,
+) -> Result<Vec<ResourceCheck>, HyperVError> {
+    let config = config.clone();
+    // The inspector reads the ISO from this machine, so only the local
+    // host's ISOs are inspected; a remote one is reported as unverified
+    let is_iso = matches!(config.source, ProvisionSource::Iso);
+    let remote_host = ps.host().map(|h| h.hostname);
+    let inspect = remote_host.is_none() && is_iso;
+    ps.run_blocking(
+        AuditContext::new("check_vm_resources").vm(&config.name),
+        None,
+        move |ps| {
+            let vhd_file = vhd_file_path(&config);
+            let data_disk_file = data_disk_file_path(&config);
+            let facts = host_facts(
+                ps,
+                &HostProbe {
+                    vm_name: &config.name,
+                    vhd_file: &vhd_file,
+                    data_disk_file: data_disk_file.as_deref(),
+                    vhd_dir: &config.vhd_path,
+                    source_path: config.source_path(),
+                },
+            )?;
+            let iso = (inspect && facts.source_exists)
+                .then(|| inspect_iso_file(Path::new(&config.iso_path), false));
+            let mut checks = vm_resource_checks(&config, &facts, iso.as_ref(), interrupted);
+            if let (Some(hostname), true, true) = (&remote_host, is_iso, facts.source_exists) {
+                checks.push(
+                    ResourceCheck::new(
+                        "iso_contents",
+                        false,
+                        format!(
+                            "ISO on {} was not inspected; its install image, architecture and editions are unverified",
+                            hostname
+                        ),
+                    )
+                    .field("iso_path")
+                    .warning(),
+                );
+            }
+            Ok(checks)
+        },
+    )
+    .await
+}
+
+/// Individual resource checks run before VM creation, in order. With an
+/// `interrupted` run, the VM and disks it created are expected to exist.
+/// `iso` is the inspection of the ISO source, when it could be inspected.
+fn vm_resource_checks(
+    config: &VMConfig,
+    facts: &HostFacts,
+    iso: Option<&Result<IsoInfo, HyperVError>>,
+    interrupted: bool,
+) -> Vec<ResourceCheck> {
+    const GB: u64 = 1024 * 1024 * 1024;
+    let name = config.name.as_str();
+    let name_problem = vm_name_problem(name);
+    let memory_mb = u64::from(config.memory_gb) * 1024;
+    let disk_size_gb = config.disk_size_gb;
+    let vhd_path = config.vhd_path.as_str();
+    let source = &config.source;
+    let source_path = config.source_path();
+
     // Simple basic checks
-    if name.is_empty() {
-        return Err("VM Name cannot be empty".to_string());
+    let mut checks = vec![
+        ResourceCheck::new(
+            "name",
+            name_problem.is_none(),
+            name_problem.unwrap_or("VM name is set"),
+        )
+        .field("name"),
+        ResourceCheck::new(
+            "memory",
+            memory_mb >= 2048,
+            if memory_mb >= 2048 {
+                format!("{} MB of memory", memory_mb)
+            } else {
+                "Minimum memory is 2GB".to_string()
+            },
+        )
+        .field("memory_gb"),
+        ResourceCheck::new(
+            "disk_size",
+            disk_size_gb >= 20,
+            if disk_size_gb >= 20 {
+                format!("{} GB disk", disk_size_gb)
+            } else {
+                "Minimum disk size is 20GB".to_string()
+            },
+        )
+        .field("disk_size_gb"),
+    ];
+
+    if !interrupted {
+        checks.push(
+            ResourceCheck::new(
+                "vm_name_unique",
+                !facts.vm_exists,
+                if facts.vm_exists {
+                    format!("A VM named '{}' already exists", name)
+                } else {
+                    format!("No VM named '{}' yet", name)
+                },
+            )
+            .field("name"),
+        );
+        let vhd_file = vhd_file_path(config);
+        checks.push(
+            ResourceCheck::new(
+                "vhd_file",
+                !facts.vhd_exists,
+                if facts.vhd_exists {
+                    format!("Virtual disk already exists: {}", vhd_file)
+                } else {
+                    format!("Virtual disk will be created: {}", vhd_file)
+                },
+            )
+            .field("vhd_path"),
+        );
+        if let Some(data_disk_file) = data_disk_file_path(config) {
+            checks.push(
+                ResourceCheck::new(
+                    "data_disk_file",
+                    !facts.data_disk_exists,
+                    if facts.data_disk_exists {
+                        format!("Data disk already exists: {}", data_disk_file)
+                    } else {
+                        format!("Data disk will be created: {}", data_disk_file)
+                    },
+                )
+                .field("disk"),
+            );
+        }
     }
-    if memory_mb < 2048 {
-        return Err("Minimum memory is 2GB".to_string());
+
+    // Check paths
+    checks.push(ResourceCheck::new(
+        "vhd_path",
+        facts.vhd_dir_exists,
+        if facts.vhd_dir_exists {
+            format!("VHD Path exists: {}", vhd_path)
+        } else {
+            format!("VHD Path does not exist: {}", vhd_path)
+        },
+    ));
+
+    let (id, field, label) = match source {
+        ProvisionSource::Iso => ("iso_path", "iso_path", "ISO Path"),
+        ProvisionSource::GoldenImage { .. } => ("source_vhd", "source", "Golden image"),
+    };
+    checks.push(
+        ResourceCheck::new(
+            id,
+            facts.source_exists,
+            if facts.source_exists {
+                format!("{} exists: {}", label, source_path)
+            } else {
+                format!("{} does not exist: {}", label, source_path)
+            },
+        )
+        .field(field),
+    );
+
+    // Guessing an index could install the wrong edition
+    if matches!(source, ProvisionSource::Iso) && config.edition.is_none() {
+        checks.push(
+            ResourceCheck::new("edition", false, "Choose the Windows edition to install")
+                .field("edition"),
+        );
     }
-    if disk_size_gb < 20 {
-        return Err("Minimum disk size is 20GB".to_string());
+
+    // Only Windows installation media can be converted to a disk
+    if let Some(iso) = iso {
+        let (passed, message) = match iso {
+            Ok(info) if info.is_windows_installer() => (
+                true,
+                format!(
+                    "ISO has {}{}{}",
+                    info.install_image.as_deref().unwrap_or_default(),
+                    info.build
+                        .map(|b| format!(", build {}", b))
+                        .unwrap_or_default(),
+                    info.architecture
+                        .as_deref()
+                        .map(|a| format!(", {}", a))
+                        .unwrap_or_default(),
+                ),
+            ),
+            Ok(_) => (
+                false,
+                "ISO has no sources\\install.wim or install.esd to install".to_string(),
+            ),
+            Err(e) => (false, e.message().to_string()),
+        };
+        checks.push(ResourceCheck::new("iso_contents", passed, message).field("iso_path"));
+
+        // Hyper-V runs guests of the host's architecture; 32-bit Windows
+        // only boots in a Generation 1 VM on an x64 host
+        let iso_arch = iso.as_ref().ok().and_then(|i| i.architecture.as_deref());
+        let host_arch = normalize_architecture(&facts.architecture);
+        if let (Some(iso_arch), Some(host_arch)) = (iso_arch, host_arch) {
+            let generation = config.disk.format.vm_generation();
+            let supported = iso_arch == host_arch
+                || (iso_arch == "x86" && host_arch == "x64" && generation == 1);
+            checks.push(
+                ResourceCheck::new(
+                    "iso_architecture",
+                    supported,
+                    if supported {
+                        format!("{} ISO runs on this {} host", iso_arch, host_arch)
+                    } else {
+                        format!(
+                            "{} ISO cannot boot a Generation {} VM on this {} host",
+                            iso_arch, generation, host_arch
+                        )
+                    },
+                )
+                .field("iso_path"),
+            );
+        }
+
+        // The edition is an image index of the install image
+        if let (Some(index), Some(editions)) =
+            (config.edition, iso.as_ref().ok().map(|i| &i.editions))
+        {
+            if !editions.is_empty() {
+                let edition = editions.iter().find(|e| e.index == index);
+                checks.push(
+                    ResourceCheck::new(
+                        "edition",
+                        edition.is_some(),
+                        match edition {
+                            Some(e) => format!("Installs {} ({})", e.name, e.index),
+                            None => format!("ISO has no edition with index {}", index),
+                        },
+                    )
+                    .field("edition"),
+                );
+            }
+        }
     }
 
-    // Check paths
-    let vhd_dir = Path::new(vhd_path);
-    if !vhd_dir.exists() {
-        return Err(format!("VHD Path does not exist: {}", vhd_path));
+    // The VM's disk is a .vhdx, and a differencing child must match its parent
+    if let ProvisionSource::GoldenImage { .. } = source {
+        let is_vhdx = source_path.to_ascii_lowercase().ends_with(".vhdx");
+        checks.push(
+            ResourceCheck::new(
+                "source_vhd_format",
+                is_vhdx,
+                if is_vhdx {
+                    "Golden image is a VHDX"
+                } else {
+                    "Golden image must be a .vhdx file"
+                },
+            )
+            .field("source"),
+        );
     }
 
-    let iso = Path::new(iso_path);
-    if !iso.exists() {
-        return Err(format!("ISO Path does not exist: {}", iso_path));
+    let disk = config.disk.check(
+        disk_size_gb,
+        matches!(source, ProvisionSource::GoldenImage { .. }),
+        config.tpm_enabled,
+    );
+    checks.push(
+        ResourceCheck::new(
+            "disk_options",
+            disk.is_ok(),
+            disk.err().unwrap_or_else(|| config.disk.describe()),
+        )
+        .field("disk"),
+    );
+
+    // Fixed disks and golden image copies take their space up front;
+    // dynamic disks only need it as the guest fills them
+    if !interrupted && facts.vhd_dir_exists {
+        let fixed = config.disk.allocation == DiskAllocation::Fixed;
+        let system_bytes = match source {
+            ProvisionSource::Iso => u64::from(disk_size_gb) * GB,
+            ProvisionSource::GoldenImage {
+                differencing: true, ..
+            } => 0,
+            ProvisionSource::GoldenImage { .. } => facts.source_bytes.unwrap_or(0),
+        };
+        let data_bytes = u64::from(config.disk.data_disk_gb.unwrap_or(0)) * GB;
+        let (upfront, growable) = match (source, fixed) {
+            (ProvisionSource::Iso, true) => (system_bytes + data_bytes, 0),
+            (ProvisionSource::Iso, false) => (0, system_bytes + data_bytes),
+            (_, true) => (system_bytes + data_bytes, 0),
+            (_, false) => (system_bytes, data_bytes),
+        };
+        checks.push(
+            match facts.free_disk_bytes {
+                Some(free) if free < upfront => ResourceCheck::new(
+                    "free_space",
+                    false,
+                    format!(
+                        "{} GB free on the VHD volume, {} GB needed",
+                        free / GB,
+                        upfront.div_ceil(GB)
+                    ),
+                ),
+                Some(free) if free < upfront + growable => ResourceCheck::new(
+                    "free_space",
+                    false,
+                    format!(
+                        "{} GB free on the VHD volume; the disks can grow to {} GB",
+                        free / GB,
+                        (upfront + growable).div_ceil(GB)
+                    ),
+                )
+                .warning(),
+                Some(free) => ResourceCheck::new(
+                    "free_space",
+                    true,
+                    format!("{} GB free on the VHD volume", free / GB),
+                ),
+                None => ResourceCheck::new(
+                    "free_space",
+                    false,
+                    "Free space on the VHD volume could not be read",
+                )
+                .warning(),
+            }
+            .field("vhd_path"),
+        );
     }
 
-    Ok(())
+    let memory_bytes = u64::from(config.memory_gb) * GB;
+    checks.push(
+        if memory_bytes > facts.total_memory_bytes {
+            ResourceCheck::new(
+                "host_memory",
+                false,
+                format!(
+                    "The host only has {} GB of memory",
+                    facts.total_memory_bytes / GB
+                ),
+            )
+        } else if memory_bytes > facts.free_memory_bytes {
+            ResourceCheck::new(
+                "host_memory",
+                false,
+                format!(
+                    "Only {} GB of host memory is free; the VM may fail to start",
+                    facts.free_memory_bytes / GB
+                ),
+            )
+            .warning()
+        } else {
+            ResourceCheck::new(
+                "host_memory",
+                true,
+                format!("{} GB of host memory free", facts.free_memory_bytes / GB),
+            )
+        }
+        .field("memory_gb"),
+    );
+
+    let cpu_ok = config.cpu_cores >= 1 && config.cpu_cores <= facts.logical_processors;
+    checks.push(ResourceCheck::new(
+        "cpu_cores",
+        cpu_ok,
+        if config.cpu_cores == 0 {
+            "At least one CPU core is required".to_string()
+        } else if cpu_ok {
+            format!(
+                "{} of {} logical processors",
+                config.cpu_cores, facts.logical_processors
+            )
+        } else {
+            format!(
+                "The host only has {} logical processors",
+                facts.logical_processors
+            )
+        },
+    ));
+
+    let switch_exists = facts
+        .switches
+        .iter()
+        .any(|s| s.eq_ignore_ascii_case(&config.network_switch));
+    checks.push(ResourceCheck::new(
+        "network_switch",
+        switch_exists,
+        if switch_exists {
+            format!("Network switch '{}' exists", config.network_switch)
+        } else if config.network_switch.is_empty() {
+            "Select a network switch".to_string()
+        } else {
+            format!("Network switch '{}' does not exist", config.network_switch)
+        },
+    ));
+
+    // "None" is what the form sends with GPU partitioning turned off
+    let gpu_name = config.gpu_name.as_str();
+    if !gpu_name.is_empty() && !gpu_name.eq_ignore_ascii_case("None") {
+        checks.push(
+            ResourceCheck::new(
+                "gpu_partitioning",
+                facts.partitionable_gpus > 0,
+                if facts.partitionable_gpus > 0 {
+                    format!("{} partitionable GPU(s)", facts.partitionable_gpus)
+                } else {
+                    "No GPU on the host supports partitioning".to_string()
+                },
+            )
+            .field("gpu_name"),
+        );
+        if !gpu_name.eq_ignore_ascii_case("AUTO") {
+            let gpu_exists = facts.gpus.iter().any(|g| g.eq_ignore_ascii_case(gpu_name));
+            checks.push(ResourceCheck::new(
+                "gpu_name",
+                gpu_exists,
+                if gpu_exists {
+                    format!("GPU '{}' found", gpu_name)
+                } else {
+                    format!("GPU '{}' was not found on the host", gpu_name)
+                },
+            ));
+        }
+        let percent = config.gpu_allocation_percent;
+        checks.push(ResourceCheck::new(
+            "gpu_allocation_percent",
+            (1..=100).contains(&percent),
+            if (1..=100).contains(&percent) {
+                format!("GPU allocation of {}%", percent)
+            } else {
+                "GPU allocation must be between 1 and 100%".to_string()
+            },
+        ));
+    }
+
+    checks
 }
 
 #[tauri::command]
 pub async fn create_vm(
     window: Window,
-    state: State<'_, ProvisioningState>,
+    jobs: State<'_, JobRegistry>,
+    ps: State<'_, ExecutorState>,
+    locks: State<'_, VmLocks>,
     config: VMConfig,
-) -> Result<VMProgress, String> {
+    dry_run: Option<bool>,
+    resume: Option<bool>,
+) -> Result<RunOutcome<VMProgress>, HyperVError> {
+    // A second create for the same name would share its staging directory, and
+    // checks made before taking the lock could be stale once it is held
+    let _lock = match dry_run {
+        Some(true) => None,
+        _ => Some(
+            lock_vm(
+                &locks,
+                &ps,
+                &config.name,
+                "create_vm",
+                "Creating VM",
+                LockPolicy::Reject,
+            )
+            .await?,
+        ),
+    };
+
     // 1. Check resources
-    check_vm_resources(
-        &config.name,
-        config.memory_gb * 1024,
-        config.disk_size_gb,
-        &config.vhd_path,
-        &config.iso_path,
-    )?;
+    let resume = resume.unwrap_or(false);
+    if !resume && has_interrupted_run(window.app_handle(), &ps, &config.name) {
+        return Err(interrupted_run_error(&config.name));
+    }
+    let checks = host_resource_checks(&ps, &config, resume).await?;
+    if dry_run.unwrap_or(false) {
+        return Ok(RunOutcome::DryRun(plan_create_vm(
+            window.app_handle(),
+            &config,
+            checks,
+            ps.host().is_none(),
+        )?));
+    }
+    let report = check_vm_resources(&checks)?;
+
+    let job = jobs.start(ps.host(), &config.name)?;
+    job.persist_to(
+        RunLogStore::new(window.app_handle())
+            .start(
+                job.host.as_ref().map(|h| h.name.as_str()),
+                &config.name,
+                "create_vm",
+            )
+            .secret(Some(&config.password)),
+    );
+    let log = job_logger(&window, &job);
+    for warning in &report.warnings {
+        log(format!("[WARNING] {}", warning.message));
+    }
+    let result = match provision_vm(&window, &ps, &job, &config, resume).await {
+        Err(e) => Err(roll_back_job(&window, &ps, &job, e).await),
+        ok => ok,
+    };
+    job.finish(&result);
 
-    // 2. Prepare the provision script (copy deps + patch params)
-    let app_handle = window.app_handle();
-    let script_path = prepare_provision_script(&app_handle, &config)?;
+    // Verify success
+    if result.is_ok() {
+        remove_staging(&window, &ps, &job).await;
 
-    // 3. Execute script
-    let _ = window.emit(
-        "vm-log",
-        format!("Starting provisioning for VM: {}...", config.name),
-    );
+        let store = crate::commands::config::VMSettingsStore::new(window.app_handle());
+        let mut current_settings = store.get(&config.name);
 
-    // We execute the PATCHED script path directly.
-    // It is already a full path to a .ps1 file.
-    let exec_command = format!(r#"& "{}""#, script_path);
+        // Update hardware fields (Only persist GPU settings as requested)
+        current_settings.gpu_name = Some(config.gpu_name.clone());
+        current_settings.gpu_allocation_percent = Some(config.gpu_allocation_percent);
+
+        // Save
+        let _ = store.set(config.name.clone(), current_settings);
 
-    let mut child =
-        spawn_powershell(&exec_command).map_err(|e| format!("Failed to spawn process: {}", e))?;
-    let pid = child.id();
+        // Nothing left to resume
+        let phases = PhaseStateStore::new(window.app_handle());
+        let _ = phases.remove(job.host.as_ref().map(|h| h.name.as_str()), &config.name);
+    }
 
-    {
-        let mut lock = state.current_pid.lock().unwrap();
-        *lock = Some(pid);
+    result.map(RunOutcome::Completed)
+}
+
+/// Log sink for a job: buffers each line and forwards it to the frontend
+fn job_logger(window: &Window, job: &Arc<ProvisioningJob>) -> impl Fn(String) + Clone + Send {
+    let log = job_stream_logger(window, job);
+    move |line: String| log(LogStream::App, line)
+}
+
+/// `job_logger` for process output, keeping the stream each line came from
+fn job_stream_logger(
+    window: &Window,
+    job: &Arc<ProvisioningJob>,
+) -> impl Fn(LogStream, String) + Clone + Send {
+    let window = window.clone();
+    let job = job.clone();
+    move |stream: LogStream, line: String| {
+        job.log(stream, &line);
+        let _ = window.emit(
+            JOB_LOG_EVENT,
+            JobLogLine {
+                job_id: job.id,
+                vm: job.vm.clone(),
+                line: stream.display(&line),
+            },
+        );
     }
+}
 
-    let pid_clone = state.current_pid.clone();
+/// Undo what a failed or cancelled `job` created. A failed run keeps what
+/// its completed checkpoints created so it can be resumed; a cancelled one
+/// is abandoned, along with what the runs it resumed had created. The report
+/// is kept on the job and summarized in the hint of the returned `error`.
+async fn roll_back_job(
+    window: &Window,
+    ps: &ExecutorState,
+    job: &Arc<ProvisioningJob>,
+    error: HyperVError,
+) -> HyperVError {
+    let abandon = job.is_cancelled();
+    let store = PhaseStateStore::new(window.app_handle());
+    let host = job.host.as_ref().map(|h| h.name.clone());
+    let saved = store.load(host.as_deref(), &job.vm);
+
+    let mut resources = Vec::new();
+    if abandon {
+        resources.extend(saved.iter().flat_map(|s| s.resources.clone()));
+    }
+    for resource in job.rollback_resources(abandon) {
+        if !resources.contains(&resource) {
+            resources.push(resource);
+        }
+    }
 
-    // Stream output
-    let window_clone = window.clone();
-    let result = tokio::task::spawn_blocking(move || {
-        let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
-        let stderr = child.stderr.take().ok_or("Failed to open stderr")?;
-
-        // Spawn a thread to read stderr to prevent deadlocks and capture errors
-        let window_stderr = window_clone.clone();
-        std::thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(l) = line {
-                    let _ = window_stderr.emit("vm-log", format!("[ERROR] {}", l));
-                }
+    let mut error = error;
+    if !resources.is_empty() {
+        error = match undo_resources(window, ps, job, resources).await {
+            Ok(report) => {
+                let error = error.with_hint(report.summary());
+                job.set_rollback(report);
+                error
             }
-        });
+            Err(e) => error.with_hint(format!(
+                "Rollback did not complete ({}). Remove the VM, its disk and the staging directory manually.",
+                e
+            )),
+        };
+    }
 
-        let reader = BufReader::new(stdout);
-        let mut success = false;
-        let mut final_result: Result<(), String> = Err("Process exited unexpectedly".to_string());
+    let next = saved
+        .filter(|_| !abandon)
+        .and_then(|s| {
+            Checkpoint::ALL
+                .into_iter()
+                .find(|c| !s.completed.contains(c))
+        })
+        .filter(|next| *next != Checkpoint::Image);
+    match next {
+        Some(next) => error.with_hint(format!(
+            "Resume to continue from the '{}' checkpoint.",
+            next.id()
+        )),
+        None => {
+            let _ = store.remove(host.as_deref(), &job.vm);
+            error
+        }
+    }
+}
 
-        for line in reader.lines() {
-            match line {
-                Ok(l) => {
-                    let _ = window_clone.emit("vm-log", &l);
-                    if l.contains("PROVISION_SUCCESS") {
-                        success = true;
-                    }
-                    if l.contains("PROVISION_FAILED") {
-                        final_result = Err(format!("Provisioning failed: {}", l));
-                    }
-                }
-                Err(e) => {
-                    let _ = window_clone.emit("vm-log", format!("Error reading log: {}", e));
-                }
+/// Scrub and remove the staging directories of a finished job. A failed job
+/// removes them with the rest of its rollback.
+async fn remove_staging(window: &Window, ps: &ExecutorState, job: &Arc<ProvisioningJob>) {
+    let staging: Vec<ProvisionResource> = job
+        .rollback_resources(false)
+        .into_iter()
+        .filter(|r| {
+            matches!(
+                r,
+                ProvisionResource::Staging(_) | ProvisionResource::RemoteStaging(_)
+            )
+        })
+        .collect();
+    let log = job_logger(window, job);
+    let executor = ps.executor_on(AuditContext::new("create_vm").vm(&job.vm), job.host.clone());
+    let _ = tokio::task::spawn_blocking(move || {
+        for resource in staging {
+            if let Err(e) = undo(executor.as_ref(), &resource) {
+                log(format!(
+                    "[WARN] Could not remove {}: {}",
+                    resource.describe(),
+                    e
+                ));
             }
         }
+    })
+    .await;
+}
 
-        let status = child
-            .wait()
-            .map_err(|e| format!("Failed to wait on child: {}", e))?;
-
-        if success && status.success() {
-            Ok(VMProgress {
-                step: 1,
-                total_steps: 1,
-                message: "VM Provisioned Successfully!".to_string(),
-                completed: true,
-                error: None,
-            })
-        } else if let Err(e) = final_result {
-            Err(e)
-        } else {
-            Err(format!(
-                "Provisioning process exited with code: {:?}",
-                status.code()
-            ))
+/// Roll back `resources` on the job's host, logging each step to the job
+async fn undo_resources(
+    window: &Window,
+    ps: &ExecutorState,
+    job: &Arc<ProvisioningJob>,
+    resources: Vec<ProvisionResource>,
+) -> Result<RollbackReport, HyperVError> {
+    let log = job_logger(window, job);
+    log("Rolling back provisioning...".to_string());
+
+    let executor = ps.executor_on(
+        AuditContext::new("rollback_create_vm").vm(&job.vm),
+        job.host.clone(),
+    );
+    let settings = VMSettingsStore::new(window.app_handle());
+    tokio::task::spawn_blocking(move || rollback(executor.as_ref(), &settings, &resources, log))
+        .await
+        .map_err(|e| HyperVError::internal(format!("Task failed: {}", e)))
+}
+
+/// Phase state to provision `config` with. Resuming continues the saved run
+/// after its last verified checkpoint; a fresh run refuses to start over one,
+/// since its VM and disk would block it.
+async fn load_phase_state(
+    window: &Window,
+    ps: &ExecutorState,
+    job: &Arc<ProvisioningJob>,
+    store: &PhaseStateStore,
+    config: &VMConfig,
+    resume: bool,
+) -> Result<PhaseState, HyperVError> {
+    let host = job.host.as_ref().map(|h| h.name.clone());
+    let saved = store.load(host.as_deref(), &config.name);
+    let vhd_file = vhd_file_path(config);
+
+    if !resume {
+        if saved.is_some() {
+            return Err(interrupted_run_error(&config.name));
         }
+        return Ok(PhaseState::new(
+            &config.name,
+            host,
+            &vhd_file,
+            config.source_path(),
+            &config.gpu_name,
+        ));
+    }
+
+    let mut state = saved.ok_or_else(|| {
+        HyperVError::invalid_input(format!(
+            "No interrupted provisioning run to resume for VM '{}'",
+            config.name
+        ))
+    })?;
+    if !state.vhd_file.eq_ignore_ascii_case(&vhd_file)
+        || state.source_path != config.source_path()
+        || state.gpu_name != config.gpu_name
+    {
+        return Err(HyperVError::InvalidInput(ErrorDetails {
+            message: format!(
+                "The disk, image source or GPU of '{}' changed since the interrupted run",
+                config.name
+            ),
+            hint: Some("Resume with the original settings, or start over.".to_string()),
+            record: None,
+        }));
+    }
+
+    let executor = ps.executor_on(
+        AuditContext::new("create_vm").vm(&config.name),
+        job.host.clone(),
+    );
+    let saved = state.clone();
+    let verified = tokio::task::spawn_blocking(move || verify(executor.as_ref(), &saved))
+        .await
+        .map_err(HyperVError::task_failed)??;
+    if !verified.is_empty() {
+        job_logger(window, job)(format!(
+            "Resuming provisioning, skipping: {}",
+            Checkpoint::join(&verified)
+        ));
+    }
+    state.completed = verified;
+    Ok(state)
+}
+
+/// Roll back the interrupted provisioning run of VM `name` on the active
+/// host, removing the VM and disks it created, so a fresh run can start
+#[tauri::command]
+pub async fn discard_provisioning_run(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    locks: State<'_, VmLocks>,
+    name: String,
+) -> Result<RollbackReport, HyperVError> {
+    let _lock = lock_vm(
+        &locks,
+        &ps,
+        &name,
+        "discard_provisioning_run",
+        "Discarding interrupted run",
+        LockPolicy::Reject,
+    )
+    .await?;
+    let store = PhaseStateStore::new(window.app_handle());
+    let host = ps.host();
+    let host_name = host.as_ref().map(|h| h.name.clone());
+    let saved = store.load(host_name.as_deref(), &name).ok_or_else(|| {
+        HyperVError::invalid_input(format!(
+            "No interrupted provisioning run to discard for VM '{}'",
+            name
+        ))
+    })?;
+
+    let executor = ps.executor_on(
+        AuditContext::new("discard_provisioning_run").vm(&name),
+        host,
+    );
+    let settings = VMSettingsStore::new(window.app_handle());
+    let report = tokio::task::spawn_blocking(move || {
+        rollback(executor.as_ref(), &settings, &saved.resources, |_| {})
     })
     .await
-    .map_err(|e| format!("Task failed: {}", e))?;
+    .map_err(|e| HyperVError::internal(format!("Task failed: {}", e)))?;
+    // A partly discarded run stays saved, so discarding can be retried
+    if report.failed().next().is_some() {
+        return Err(HyperVError::internal(format!(
+            "Could not clean up the interrupted provisioning run of '{}'",
+            name
+        ))
+        .with_hint(report.summary()));
+    }
+    store.remove(host_name.as_deref(), &name)?;
+    Ok(report)
+}
 
-    {
-        let mut lock = pid_clone.lock().unwrap();
-        *lock = None;
+/// Stage and run the provisioning script for `config` as `job`, saving the
+/// phase state at every checkpoint
+async fn provision_vm(
+    window: &Window,
+    ps: &ExecutorState,
+    job: &Arc<ProvisioningJob>,
+    config: &VMConfig,
+    resume: bool,
+) -> Result<VMProgress, HyperVError> {
+    let log = job_logger(window, job);
+    let store = PhaseStateStore::new(window.app_handle());
+    let mut state = load_phase_state(window, ps, job, &store, config, resume).await?;
+
+    // 2. Prepare the provision script (copy deps + patch params)
+    job.track(ProvisionResource::Staging(provision_staging_dir(
+        &config.name,
+    )?));
+    let local = ps.local_executor(AuditContext::new("prepare_provision_script").vm(&config.name));
+    let app = window.app_handle().clone();
+    let staged = config.clone();
+    let completed = state.completed.clone();
+    // vmconnect can only open the console of a VM on this machine
+    let open_console = job.host.is_none();
+    let mut script_path = tokio::task::spawn_blocking(move || {
+        prepare_provision_script(&app, local.as_ref(), &staged, &completed, open_console)
+    })
+    .await
+    .map_err(HyperVError::task_failed)??;
+
+    // A remote host runs the script from its own copy of the staging directory
+    if let Some(host) = job.host.clone() {
+        log(format!(
+            "Copying provisioning files to {}...",
+            host.hostname
+        ));
+        let local = ps.local_executor(AuditContext::new("stage_on_host").vm(&config.name));
+        let staging = provision_staging_dir(&config.name)?;
+        let dir_name = remote_staging_name(&config.name)?;
+        let remote_dir = tokio::task::spawn_blocking(move || {
+            stage_on_host(local.as_ref(), &host, &staging, &dir_name)
+        })
+        .await
+        .map_err(HyperVError::task_failed)??;
+        job.track(ProvisionResource::RemoteStaging(remote_dir.clone()));
+        script_path = format!("{}\\CopyFilesToVM.ps1", remote_dir.trim_end_matches('\\'));
     }
 
-    // Verify success
-    if result.is_ok() {
-        let store = crate::commands::config::VMSettingsStore::new(&app_handle);
-        let mut current_settings = store.get(&config.name);
+    // 3. Execute script
+    log(format!("Starting provisioning for VM: {}...", config.name));
 
-        // Update hardware fields (Only persist GPU settings as requested)
-        current_settings.gpu_name = Some(config.gpu_name.clone());
-        current_settings.gpu_allocation_percent = Some(config.gpu_allocation_percent);
+    // We execute the PATCHED script path directly.
+    // It is already a full path to a .ps1 file.
+    let exec_command = provision_command(&script_path, &config.password);
 
-        // Save
-        let _ = store.set(config.name.clone(), current_settings);
+    let ps = ps.executor_on(
+        AuditContext::new("create_vm")
+            .vm(&config.name)
+            .secret(Some(&config.password)),
+        job.host.clone(),
+    );
+    let output_log = job_stream_logger(window, job);
+    let job = job.clone();
+    let window = window.clone();
+    let progress = move |event: ProgressEvent| {
+        let _ = window.emit(PROGRESS_EVENT, event);
+    };
+    let checkpoint_log = log.clone();
+    let checkpoint = move |checkpoint: Checkpoint, resources: Vec<ProvisionResource>| {
+        state.complete(checkpoint, resources);
+        if let Err(e) = store.save(&state) {
+            checkpoint_log(format!(
+                "[WARN] Could not save checkpoint '{}': {}",
+                checkpoint.id(),
+                e
+            ));
+        }
+    };
+    tokio::task::spawn_blocking(move || {
+        run_provisioning(
+            ps.as_ref(),
+            &exec_command,
+            &job,
+            output_log,
+            progress,
+            checkpoint,
+        )
+    })
+    .await
+    .map_err(HyperVError::task_failed)?
+}
+
+/// What `create_vm` would do for `config`, without touching Hyper-V
+fn plan_create_vm(
+    app: &tauri::AppHandle,
+    config: &VMConfig,
+    checks: Vec<ResourceCheck>,
+    open_console: bool,
+) -> Result<ExecutionPlan, HyperVError> {
+    let mut plan = ExecutionPlan::new("create_vm", &config.name);
+    plan.checks = checks;
+    // Nothing is staged for a name the checks reject
+    let Ok(staging) = provision_staging_dir(&config.name) else {
+        return Ok(plan);
+    };
+
+    let base_path = easy_gpu_pv_dir(app)?;
+    let rendered = render_provision_files(&base_path, config, &[], open_console)?;
+    let secrets = [config.password.clone()];
+    let script_path = staging.join("CopyFilesToVM.ps1");
+
+    plan.files = vec![
+        PlannedFile {
+            path: staging.to_string_lossy().to_string(),
+            description: format!("Copy of provisioning assets from {}", base_path.display()),
+            contents: None,
+        },
+        PlannedFile {
+            path: staging
+                .join("autounattend.xml")
+                .to_string_lossy()
+                .to_string(),
+            description: "Unattended setup answer file".to_string(),
+            contents: Some(redact(&rendered.autounattend, &secrets)),
+        },
+        PlannedFile {
+            path: script_path.to_string_lossy().to_string(),
+            description: "Patched provisioning script".to_string(),
+            contents: Some(redact(&rendered.script, &secrets)),
+        },
+    ];
+    plan.commands = vec![redact(
+        &provision_command(&script_path.to_string_lossy(), &config.password),
+        &secrets,
+    )];
+
+    plan.cmdlets = planned_cmdlets(&rendered.script, &base_path);
+    Ok(plan)
+}
+
+/// Invocation of the staged provisioning script. The password is passed as
+/// an argument so it is never written to the staging directory; executors
+/// hand scripts to PowerShell over stdin, keeping it off command lines too.
+fn provision_command(script_path: &str, password: &str) -> String {
+    ScriptBuilder::new()
+        .arg("ScriptPath", script_path)
+        .arg("Password", password)
+        .build("& $ScriptPath -Password $Password")
+}
+
+/// Hyper-V cmdlets run by an easy-gpu-pv script living in `dir`
+fn planned_cmdlets(script: &str, dir: &Path) -> Vec<String> {
+    // The scripts import the GPU driver helper module, whose cmdlets run too
+    let module =
+        fs::read_to_string(dir.join("Add-VMGpuPartitionAdapterFiles.psm1")).unwrap_or_default();
+    hyperv_cmdlets(&format!("{}\n{}", script, module))
+}
+
+/// Run the patched provisioning script, streaming its output through `log`,
+/// its progress markers through `on_progress` and each completed checkpoint,
+/// with the resources it created, through `on_checkpoint`.
+/// The spawned PID is kept on `job` while running so it can be cancelled.
+pub fn run_provisioning<F>(
+    ps: &dyn PowerShellExecutor,
+    command: &str,
+    job: &ProvisioningJob,
+    log: F,
+    mut on_progress: impl FnMut(ProgressEvent),
+    mut on_checkpoint: impl FnMut(Checkpoint, Vec<ProvisionResource>),
+) -> Result<VMProgress, HyperVError>
+where
+    F: Fn(LogStream, String) + Clone + Send + 'static,
+{
+    if job.is_cancelled() {
+        return Err(HyperVError::cancelled("Provisioning was cancelled"));
+    }
+    let mut child = ps
+        .spawn(command)
+        .map_err(|e| HyperVError::powershell(format!("Failed to start PowerShell: {}", e)))?;
+
+    job.set_pid(Some(child.id()));
+    // A cancel that came in while spawning found no PID to kill
+    if job.is_cancelled() {
+        let _ = ps.kill(child.id());
     }
 
-    result
+    let mut success = false;
+    let mut final_result: Result<(), String> = Err("Process exited unexpectedly".to_string());
+    let mut tracker = ProgressTracker::new(job.id, &job.vm, PROVISION_PHASES);
+    let status = stream_output(child.as_mut(), log, |l| {
+        if let Some(marker) = parse_marker(l) {
+            let event = tracker.update(&marker);
+            job.set_progress(event.clone());
+            on_progress(event);
+        }
+        if let Some(resource) = ProvisionResource::parse_marker(l) {
+            job.track(resource);
+        }
+        if let Some(checkpoint) = Checkpoint::parse_marker(l) {
+            let resources = job.complete_checkpoint(checkpoint);
+            on_checkpoint(checkpoint, resources);
+        }
+        if l.contains("PROVISION_SUCCESS") {
+            success = true;
+        }
+        if l.contains("PROVISION_FAILED") {
+            final_result = Err(format!("Provisioning failed: {}", l));
+        }
+    });
+
+    job.set_pid(None);
+
+    let code = status?;
+    if job.is_cancelled() {
+        Err(HyperVError::cancelled("Provisioning was cancelled"))
+    } else if success && code == Some(0) {
+        Ok(VMProgress {
+            step: PROVISION_PHASES.len() as u32,
+            total_steps: PROVISION_PHASES.len() as u32,
+            message: "VM Provisioned Successfully!".to_string(),
+            completed: true,
+            error: None,
+        })
+    } else if let Err(e) = final_result {
+        Err(HyperVError::powershell(e))
+    } else {
+        Err(HyperVError::powershell(format!(
+            "Provisioning process exited with code: {:?}",
+            code
+        )))
+    }
 }
 
-fn prepare_provision_script(app: &tauri::AppHandle, config: &VMConfig) -> Result<String, String> {
+/// Forward stdout/stderr of a spawned process to `log`, tagged with their
+/// stream, until it exits.
+/// `on_line` sees every stdout line, the `##` markers are kept out of `log`;
+/// the exit code is returned.
+fn stream_output<F>(
+    child: &mut dyn PowerShellProcess,
+    log: F,
+    mut on_line: impl FnMut(&str),
+) -> Result<Option<i32>, HyperVError>
+where
+    F: Fn(LogStream, String) + Clone + Send + 'static,
+{
+    let stdout = child.take_stdout().ok_or("Failed to open stdout")?;
+    let stderr = child.take_stderr().ok_or("Failed to open stderr")?;
+
+    // Spawn a thread to read stderr to prevent deadlocks and capture errors
+    let log_stderr = log.clone();
+    let stderr_thread = std::thread::spawn(move || {
+        let reader = BufReader::new(stderr);
+        for l in reader.lines().map_while(Result::ok) {
+            log_stderr(LogStream::Stderr, l);
+        }
+    });
+
+    let reader = BufReader::new(stdout);
+    for line in reader.lines() {
+        match line {
+            Ok(l) => {
+                on_line(&l);
+                let marker = l.trim_start();
+                if ![PROGRESS_MARKER, RESOURCE_MARKER, CHECKPOINT_MARKER]
+                    .iter()
+                    .any(|m| marker.starts_with(m))
+                {
+                    log(LogStream::Stdout, l);
+                }
+            }
+            Err(e) => log(LogStream::App, format!("Error reading log: {}", e)),
+        }
+    }
+
+    // stderr reaches EOF when the process exits; drain it before reporting the exit
+    let _ = stderr_thread.join();
+    let code = child
+        .wait()
+        .map_err(|e| HyperVError::powershell(format!("Failed to wait on PowerShell: {}", e)))?;
+    Ok(code)
+}
+
+/// Locate the bundled `easy-gpu-pv` directory
+fn easy_gpu_pv_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
     // 1. Identify source path for dependencies (Easy-GPU-PV folder)
     let mut possible_paths = vec![
         "src-tauri/src/commands/easy-gpu-pv".into(),
@@ -294,79 +1291,186 @@ fn prepare_provision_script(app: &tauri::AppHandle, config: &VMConfig) -> Result
         possible_paths.insert(0, resource_path);
     }
 
-    let base_path = possible_paths
+    possible_paths
         .iter()
         .map(|p| Path::new(p))
         .find(|p| p.exists())
+        .map(Path::to_path_buf)
         .ok_or_else(|| {
             format!(
                 "Could not find 'easy-gpu-pv' dependency directory. Checked: {:?}",
                 possible_paths
             )
-        })?;
-
-    println!("DEBUG: Found easy-gpu-pv at: {:?}", base_path);
-    let template_check = base_path.join("CopyFilesToVM.template.ps1");
-    println!(
-        "DEBUG: Template exists at source? {}",
-        template_check.exists()
-    );
+        })
+}
 
-    // 2. Create unique staging directory
-    let temp_dir = env::temp_dir()
-        .join("HyperV_GPU_Provisioning")
-        .join(&config.name);
-    if temp_dir.exists() {
-        fs::remove_dir_all(&temp_dir).map_err(|e| format!("Failed to clean temp dir: {}", e))?;
-    }
-    fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp dir: {}", e))?;
-
-    // 3. Copy ALL dependencies recursively
-    copy_dir_recursive(base_path, &temp_dir)
-        .map_err(|e| format!("Failed to copy dependencies: {}", e))?;
-
-    // 4. Patch autounattend.xml from Template
-    let xml_template_path = temp_dir.join("autounattend.template.xml");
-    if xml_template_path.exists() {
-        let mut content = fs::read_to_string(&xml_template_path)
-            .map_err(|e| format!("Failed to read xml template: {}", e))?;
-        content = content.replace("__USERNAME__", &config.username);
-        content = content.replace("__PASSWORD__", &config.password);
-
-        let xml_path = temp_dir.join("autounattend.xml");
-        fs::write(&xml_path, content).map_err(|e| format!("Failed to write xml: {}", e))?;
+/// Why `name` cannot be given to a new VM, if it cannot. The name becomes a
+/// directory under the staging roots, which are scrubbed and removed.
+fn vm_name_problem(name: &str) -> Option<&'static str> {
+    if name.is_empty() {
+        Some("VM Name cannot be empty")
+    } else if name.contains("..")
+        || name
+            .chars()
+            .any(|c| c.is_control() || r#"<>:"/\|?*"#.contains(c))
+    {
+        Some("VM name cannot contain '..', control characters or any of < > : \" / \\ | ? *")
+    } else if name.ends_with(['.', ' ']) {
+        Some("VM name cannot end with a dot or a space")
     } else {
-        return Err("autounattend.template.xml not found in dependency directory".to_string());
+        None
+    }
+}
+
+/// `name` if it can name a staging directory
+fn staging_name(name: &str) -> Result<&str, HyperVError> {
+    match vm_name_problem(name) {
+        Some(problem) => Err(HyperVError::invalid_input(problem)),
+        None => Ok(name),
     }
+}
+
+/// Unique staging directory a VM is provisioned from
+fn provision_staging_dir(name: &str) -> Result<PathBuf, HyperVError> {
+    Ok(staging_root().join(staging_name(name)?))
+}
+
+/// Virtual disk the provisioning script creates, as `ConcatenateVHDPath`
+/// in CopyFilesToVM.ps1 builds it
+fn vhd_file_path(config: &VMConfig) -> String {
+    format!(
+        "{}\\{}.{}",
+        config.vhd_path.trim_end_matches('\\'),
+        config.name,
+        config.disk.format.extension()
+    )
+}
+
+/// Data disk the provisioning script creates next to the VM's disk, if any
+fn data_disk_file_path(config: &VMConfig) -> Option<String> {
+    config.disk.data_disk_gb.map(|_| {
+        format!(
+            "{}\\{}-data.{}",
+            config.vhd_path.trim_end_matches('\\'),
+            config.name,
+            config.disk.format.extension()
+        )
+    })
+}
+
+/// Staging directory name under a remote host's temp directory
+fn remote_staging_name(name: &str) -> Result<String, HyperVError> {
+    Ok(format!("HyperV_GPU_Provisioning\\{}", staging_name(name)?))
+}
+
+/// Directory name under a remote host's temp directory for a VM's update
+/// script; the VM lock keeps updates of one VM from sharing it
+fn remote_update_staging_name(name: &str) -> Result<String, HyperVError> {
+    Ok(format!("HyperV_GPU_Update\\{}", staging_name(name)?))
+}
 
-    // 5. Patch CopyFilesToVM.ps1 from Template
-    // We look for the template file in the temp dir (since we copied everything)
-    let template_path = temp_dir.join("CopyFilesToVM.template.ps1");
+/// Provisioning files rendered from the `easy-gpu-pv` templates
+struct ProvisionFiles {
+    autounattend: String,
+    script: String,
+}
 
+/// `resume` lists the checkpoints the script skips
+fn render_provision_files(
+    base_path: &Path,
+    config: &VMConfig,
+    resume: &[Checkpoint],
+    open_console: bool,
+) -> Result<ProvisionFiles, HyperVError> {
+    let autounattend = UnattendBuilder::for_vm(
+        &config.name,
+        &config.username,
+        UNATTEND_PASSWORD_TOKEN,
+        config.auto_logon,
+        &config.unattend,
+    )
+    .build()
+    .map_err(HyperVError::invalid_input)?;
+
+    // Patch CopyFilesToVM.ps1 from Template
+    let template_path = base_path.join(PROVISION_SCRIPT.file_name);
     let script_content_template = if template_path.exists() {
         fs::read_to_string(&template_path)
-            .map_err(|e| format!("Failed to read template script: {}", e))?
+            .map_err(|e| HyperVError::io("Failed to read template script", &e))?
     } else {
-        return Err("CopyFilesToVM.template.ps1 not found in dependency directory".to_string());
+        return Err(HyperVError::internal(
+            "CopyFilesToVM.template.ps1 not found in dependency directory",
+        ));
     };
 
     // Perform Replacements
-    let mut script_content = script_content_template
-        .replace("__VM_NAME__", &config.name)
-        .replace("__ISO_PATH__", &config.iso_path)
-        .replace("__VHD_PATH__", &config.vhd_path) // Directory path
-        .replace("__DISK_SIZE_GB__", &config.disk_size_gb.to_string())
-        .replace("__MEMORY_GB__", &config.memory_gb.to_string())
-        .replace("__CPU_COUNT__", &config.cpu_cores.to_string())
-        .replace("__GPU_NAME__", &config.gpu_name)
-        .replace("__SWITCH_NAME__", &config.network_switch)
-        .replace("__USERNAME__", &config.username)
-        .replace("__PASSWORD__", &config.password)
-        .replace("__AUTO_LOGON__", &config.auto_logon.to_string())
-        .replace(
-            "__GPU_ALLOCATION_PERCENT__",
-            &config.gpu_allocation_percent.to_string(),
-        );
+    let mut script_content = PROVISION_SCRIPT.render(
+        &script_content_template,
+        &[
+            ("VM_NAME", config.name.clone()),
+            ("ISO_PATH", config.iso_path.clone()),
+            // Only read by ISO installs, which the checks refuse without one
+            ("EDITION", config.edition.unwrap_or_default().to_string()),
+            (
+                "SOURCE_VHD",
+                match &config.source {
+                    ProvisionSource::Iso => String::new(),
+                    ProvisionSource::GoldenImage { path, .. } => path.clone(),
+                },
+            ),
+            (
+                "DIFFERENCING",
+                matches!(
+                    config.source,
+                    ProvisionSource::GoldenImage {
+                        differencing: true,
+                        ..
+                    }
+                )
+                .to_string(),
+            ),
+            ("VHD_PATH", config.vhd_path.clone()), // Directory path
+            ("VHD_FORMAT", config.disk.format.ps_name().to_string()),
+            ("DISK_LAYOUT", config.disk.format.disk_layout().to_string()),
+            (
+                "VM_GENERATION",
+                config.disk.format.vm_generation().to_string(),
+            ),
+            (
+                "FIXED_DISK",
+                (config.disk.allocation == DiskAllocation::Fixed).to_string(),
+            ),
+            (
+                "BLOCK_SIZE_KB",
+                config.disk.block_size_kb.unwrap_or(0).to_string(),
+            ),
+            (
+                "LOGICAL_SECTOR_BYTES",
+                config.disk.logical_sector_bytes.unwrap_or(0).to_string(),
+            ),
+            (
+                "PHYSICAL_SECTOR_BYTES",
+                config.disk.physical_sector_bytes.unwrap_or(0).to_string(),
+            ),
+            (
+                "DATA_DISK_GB",
+                config.disk.data_disk_gb.unwrap_or(0).to_string(),
+            ),
+            ("DISK_SIZE_GB", config.disk_size_gb.to_string()),
+            ("MEMORY_GB", config.memory_gb.to_string()),
+            ("CPU_COUNT", config.cpu_cores.to_string()),
+            ("GPU_NAME", config.gpu_name.clone()),
+            ("SWITCH_NAME", config.network_switch.clone()),
+            ("USERNAME", config.username.clone()),
+            ("AUTO_LOGON", config.auto_logon.to_string()),
+            (
+                "GPU_ALLOCATION_PERCENT",
+                config.gpu_allocation_percent.to_string(),
+            ),
+            ("RESUME_CHECKPOINTS", Checkpoint::join(resume)),
+            ("OPEN_CONSOLE", open_console.to_string()),
+        ],
+    )?;
 
     // Patch known issues for headless execution (still needed as they are code logic, not params)
     script_content = script_content.replace("Read-host", "# Read-host");
@@ -375,14 +1479,52 @@ fn prepare_provision_script(app: &tauri::AppHandle, config: &VMConfig) -> Result
         "$params.VMName.Length -gt 15",
         "$params.VMName.Length -gt 100",
     );
+    // autounattend.xml is complete already; the script would overwrite the
+    // computer name with the raw VM name
+    script_content = script_content.replace(
+        "    Modify-AutoUnattend -username",
+        "    # Modify-AutoUnattend -username",
+    );
 
     // Add success marker at the end
     script_content.push_str("\nWrite-Host 'PROVISION_SUCCESS'");
 
+    Ok(ProvisionFiles {
+        autounattend,
+        script: script_content,
+    })
+}
+
+/// `ps` runs locally; it restricts access to the staging directory before
+/// anything is written to it
+fn prepare_provision_script(
+    app: &tauri::AppHandle,
+    ps: &dyn PowerShellExecutor,
+    config: &VMConfig,
+    resume: &[Checkpoint],
+    open_console: bool,
+) -> Result<String, HyperVError> {
+    let base_path = easy_gpu_pv_dir(app)?;
+    let rendered = render_provision_files(&base_path, config, resume, open_console)?;
+
+    // Create unique staging directory; one left by a crashed run is scrubbed
+    let temp_dir = provision_staging_dir(&config.name)?;
+    scrub_staging_dir(&temp_dir).map_err(|e| HyperVError::io("Failed to clean temp dir", &e))?;
+    fs::create_dir_all(&temp_dir).map_err(|e| HyperVError::io("Failed to create temp dir", &e))?;
+    restrict_dir_acl(ps, &temp_dir.to_string_lossy())?;
+
+    // Copy ALL dependencies recursively
+    copy_dir_recursive(&base_path, &temp_dir)
+        .map_err(|e| HyperVError::io("Failed to copy dependencies", &e))?;
+
+    let xml_path = temp_dir.join("autounattend.xml");
+    fs::write(&xml_path, rendered.autounattend)
+        .map_err(|e| HyperVError::io("Failed to write xml", &e))?;
+
     // Write to the actual script name expected by everything
     let script_path = temp_dir.join("CopyFilesToVM.ps1");
-    fs::write(&script_path, script_content)
-        .map_err(|e| format!("Failed to write patched script: {}", e))?;
+    fs::write(&script_path, rendered.script)
+        .map_err(|e| HyperVError::io("Failed to write patched script", &e))?;
 
     Ok(script_path.to_string_lossy().to_string())
 }
@@ -405,102 +1547,153 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Cancel the provisioning job for VM `name` on the active host, or job `job_id`
 #[tauri::command]
 pub async fn cancel_create_vm(
-    state: State<'_, ProvisioningState>,
+    jobs: State<'_, JobRegistry>,
+    ps: State<'_, ExecutorState>,
     name: String,
-) -> Result<(), String> {
-    let pid_opt = state.current_pid.lock().unwrap().take();
-    if let Some(pid) = pid_opt {
-        let kill_cmd = format!("taskkill /PID {} /T /F", pid);
-        let _ = run_powershell(&kill_cmd);
+    job_id: Option<u64>,
+) -> Result<(), HyperVError> {
+    let job = match job_id {
+        Some(id) => jobs.get(id).filter(|job| job.is_running()),
+        None => jobs.running(ps.host().map(|h| h.name).as_deref(), &name),
     }
-
-    // Helper functionality for cleanup could be expanded here.
-    // Currently relying on Remove-VM which might fail if VM wasn't created yet or halfway.
-    let cleanup_script = format!(
-        r#"
-        $vmName = '{}'
-        Remove-VM -Name $vmName -Force -ErrorAction SilentlyContinue
-        "#,
-        name
+    .ok_or_else(|| {
+        HyperVError::invalid_input(format!("No provisioning job is running for VM '{}'", name))
+    })?;
+
+    job.cancel();
+    let ps = ps.executor_on(
+        AuditContext::new("cancel_create_vm").vm(&job.vm),
+        job.host.clone(),
     );
-    let _ = run_powershell(&cleanup_script);
+    if let Some(pid) = job.pid() {
+        let _ = ps.kill(pid);
+    }
+    // create_vm rolls back whatever the job created once the script exits
     Ok(())
 }
 
 /// Get list of all VMs
 #[tauri::command]
-pub async fn list_vms() -> Result<Vec<VMInfo>, String> {
-    let parts_approach = run_powershell(
+pub async fn list_vms(ps: State<'_, ExecutorState>) -> Result<Vec<VMInfo>, HyperVError> {
+    ps.run_blocking(AuditContext::new("list_vms"), None, list_vms_sync)
+        .await
+}
+
+pub fn list_vms_sync(ps: &dyn PowerShellExecutor) -> Result<Vec<VMInfo>, HyperVError> {
+    let output = ps.run(
         r#"
-        Get-VM | ForEach-Object {
+        $vms = @(Get-VM | ForEach-Object {
             $gpu = Get-VMGpuPartitionAdapter -VMName $_.Name -ErrorAction SilentlyContinue
-            $hasGpu = if ($gpu) { "true" } else { "false" }
-            $switch = (Get-VMNetworkAdapter -VM $_).SwitchName
+            $switch = (Get-VMNetworkAdapter -VM $_).SwitchName | Select-Object -First 1
             if (-not $switch) { $switch = "None" }
             $mem = Get-VMMemory -VMName $_.Name
-            
+
             # Get IP Address (IPv4, first one found)
             $ip = ($_.NetworkAdapters | Select-Object -ExpandProperty IPAddresses | Where-Object { $_ -match '^(?:[0-9]{1,3}\.){3}[0-9]{1,3}$' } | Select-Object -First 1)
-            if (-not $ip) { $ip = "" }
-            
-            "$($_.Name)|$($_.State)|$($_.CpuUsage)|$($mem.Startup)|$($_.Uptime)|$hasGpu|$($_.ProcessorCount)|$switch|$ip"
-        }
-        "#,
-    )?;
 
-    let vms: Vec<VMInfo> = parts_approach
-        .lines()
-        .filter_map(|line| {
-            let parts: Vec<&str> = line.split('|').collect();
-            if parts.len() >= 8 {
-                let ip_val = if parts.len() >= 9 {
-                    let s = parts[8].trim();
-                    if s.is_empty() {
-                        None
-                    } else {
-                        Some(s.to_string())
-                    }
-                } else {
-                    None
-                };
-
-                Some(VMInfo {
-                    name: parts[0].trim().to_string(),
-                    state: parts[1].trim().to_string(),
-                    cpu_usage: parts[2].parse::<u32>().unwrap_or(0),
-                    memory_assigned_mb: parts[3].parse::<u64>().unwrap_or(0) / 1024 / 1024,
-                    uptime: parts[4].trim().to_string(),
-                    has_gpu: parts[5].trim() == "true",
-                    cpu_cores: parts[6].parse::<u32>().unwrap_or(0),
-                    network_switch: parts[7].trim().to_string(),
-                    ip_address: ip_val,
-                })
-            } else {
-                None
+            [PSCustomObject]@{
+                name = "$($_.Name)"
+                state = "$($_.State)"
+                cpu_usage = [int]$_.CpuUsage
+                memory_assigned_mb = [int64][math]::Floor($mem.Startup / 1MB)
+                uptime = "$($_.Uptime)"
+                has_gpu = [bool]$gpu
+                cpu_cores = [int]$_.ProcessorCount
+                network_switch = "$switch"
+                ip_address = if ($ip) { "$ip" } else { $null }
             }
         })
-        .collect();
+        ConvertTo-Json -InputObject $vms -Compress
+        "#,
+    )?;
+
+    parse_json_list(&output)
+        .map_err(|e| HyperVError::internal(format!("Failed to read VM list: {}", e)))
+}
 
-    Ok(vms)
+/// Claim `name` on the active host for a state-changing operation
+async fn lock_vm<'a>(
+    locks: &'a VmLocks,
+    ps: &ExecutorState,
+    name: &str,
+    operation: &str,
+    description: &str,
+    policy: LockPolicy,
+) -> Result<VmLock<'a>, HyperVError> {
+    let host = ps.host().map(|h| h.name);
+    locks
+        .acquire(host.as_deref(), name, operation, description, policy)
+        .await
 }
 
 #[tauri::command]
-pub async fn start_vm(name: String) -> Result<(), String> {
-    run_powershell(&format!("Start-VM -Name '{}'", name))?;
+pub async fn start_vm(
+    ps: State<'_, ExecutorState>,
+    locks: State<'_, VmLocks>,
+    name: String,
+) -> Result<(), HyperVError> {
+    let _lock = lock_vm(&locks, &ps, &name, "start_vm", "Starting", LockPolicy::Wait).await?;
+    let script = ScriptBuilder::new()
+        .with_get_vm_exact()
+        .arg("VMName", &name)
+        .build("Start-VM -VM (Get-VMExact $VMName)");
+    ps.run_blocking(
+        AuditContext::new("start_vm").vm(&name),
+        Some(LIFECYCLE_TIMEOUT),
+        move |ps| ps.run(&script),
+    )
+    .await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn stop_vm(name: String) -> Result<(), String> {
-    run_powershell(&format!("Stop-VM -Name '{}' -Force", name))?;
+pub async fn stop_vm(
+    ps: State<'_, ExecutorState>,
+    locks: State<'_, VmLocks>,
+    name: String,
+) -> Result<(), HyperVError> {
+    let _lock = lock_vm(&locks, &ps, &name, "stop_vm", "Stopping", LockPolicy::Wait).await?;
+    let script = ScriptBuilder::new()
+        .with_get_vm_exact()
+        .arg("VMName", &name)
+        .build("Stop-VM -VM (Get-VMExact $VMName) -Force");
+    ps.run_blocking(
+        AuditContext::new("stop_vm").vm(&name),
+        Some(LIFECYCLE_TIMEOUT),
+        move |ps| ps.run(&script),
+    )
+    .await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn delete_vm(name: String) -> Result<(), String> {
-    run_powershell(&format!("Remove-VM -Name '{}' -Force", name))?;
+pub async fn delete_vm(
+    ps: State<'_, ExecutorState>,
+    locks: State<'_, VmLocks>,
+    name: String,
+) -> Result<(), HyperVError> {
+    let _lock = lock_vm(
+        &locks,
+        &ps,
+        &name,
+        "delete_vm",
+        "Deleting",
+        LockPolicy::Wait,
+    )
+    .await?;
+    let script = ScriptBuilder::new()
+        .with_get_vm_exact()
+        .arg("VMName", &name)
+        .build("Remove-VM -VM (Get-VMExact $VMName) -Force");
+    ps.run_blocking(
+        AuditContext::new("delete_vm").vm(&name),
+        Some(LIFECYCLE_TIMEOUT),
+        move |ps| ps.run(&script),
+    )
+    .await?;
     Ok(())
 }
 
@@ -515,101 +1708,110 @@ pub struct VMUpdateConfig {
 }
 
 #[tauri::command]
-pub async fn update_vm_config(window: Window, config: VMUpdateConfig) -> Result<String, String> {
+pub async fn update_vm_config(
+    window: Window,
+    ps: State<'_, ExecutorState>,
+    locks: State<'_, VmLocks>,
+    config: VMUpdateConfig,
+    dry_run: Option<bool>,
+) -> Result<RunOutcome<String>, HyperVError> {
     // 1. Locate the script
-    let mut possible_paths = vec![
-        "src-tauri/src/commands/easy-gpu-pv/Update-VMConfig.ps1".into(),
-        "src/commands/easy-gpu-pv/Update-VMConfig.ps1".into(),
-        "easy-gpu-pv/Update-VMConfig.ps1".into(),
-        "../src-tauri/src/commands/easy-gpu-pv/Update-VMConfig.ps1".into(),
-    ];
+    let mut script_path = update_config_script(window.app_handle())?;
+
+    // The update stops the VM and mounts its disk; nothing else may touch it meanwhile
+    let _lock = match dry_run {
+        Some(true) => None,
+        _ => Some(
+            lock_vm(
+                &locks,
+                &ps,
+                &config.name,
+                "update_vm_config",
+                "Updating GPU",
+                LockPolicy::Reject,
+            )
+            .await?,
+        ),
+    };
 
-    // Check resource path (Production)
-    if let Ok(resource_path) = window.app_handle().path().resolve(
-        "src/commands/easy-gpu-pv/Update-VMConfig.ps1",
-        tauri::path::BaseDirectory::Resource,
-    ) {
-        possible_paths.insert(0, resource_path);
+    // A remote host needs its own copy of the script and the driver module it imports
+    let mut remote_staging = None;
+    if let (Some(host), false) = (ps.host(), dry_run.unwrap_or(false)) {
+        let local = ps.local_executor(AuditContext::new("stage_on_host").vm(&config.name));
+        let script_dir = script_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let dir_name = remote_update_staging_name(&config.name)?;
+        let remote_dir = tokio::task::spawn_blocking(move || {
+            stage_on_host(local.as_ref(), &host, &script_dir, &dir_name)
+        })
+        .await
+        .map_err(HyperVError::task_failed)??;
+        script_path = PathBuf::from(format!(
+            "{}\\Update-VMConfig.ps1",
+            remote_dir.trim_end_matches('\\')
+        ));
+        remote_staging = Some(ProvisionResource::RemoteStaging(remote_dir));
     }
 
-    let script_path = possible_paths
-        .iter()
-        .map(|p| Path::new(p))
-        .find(|p| p.exists())
-        .ok_or_else(|| "Could not find Update-VMConfig.ps1 script".to_string())?;
-
-    let abs_path = fs::canonicalize(script_path)
-        .map_err(|e| format!("Failed to resolve script path: {}", e))?;
-
     // 2. Build Command
-    let command = format!(
-        r#"& "{}" -VMName "{}" -GPUName "{}" -GPUResourceAllocationPercentage {} -ProcessorCount {} -MemoryMB {} -NetworkSwitch "{}""#,
-        abs_path.to_string_lossy(),
-        config.name,
-        config.gpu_name,
-        config.gpu_allocation_percent,
-        config.cpu_count,
-        config.memory_mb,
-        config.network_switch
-    );
+    let command = update_config_command(&script_path, &config);
+
+    if dry_run.unwrap_or(false) {
+        let mut plan = ExecutionPlan::new("update_vm_config", &config.name);
+        plan.checks = vec![ResourceCheck::new(
+            "gpu_allocation_percent",
+            (1..=100).contains(&config.gpu_allocation_percent),
+            format!("GPU allocation of {}%", config.gpu_allocation_percent),
+        )];
+        plan.cmdlets = planned_cmdlets(
+            &fs::read_to_string(&script_path).unwrap_or_default(),
+            script_path.parent().unwrap_or(Path::new(".")),
+        );
+        plan.commands = vec![command];
+        return Ok(RunOutcome::DryRun(plan));
+    }
 
-    let _ = window.emit(
-        "vm-log",
+    // Every line goes to the frontend and to the VM's persisted run log
+    let host = ps.host().map(|h| h.name);
+    let run_log = Arc::new(RunLogStore::new(window.app_handle()).start(
+        host.as_deref(),
+        &config.name,
+        "update_vm_config",
+    ));
+    let log = {
+        let window = window.clone();
+        let run_log = run_log.clone();
+        move |stream: LogStream, line: String| {
+            run_log.write(stream, &line);
+            let _ = window.emit("vm-log", stream.display(&line));
+        }
+    };
+    log(
+        LogStream::App,
         format!("Starting Configuration Update for VM: {}...", config.name),
     );
 
-    // 3. Execute with Streaming (spawn_powershell)
-    let mut child =
-        spawn_powershell(&command).map_err(|e| format!("Failed to spawn process: {}", e))?;
-
-    let window_task = window.clone();
+    // 3. Execute with Streaming
+    let ps = ps.executor(AuditContext::new("update_vm_config").vm(&config.name));
     let result = tokio::task::spawn_blocking(move || {
-        let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
-        let stderr = child.stderr.take().ok_or("Failed to open stderr")?;
-
-        // Spawn a thread to read stderr
-        let window_stderr = window_task.clone();
-        std::thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(l) = line {
-                    let _ = window_stderr.emit("vm-log", format!("[ERROR] {}", l));
-                }
-            }
-        });
-
-        let reader = BufReader::new(stdout);
-        let mut success = false;
-
-        for line in reader.lines() {
-            match line {
-                Ok(l) => {
-                    let _ = window_task.emit("vm-log", &l);
-                    if l.contains("UPDATE_SUCCESS") {
-                        success = true;
-                    }
-                }
-                Err(e) => {
-                    let _ = window_task.emit("vm-log", format!("Error reading log: {}", e));
-                }
+        let result = run_config_update(ps.as_ref(), &command, log.clone());
+        // The staged copy is only needed for this run
+        if let Some(staging) = remote_staging {
+            if let Err(e) = undo(ps.as_ref(), &staging) {
+                log(
+                    LogStream::App,
+                    format!("[WARN] Could not remove {}: {}", staging.describe(), e),
+                );
             }
         }
-
-        let status = child
-            .wait()
-            .map_err(|e| format!("Failed to wait on child: {}", e))?;
-
-        if success && status.success() {
-            Ok("VM Configuration Updated Successfully".to_string())
-        } else {
-            Err(format!(
-                "Update process failed (Exit Code: {:?}). Check logs for details.",
-                status.code()
-            ))
-        }
+        result
     })
     .await
-    .map_err(|e| format!("Task failed: {}", e))??;
+    .map_err(HyperVError::task_failed)?;
+    run_log.finish_with(&result);
+    let result = result?;
 
     // Save updated config to store
     let store = crate::commands::config::VMSettingsStore::new(&window.app_handle());
@@ -627,17 +1829,92 @@ pub async fn update_vm_config(window: Window, config: VMUpdateConfig) -> Result<
 
     let _ = store.set(config.name.clone(), current_settings);
 
-    Ok(result)
+    Ok(RunOutcome::Completed(result))
+}
+
+/// Locate Update-VMConfig.ps1 and resolve it to an absolute path
+fn update_config_script(app: &tauri::AppHandle) -> Result<PathBuf, HyperVError> {
+    let mut possible_paths = vec![
+        "src-tauri/src/commands/easy-gpu-pv/Update-VMConfig.ps1".into(),
+        "src/commands/easy-gpu-pv/Update-VMConfig.ps1".into(),
+        "easy-gpu-pv/Update-VMConfig.ps1".into(),
+        "../src-tauri/src/commands/easy-gpu-pv/Update-VMConfig.ps1".into(),
+    ];
+
+    // Check resource path (Production)
+    if let Ok(resource_path) = app.path().resolve(
+        "src/commands/easy-gpu-pv/Update-VMConfig.ps1",
+        tauri::path::BaseDirectory::Resource,
+    ) {
+        possible_paths.insert(0, resource_path);
+    }
+
+    let script_path = possible_paths
+        .iter()
+        .map(|p| Path::new(p))
+        .find(|p| p.exists())
+        .ok_or_else(|| HyperVError::internal("Could not find Update-VMConfig.ps1 script"))?;
+
+    fs::canonicalize(script_path).map_err(|e| HyperVError::io("Failed to resolve script path", &e))
+}
+
+/// Invocation of Update-VMConfig.ps1 for `config`
+fn update_config_command(script_path: &Path, config: &VMUpdateConfig) -> String {
+    ScriptBuilder::new()
+        .arg("ScriptPath", &script_path.to_string_lossy())
+        .arg("VMName", &config.name)
+        .arg("GPUName", &config.gpu_name)
+        .int("GPUPercent", config.gpu_allocation_percent.into())
+        .int("ProcessorCount", config.cpu_count.into())
+        .int("MemoryMB", config.memory_mb as i64)
+        .arg("NetworkSwitch", &config.network_switch)
+        .build(
+            "& $ScriptPath -VMName $VMName -GPUName $GPUName -GPUResourceAllocationPercentage $GPUPercent -ProcessorCount $ProcessorCount -MemoryMB $MemoryMB -NetworkSwitch $NetworkSwitch",
+        )
+}
+
+/// Run Update-VMConfig.ps1, streaming its output through `log`
+pub fn run_config_update<F>(
+    ps: &dyn PowerShellExecutor,
+    command: &str,
+    log: F,
+) -> Result<String, HyperVError>
+where
+    F: Fn(LogStream, String) + Clone + Send + 'static,
+{
+    let mut child = ps
+        .spawn(command)
+        .map_err(|e| HyperVError::powershell(format!("Failed to start PowerShell: {}", e)))?;
+
+    let mut success = false;
+    let code = stream_output(child.as_mut(), log, |l| {
+        if l.contains("UPDATE_SUCCESS") {
+            success = true;
+        }
+    })?;
+
+    if success && code == Some(0) {
+        Ok("VM Configuration Updated Successfully".to_string())
+    } else {
+        Err(HyperVError::powershell(format!(
+            "Update process failed (Exit Code: {:?}). Check logs for details.",
+            code
+        )))
+    }
 }
 
 #[tauri::command]
-pub async fn connect_vm_rdp(name: String) -> Result<(), String> {
+pub async fn connect_vm_rdp(ps: State<'_, ExecutorState>, name: String) -> Result<(), HyperVError> {
     // We switch back to using 'vmconnect.exe' (Hyper-V Manager Console).
     // This connects via VMBus, so it works without network/IP/Firewall configuration.
     // Ideally, this provides the most reliable "just works" experience for local VMs.
+    // For a remote host, vmconnect reaches the VM through that host instead.
+    let server = ps
+        .host()
+        .map_or_else(|| "localhost".to_string(), |h| h.hostname);
 
     std::process::Command::new("vmconnect")
-        .arg("localhost")
+        .arg(&server)
         .arg(&name)
         .spawn()
         .map_err(|e| {
@@ -651,22 +1928,29 @@ pub async fn connect_vm_rdp(name: String) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub async fn get_vm_ip(name: String) -> Result<String, String> {
-    let script = format!(
+pub async fn get_vm_ip(ps: State<'_, ExecutorState>, name: String) -> Result<String, HyperVError> {
+    ps.run_blocking(AuditContext::new("get_vm_ip").vm(&name), None, move |ps| {
+        get_vm_ip_sync(ps, &name)
+    })
+    .await
+}
+
+pub fn get_vm_ip_sync(ps: &dyn PowerShellExecutor, name: &str) -> Result<String, HyperVError> {
+    let script = ScriptBuilder::new().with_get_vm_exact().arg("VMName", name).build(
         r#"
-        $vm = Get-VM -Name '{}'
-        $ip = ($vm | Get-VMNetworkAdapter | Sort-Object -Property MacAddress | Select-Object -ExpandProperty IPAddresses | Where-Object {{ $_ -match '^(?:[0-9]{{1,3}}\.){{3}}[0-9]{{1,3}}$' }} | Select-Object -First 1)
-        if ($ip) {{ $ip }} else {{ "" }}
+        $vm = Get-VMExact $VMName
+        $ip = ($vm | Get-VMNetworkAdapter | Sort-Object -Property MacAddress | Select-Object -ExpandProperty IPAddresses | Where-Object { $_ -match '^(?:[0-9]{1,3}\.){3}[0-9]{1,3}$' } | Select-Object -First 1)
+        if ($ip) { $ip } else { "" }
         "#,
-        name
     );
-    let output = run_powershell(&script)?;
+    let output = ps.run(&script)?;
     let ip = output.trim().to_string();
     if ip.is_empty() {
-        Err(
-            "Could not retrieve VM IP Address. Ensure VM is running and connected to a network."
-                .to_string(),
-        )
+        Err(HyperVError::PowerShell(ErrorDetails {
+            message: "Could not retrieve VM IP Address.".to_string(),
+            hint: Some("Ensure VM is running and connected to a network.".to_string()),
+            record: None,
+        }))
     } else {
         Ok(ip)
     }
@@ -676,7 +1960,7 @@ pub async fn get_vm_ip(name: String) -> Result<String, String> {
 pub async fn load_vm_settings(
     window: Window,
     name: String,
-) -> Result<VMConnectionSettings, String> {
+) -> Result<VMConnectionSettings, HyperVError> {
     let store = VMSettingsStore::new(&window.app_handle());
     Ok(store.get(&name))
 }
@@ -686,19 +1970,27 @@ pub async fn save_vm_settings(
     window: Window,
     name: String,
     settings: VMConnectionSettings,
-) -> Result<(), String> {
+) -> Result<(), HyperVError> {
     let store = VMSettingsStore::new(&window.app_handle());
-    store.set(name, settings)
+    Ok(store.set(name, settings)?)
 }
 
 #[tauri::command]
 pub async fn connect_vm_rdp_native(
     _window: Window,
+    ps: State<'_, ExecutorState>,
     name: String,
     settings: VMConnectionSettings,
-) -> Result<(), String> {
+) -> Result<(), HyperVError> {
+    let context = AuditContext::new("connect_vm_rdp_native")
+        .vm(&name)
+        .secret(settings.password.as_deref());
+    // cmdkey stores credentials for the mstsc client, which always runs here
+    let local = ps.local_executor(context.clone());
+    let ps = ps.executor(context);
+
     // 1. Get IP
-    let ip = get_vm_ip(name.clone()).await?;
+    let ip = get_vm_ip_sync(ps.as_ref(), &name)?;
 
     // 2. Prepare Credentials
     let mut final_user = settings.username.clone().unwrap_or_default();
@@ -714,13 +2006,18 @@ pub async fn connect_vm_rdp_native(
     // Add credentials via cmdkey if provided
     if !final_user.is_empty() && !final_pass.is_empty() {
         // 1. Clear any existing credentials for this target first to avoid conflicts/stale data
-        let _ = run_powershell(&format!("cmdkey /delete:TERMSRV/{}", ip));
+        let delete_script = ScriptBuilder::new()
+            .arg("Target", &format!("TERMSRV/{}", ip))
+            .build(r#"cmdkey "/delete:$Target""#);
+        let _ = local.run(&delete_script);
 
         // 2. Add new credentials
-        let _ = run_powershell(&format!(
-            "cmdkey /generic:TERMSRV/{} /user:{} /pass:{}",
-            ip, final_user, final_pass
-        ));
+        let add_script = ScriptBuilder::new()
+            .arg("Target", &format!("TERMSRV/{}", ip))
+            .arg("User", &final_user)
+            .arg("Password", &final_pass)
+            .build(r#"cmdkey "/generic:$Target" "/user:$User" "/pass:$Password""#);
+        let _ = local.run(&add_script);
     }
 
     // 3. Generate .rdp file
@@ -766,7 +2063,8 @@ pub async fn connect_vm_rdp_native(
         rdp_content.push_str(&format!("drivestoredirect:s:{}\r\n", drives_str));
     }
 
-    fs::write(&rdp_path, rdp_content).map_err(|e| e.to_string())?;
+    fs::write(&rdp_path, rdp_content)
+        .map_err(|e| HyperVError::io("Failed to write RDP file", &e))?;
 
     // 4. Launch mstsc (with Zoom Automation)
     let final_scale = if settings.scale > 0 {
@@ -775,7 +2073,624 @@ pub async fn connect_vm_rdp_native(
         100
     };
     crate::commands::rdp::RdpAutomator::launch_with_zoom(&rdp_path, final_scale)
-        .map_err(|e| e.to_string())?;
+        .map_err(HyperVError::internal)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::executor::{ScriptRule, ScriptedExecutor};
+    use crate::commands::iso::IsoEdition;
+    use crate::commands::plan::Severity;
+    use std::sync::Mutex;
+
+    #[test]
+    fn list_vms_parses_output() {
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Get-VM",
+            r#"[{"name":"Gaming","state":"Running","cpu_usage":12,"memory_assigned_mb":8192,"uptime":"01:02:03","has_gpu":true,"cpu_cores":8,"network_switch":"Default Switch","ip_address":"172.20.1.5"},
+                {"name":"Lab","state":"Off","cpu_usage":0,"memory_assigned_mb":4096,"uptime":"00:00:00","has_gpu":false,"cpu_cores":2,"network_switch":"None","ip_address":null}]"#,
+        )]);
+        let vms = list_vms_sync(&ps).unwrap();
+        assert_eq!(vms.len(), 2);
+        assert_eq!(vms[0].name, "Gaming");
+        assert_eq!(vms[0].memory_assigned_mb, 8192);
+        assert!(vms[0].has_gpu);
+        assert_eq!(vms[0].ip_address.as_deref(), Some("172.20.1.5"));
+        assert_eq!(vms[1].state, "Off");
+        assert_eq!(vms[1].ip_address, None);
+
+        // One VM is a bare object, none is no output at all
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok(
+            "Get-VM",
+            r#"{"name":"Solo","state":"Off","cpu_usage":0,"memory_assigned_mb":2048,"uptime":"00:00:00","has_gpu":false,"cpu_cores":1,"network_switch":"None","ip_address":null}"#,
+        )]);
+        assert_eq!(list_vms_sync(&ps).unwrap()[0].name, "Solo");
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok("Get-VM", "")]);
+        assert!(list_vms_sync(&ps).unwrap().is_empty());
+    }
+
+    #[test]
+    fn list_vms_maps_errors() {
+        let ps = ScriptedExecutor::new(vec![ScriptRule::fail(
+            "Get-VM",
+            "Get-VM : The term 'Get-VM' is not recognized as the name of a cmdlet.\n    + CategoryInfo          : ObjectNotFound: (Get-VM:String) [], CommandNotFoundException\n    + FullyQualifiedErrorId : CommandNotFoundException",
+            1,
+        )]);
+        assert!(matches!(
+            list_vms_sync(&ps),
+            Err(HyperVError::HyperVModuleMissing(_))
+        ));
+
+        let ps = ScriptedExecutor::new(vec![ScriptRule::fail(
+            "Get-VM",
+            "Get-VM : You do not have the required permission to complete this task.\n    + CategoryInfo          : PermissionDenied: (:) [Get-VM], VirtualizationException",
+            1,
+        )]);
+        assert!(matches!(
+            list_vms_sync(&ps),
+            Err(HyperVError::NotElevated(_))
+        ));
+
+        let ps = ScriptedExecutor::new(vec![ScriptRule::ok("Get-VM", "[{\"name\":1}]")]);
+        assert!(matches!(list_vms_sync(&ps), Err(HyperVError::Internal(_))));
+    }
+
+    /// Run the provisioning script of `create_vm` against canned output
+    fn provision(
+        stdout: &str,
+        stderr: &str,
+        exit_code: i32,
+    ) -> (
+        Result<VMProgress, HyperVError>,
+        Arc<ProvisioningJob>,
+        Vec<(LogStream, String)>,
+        Vec<ProgressEvent>,
+    ) {
+        let command = provision_command("C:\\staging\\CopyFilesToVM.ps1", "pw");
+        let ps = ScriptedExecutor::new(vec![ScriptRule {
+            pattern: "& $ScriptPath -Password $Password".to_string(),
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            exit_code,
+            delay_ms: 0,
+        }]);
+        let job = JobRegistry::default().start(None, "vm").unwrap();
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let log = {
+            let lines = lines.clone();
+            move |stream, line| lines.lock().unwrap().push((stream, line))
+        };
+        let mut events = Vec::new();
+        let result = run_provisioning(
+            &ps,
+            &command,
+            &job,
+            log,
+            |event| events.push(event),
+            |_, _| {},
+        );
+        let lines = lines.lock().unwrap().clone();
+        (result, job, lines, events)
+    }
+
+    #[test]
+    fn create_vm_provisioning_succeeds() {
+        let (result, job, lines, events) = provision(
+            "##PROGRESS|prepare|1|1|50|Checking parameters\n\
+             ##RESOURCE|vhd|D:\\VMs\\vm.vhdx\n\
+             Copying drivers\n\
+             ##CHECKPOINT|image\n\
+             PROVISION_SUCCESS\n",
+            "WARNING: slow disk\n",
+            0,
+        );
+        let progress = result.unwrap();
+        assert!(progress.completed);
+        assert_eq!(progress.step, PROVISION_PHASES.len() as u32);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].phase, "prepare");
+        assert_eq!(job.pid(), None);
+        assert_eq!(job.summary().checkpoints, vec![Checkpoint::Image]);
+        // The disk belongs to the completed checkpoint, so a failed run keeps it
+        assert_eq!(
+            job.rollback_resources(true),
+            vec![ProvisionResource::Vhd("D:\\VMs\\vm.vhdx".to_string())]
+        );
+        assert!(job.rollback_resources(false).is_empty());
+        // Markers are kept out of the log; stderr keeps its stream
+        assert!(lines.iter().all(|(_, l)| !l.starts_with("##")));
+        assert!(lines.contains(&(LogStream::Stdout, "Copying drivers".to_string())));
+        assert!(lines.contains(&(LogStream::Stderr, "WARNING: slow disk".to_string())));
+    }
+
+    #[test]
+    fn create_vm_provisioning_errors() {
+        let (result, ..) = provision("PROVISION_FAILED: disk full\n", "", 1);
+        match result {
+            Err(HyperVError::PowerShell(d)) => assert!(d.message.contains("disk full")),
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+
+        // Success marker without a clean exit is still a failure
+        let (result, ..) = provision("PROVISION_SUCCESS\n", "", 3);
+        match result {
+            Err(HyperVError::PowerShell(d)) => assert!(d.message.contains("exited")),
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+
+        let ps = ScriptedExecutor::new(Vec::new());
+        let job = JobRegistry::default().start(None, "vm").unwrap();
+        job.cancel();
+        let result = run_provisioning(&ps, "& $ScriptPath", &job, |_, _| {}, |_| {}, |_, _| {});
+        assert!(matches!(result, Err(HyperVError::Cancelled(_))));
+        assert!(ps.calls().is_empty());
+    }
+
+    /// Frontend payload of a Windows 11 VM, with `overrides` applied
+    fn config(overrides: serde_json::Value) -> VMConfig {
+        let mut payload = serde_json::json!({
+            "name": "Gaming",
+            "disk_size_gb": 64,
+            "memory_gb": 8,
+            "cpu_cores": 4,
+            "iso_path": "C:\\iso\\win11.iso",
+            "edition": 1,
+            "tpm_enabled": true,
+            "secure_boot": true,
+            "network_switch": "Default Switch",
+            "gpu_name": "AUTO",
+            "vhd_path": "C:\\VMs",
+            "gpu_allocation_percent": 50,
+            "username": "gamer",
+            "password": "pw",
+            "auto_logon": true,
+        });
+        for (key, value) in overrides.as_object().unwrap() {
+            payload[key] = value.clone();
+        }
+        serde_json::from_value(payload).unwrap()
+    }
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    /// A host with room for the VM of `config`
+    fn facts() -> HostFacts {
+        HostFacts {
+            vhd_dir_exists: true,
+            source_exists: true,
+            source_bytes: Some(30 * GB),
+            free_disk_bytes: Some(500 * GB),
+            free_memory_bytes: 32 * GB,
+            total_memory_bytes: 64 * GB,
+            logical_processors: 16,
+            switches: vec!["Default Switch".to_string()],
+            gpus: vec!["NVIDIA GeForce RTX 4080".to_string()],
+            partitionable_gpus: 1,
+            architecture: "AMD64".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Windows media for `architecture` with images `indexes`
+    fn iso(architecture: &str, indexes: &[u32]) -> Result<IsoInfo, HyperVError> {
+        Ok(IsoInfo {
+            install_image: Some("sources/install.wim".to_string()),
+            build: Some(22631),
+            architecture: Some(architecture.to_string()),
+            editions: indexes
+                .iter()
+                .map(|&index| IsoEdition {
+                    index,
+                    name: format!("Windows 11 #{}", index),
+                    architecture: architecture.to_string(),
+                    build: 22631,
+                    edition_id: None,
+                    language: None,
+                    size_bytes: None,
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn check<'a>(checks: &'a [ResourceCheck], name: &str) -> Option<&'a ResourceCheck> {
+        checks.iter().find(|c| c.name == name)
+    }
+
+    #[test]
+    fn resource_checks_pass_on_a_roomy_host() {
+        let checks = vm_resource_checks(&config(serde_json::json!({})), &facts(), None, false);
+        let failed: Vec<_> = checks.iter().filter(|c| !c.passed).collect();
+        assert!(failed.is_empty(), "{:?}", failed);
+        for name in [
+            "name",
+            "vm_name_unique",
+            "vhd_file",
+            "free_space",
+            "gpu_partitioning",
+        ] {
+            assert!(check(&checks, name).is_some(), "{}", name);
+        }
+
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({ "name": "..\\..", "memory_gb": u32::MAX })),
+            &facts(),
+            None,
+            false,
+        );
+        let name = check(&checks, "name").unwrap();
+        assert!(!name.passed);
+        assert_eq!(name.field, "name");
+        assert!(check(&checks, "memory").unwrap().passed);
+        assert!(!check(&checks, "host_memory").unwrap().passed);
+    }
+
+    #[test]
+    fn interrupted_runs_expect_their_vm_and_disks() {
+        let facts = HostFacts {
+            vm_exists: true,
+            vhd_exists: true,
+            data_disk_exists: true,
+            ..facts()
+        };
+        let config = config(serde_json::json!({ "disk": { "data_disk_gb": 100 } }));
+
+        let checks = vm_resource_checks(&config, &facts, None, false);
+        for name in ["vm_name_unique", "vhd_file", "data_disk_file"] {
+            let check = check(&checks, name).unwrap();
+            assert!(
+                !check.passed && check.severity == Severity::Error,
+                "{}",
+                name
+            );
+        }
+        assert_eq!(check(&checks, "vm_name_unique").unwrap().field, "name");
+
+        let checks = vm_resource_checks(&config, &facts, None, true);
+        for name in ["vm_name_unique", "vhd_file", "data_disk_file", "free_space"] {
+            assert!(check(&checks, name).is_none(), "{}", name);
+        }
+        assert!(checks.iter().all(|c| c.passed));
+    }
+
+    #[test]
+    fn free_space_depends_on_source_and_allocation() {
+        let iso = serde_json::json!({ "kind": "iso" });
+        let copy = serde_json::json!({ "kind": "golden_image", "path": "D:\\golden.vhdx" });
+        let child = serde_json::json!({
+            "kind": "golden_image", "path": "D:\\golden.vhdx", "differencing": true,
+        });
+        // Source, disk options, free GB, then the outcome: passed or the severity of the failure
+        let cases = [
+            (&iso, serde_json::json!({}), 100, None),
+            (&iso, serde_json::json!({}), 50, Some(Severity::Warning)),
+            (
+                &iso,
+                serde_json::json!({ "allocation": "fixed" }),
+                100,
+                None,
+            ),
+            (
+                &iso,
+                serde_json::json!({ "allocation": "fixed" }),
+                50,
+                Some(Severity::Error),
+            ),
+            (&copy, serde_json::json!({}), 40, None),
+            (&copy, serde_json::json!({}), 20, Some(Severity::Error)),
+            (
+                &copy,
+                serde_json::json!({ "data_disk_gb": 20 }),
+                40,
+                Some(Severity::Warning),
+            ),
+            (&child, serde_json::json!({}), 1, None),
+            (
+                &child,
+                serde_json::json!({ "data_disk_gb": 20 }),
+                10,
+                Some(Severity::Warning),
+            ),
+            (
+                &child,
+                serde_json::json!({ "allocation": "fixed", "data_disk_gb": 20 }),
+                10,
+                Some(Severity::Error),
+            ),
+        ];
+        for (source, disk, free_gb, outcome) in cases {
+            let config = config(serde_json::json!({ "source": source, "disk": disk }));
+            let facts = HostFacts {
+                free_disk_bytes: Some(free_gb * GB),
+                ..facts()
+            };
+            let checks = vm_resource_checks(&config, &facts, None, false);
+            let free_space = check(&checks, "free_space").unwrap();
+            let case = format!(
+                "{} {} {} GB",
+                source,
+                serde_json::json!(config.disk),
+                free_gb
+            );
+            assert_eq!(free_space.field, "vhd_path", "{}", case);
+            match outcome {
+                None => assert!(free_space.passed, "{}: {}", case, free_space.message),
+                Some(severity) => {
+                    assert!(!free_space.passed, "{}", case);
+                    assert_eq!(free_space.severity, severity, "{}", case);
+                }
+            }
+        }
+
+        let facts = HostFacts {
+            free_disk_bytes: None,
+            ..facts()
+        };
+        let checks = vm_resource_checks(&config(serde_json::json!({})), &facts, None, false);
+        let free_space = check(&checks, "free_space").unwrap();
+        assert!(!free_space.passed);
+        assert_eq!(free_space.severity, Severity::Warning);
+    }
+
+    #[test]
+    fn iso_architecture_must_run_on_the_host() {
+        // Host architecture, ISO architecture, disk format, whether it boots
+        let cases = [
+            ("AMD64", "x64", "vhdx", true),
+            ("AMD64", "x86", "vhdx", false),
+            ("AMD64", "x86", "vhd", true),
+            ("ARM64", "x64", "vhdx", false),
+            ("ARM64", "arm64", "vhdx", true),
+            ("x86", "x86", "vhd", true),
+        ];
+        for (host, architecture, format, boots) in cases {
+            let config = config(serde_json::json!({ "disk": { "format": format } }));
+            let facts = HostFacts {
+                architecture: host.to_string(),
+                ..facts()
+            };
+            let checks = vm_resource_checks(&config, &facts, Some(&iso(architecture, &[1])), false);
+            let check = check(&checks, "iso_architecture").unwrap();
+            assert_eq!(check.passed, boots, "{}", check.message);
+            assert_eq!(check.field, "iso_path");
+        }
+
+        // Nothing is known about an unrecognised host
+        let facts = HostFacts {
+            architecture: String::new(),
+            ..facts()
+        };
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({})),
+            &facts,
+            Some(&iso("x64", &[1])),
+            false,
+        );
+        assert!(check(&checks, "iso_architecture").is_none());
+    }
+
+    #[test]
+    fn edition_must_be_an_image_of_the_iso() {
+        let media = iso("x64", &[1, 6]);
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({ "edition": 6 })),
+            &facts(),
+            Some(&media),
+            false,
+        );
+        let edition = check(&checks, "edition").unwrap();
+        assert!(edition.passed);
+        assert_eq!(edition.message, "Installs Windows 11 #6 (6)");
+        assert!(check(&checks, "iso_contents").unwrap().passed);
+
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({ "edition": 3 })),
+            &facts(),
+            Some(&media),
+            false,
+        );
+        let edition = check(&checks, "edition").unwrap();
+        assert!(!edition.passed);
+        assert_eq!(edition.field, "edition");
+
+        // An ISO install needs an edition; a golden image has none
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({ "edition": null })),
+            &facts(),
+            Some(&media),
+            false,
+        );
+        let edition = check(&checks, "edition").unwrap();
+        assert!(!edition.passed);
+        assert_eq!(edition.severity, Severity::Error);
+        let golden = config(serde_json::json!({
+            "edition": null,
+            "source": { "kind": "golden_image", "path": "D:\\golden.vhdx" },
+        }));
+        assert!(check(
+            &vm_resource_checks(&golden, &facts(), None, false),
+            "edition"
+        )
+        .is_none());
+
+        // Unreadable metadata and unreadable media leave the edition unchecked
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({ "edition": 3 })),
+            &facts(),
+            Some(&iso("x64", &[])),
+            false,
+        );
+        assert!(check(&checks, "edition").is_none());
+        let broken = Err(HyperVError::invalid_input("Truncated ISO image"));
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({})),
+            &facts(),
+            Some(&broken),
+            false,
+        );
+        let contents = check(&checks, "iso_contents").unwrap();
+        assert!(!contents.passed);
+        assert_eq!(contents.message, "Truncated ISO image");
+        assert!(check(&checks, "edition").is_none());
+
+        let not_windows = Ok(IsoInfo::default());
+        let checks = vm_resource_checks(
+            &config(serde_json::json!({})),
+            &facts(),
+            Some(&not_windows),
+            false,
+        );
+        assert!(!check(&checks, "iso_contents").unwrap().passed);
+    }
+
+    #[test]
+    fn gpu_checks_follow_the_selection() {
+        let gpu_checks = |gpu_name: &str, facts: &HostFacts| {
+            let config = config(serde_json::json!({ "gpu_name": gpu_name }));
+            vm_resource_checks(&config, facts, None, false)
+                .into_iter()
+                .filter(|c| c.name.starts_with("gpu"))
+                .map(|c| (c.name, c.passed))
+                .collect::<Vec<_>>()
+        };
+        let no_gpu = HostFacts {
+            partitionable_gpus: 0,
+            ..facts()
+        };
+
+        for off in ["None", "none", ""] {
+            assert!(gpu_checks(off, &no_gpu).is_empty(), "{:?}", off);
+        }
+        assert_eq!(
+            gpu_checks("auto", &facts()),
+            [
+                ("gpu_partitioning".to_string(), true),
+                ("gpu_allocation_percent".to_string(), true),
+            ]
+        );
+        assert_eq!(
+            gpu_checks("AUTO", &no_gpu)[0],
+            ("gpu_partitioning".to_string(), false)
+        );
+        assert_eq!(
+            gpu_checks("nvidia geforce rtx 4080", &facts())[1],
+            ("gpu_name".to_string(), true)
+        );
+        assert_eq!(
+            gpu_checks("Radeon RX 7900", &facts())[1],
+            ("gpu_name".to_string(), false)
+        );
+
+        let config = config(serde_json::json!({ "gpu_allocation_percent": 0 }));
+        let checks = vm_resource_checks(&config, &facts(), None, false);
+        assert!(!check(&checks, "gpu_allocation_percent").unwrap().passed);
+    }
+
+    #[test]
+    fn staging_dirs_stay_under_their_root() {
+        for name in ["Gaming", "Win 11 (GPU)", "dev.box"] {
+            assert_eq!(
+                provision_staging_dir(name).unwrap(),
+                staging_root().join(name)
+            );
+        }
+        for name in [
+            "",
+            "..",
+            "..\\..",
+            "a/../../x",
+            "C:\\Windows",
+            "a\\b",
+            "tab\there",
+            "trailing.",
+            "trailing ",
+        ] {
+            assert!(
+                matches!(
+                    provision_staging_dir(name),
+                    Err(HyperVError::InvalidInput(_))
+                ),
+                "{:?}",
+                name
+            );
+            assert!(remote_staging_name(name).is_err(), "{:?}", name);
+            assert!(remote_update_staging_name(name).is_err(), "{:?}", name);
+        }
+
+        // Scrubbing refuses anything the root does not directly hold
+        for dir in [
+            staging_root(),
+            staging_root().join(".."),
+            staging_root().join("a").join("b"),
+            env::temp_dir(),
+        ] {
+            assert_eq!(
+                scrub_staging_dir(&dir).unwrap_err().kind(),
+                std::io::ErrorKind::InvalidInput,
+                "{}",
+                dir.display()
+            );
+        }
+    }
+
+    /// The provisioning script rendered from the bundled template
+    fn render_script(test: &str, config: &VMConfig, open_console: bool) -> String {
+        let dir = std::env::temp_dir().join(format!("{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(PROVISION_SCRIPT.file_name),
+            include_str!("easy-gpu-pv/CopyFilesToVM.template.ps1"),
+        )
+        .unwrap();
+        let rendered = render_provision_files(&dir, config, &[], open_console);
+        fs::remove_dir_all(&dir).unwrap();
+        rendered.unwrap().script
+    }
+
+    #[test]
+    fn golden_image_skips_iso_install() {
+        let golden = config(serde_json::json!({
+            "source": { "kind": "golden_image", "path": "D:\\golden.vhdx" },
+        }));
+        let script = render_script("golden-image", &golden, true);
+        assert!(script.contains("SourceVhd = \"D:\\golden.vhdx\""));
+
+        // The checkpoint is only reported, so the ISO branch must not rely on it
+        let guards: Vec<&str> = script
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.contains("Test-ProvisionCheckpoint image"))
+            .collect();
+        assert_eq!(
+            guards,
+            [
+                "if ($SourceVhd -and -not (Test-ProvisionCheckpoint image)) {",
+                "if (-not $SourceVhd -and -not (Test-ProvisionCheckpoint image)) {",
+            ]
+        );
+        let iso_guard = script.find(guards[1]).unwrap();
+        let mount = script.find("-Phase mount_iso").unwrap();
+        assert!(iso_guard < mount);
+        assert_eq!(script[iso_guard..mount].lines().count(), 2);
+    }
+
+    #[test]
+    fn vmconnect_only_opens_for_local_vms() {
+        let open = |open_console| {
+            let script = render_script("vmconnect", &config(serde_json::json!({})), open_console);
+            let guard = script.find("if ($OpenConsole) {").unwrap();
+            let vmconnect = script.find("Start-Process \"vmconnect.exe\"").unwrap();
+            assert_eq!(script[guard..vmconnect].lines().count(), 3);
+            script
+                .lines()
+                .find(|line| line.starts_with("$OpenConsole = "))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(open(true), "$OpenConsole = \"true\" -eq \"true\"");
+        assert_eq!(open(false), "$OpenConsole = \"false\" -eq \"true\"");
+    }
+}
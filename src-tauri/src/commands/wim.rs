@@ -0,0 +1,371 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::error::HyperVError;
+
+/// Size of the fixed WIM header
+pub const WIM_HEADER_BYTES: usize = 208;
+const WIM_MAGIC: &[u8; 8] = b"MSWIM\0\0\0";
+/// Largest XML metadata the parser reads, far above any real image
+const MAX_XML_BYTES: u64 = 32 * 1024 * 1024;
+/// Resource header flag of data stored compressed
+const RESOURCE_COMPRESSED: u8 = 0x04;
+
+/// Fixed header at the start of a WIM or ESD file
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct WimHeader {
+    pub version: u32,
+    pub flags: u32,
+    /// "none", "xpress", "lzx" or "lzms"
+    pub compression: String,
+    /// Part of a split (.swm) image, counted from 1
+    pub part_number: u16,
+    pub total_parts: u16,
+    pub image_count: u32,
+    /// Index of the bootable image, 0 for none
+    pub boot_index: u32,
+    xml_offset: u64,
+    xml_bytes: u64,
+}
+
+/// One image described by the XML metadata of a WIM
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct WimImage {
+    pub index: u32,
+    pub name: String,
+    /// e.g. Professional or ServerDatacenter
+    pub edition_id: Option<String>,
+    /// Windows build number, e.g. 22631
+    pub build: Option<u32>,
+    /// x86, x64, arm or arm64
+    pub architecture: Option<String>,
+    /// Default language, e.g. en-US
+    pub language: Option<String>,
+    /// Size of the applied image
+    pub total_bytes: Option<u64>,
+}
+
+/// Header and images of a WIM, read without DISM
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct WimInfo {
+    pub header: WimHeader,
+    pub images: Vec<WimImage>,
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// Architecture name for a `PROCESSOR_ARCHITECTURE_*` value, as DISM
+/// reports it
+pub fn wim_architecture(arch: u32) -> String {
+    match arch {
+        0 => "x86".to_string(),
+        5 => "arm".to_string(),
+        9 => "x64".to_string(),
+        12 => "arm64".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Parse the 208-byte header at the start of `bytes`
+pub fn parse_header(bytes: &[u8]) -> io::Result<WimHeader> {
+    if bytes.get(..8) != Some(&WIM_MAGIC[..]) {
+        return Err(invalid("Not a WIM file"));
+    }
+    if bytes.len() < WIM_HEADER_BYTES {
+        return Err(invalid("Truncated WIM header"));
+    }
+    let flags = le_u32(bytes, 16);
+    // The XML resource header: a 56-bit stored size, its flags, the offset
+    // and the original size
+    let xml_flags = bytes[72 + 7];
+    if xml_flags & RESOURCE_COMPRESSED != 0 {
+        return Err(invalid("Compressed WIM metadata is not supported"));
+    }
+    let compression = match flags {
+        f if f & 0x0008_0000 != 0 => "lzms",
+        f if f & 0x0004_0000 != 0 => "lzx",
+        f if f & 0x0002_0000 != 0 => "xpress",
+        _ => "none",
+    };
+    Ok(WimHeader {
+        version: le_u32(bytes, 12),
+        flags,
+        compression: compression.to_string(),
+        part_number: le_u16(bytes, 40),
+        total_parts: le_u16(bytes, 42),
+        image_count: le_u32(bytes, 44),
+        boot_index: le_u32(bytes, 120),
+        xml_offset: le_u64(bytes, 72 + 8),
+        xml_bytes: le_u64(bytes, 72 + 16),
+    })
+}
+
+/// Parse the UTF-16LE XML metadata of a WIM into its images
+pub fn parse_xml(bytes: &[u8]) -> io::Result<Vec<WimImage>> {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    let text = String::from_utf16_lossy(&units);
+    let text = text.trim_start_matches('\u{feff}').trim_end_matches('\0');
+    let document = roxmltree::Document::parse(text)
+        .map_err(|e| invalid(format!("Invalid WIM metadata: {}", e)))?;
+
+    let child = |node: roxmltree::Node, path: &[&str]| -> Option<String> {
+        let mut node = node;
+        for name in path {
+            node = node.children().find(|n| n.has_tag_name(*name))?;
+        }
+        node.text().map(|t| t.trim().to_string())
+    };
+    let number = |node: roxmltree::Node, path: &[&str]| -> Option<u64> {
+        let text = child(node, path)?;
+        match text.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => text.parse().ok(),
+        }
+    };
+
+    let mut images: Vec<WimImage> = document
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("IMAGE"))
+        .filter_map(|image| {
+            let index = image.attribute("INDEX")?.parse().ok()?;
+            let language = child(image, &["WINDOWS", "LANGUAGES", "DEFAULT"])
+                .or_else(|| child(image, &["WINDOWS", "LANGUAGES", "LANGUAGE"]));
+            Some(WimImage {
+                index,
+                name: child(image, &["NAME"])
+                    .or_else(|| child(image, &["DISPLAYNAME"]))
+                    .unwrap_or_default(),
+                edition_id: child(image, &["WINDOWS", "EDITIONID"]),
+                build: number(image, &["WINDOWS", "VERSION", "BUILD"]).map(|b| b as u32),
+                architecture: number(image, &["WINDOWS", "ARCH"])
+                    .map(|a| wim_architecture(a as u32)),
+                language,
+                total_bytes: number(image, &["TOTALBYTES"]),
+            })
+        })
+        .collect();
+    images.sort_by_key(|i| i.index);
+    Ok(images)
+}
+
+/// Read a WIM through `read_at`, which returns `len` bytes at an offset
+/// of the file. This lets the same parser read a WIM on disk or inside
+/// an ISO.
+pub fn read_wim<F>(mut read_at: F) -> io::Result<WimInfo>
+where
+    F: FnMut(u64, u64) -> io::Result<Vec<u8>>,
+{
+    let header = parse_header(&read_at(0, WIM_HEADER_BYTES as u64)?)?;
+    if header.xml_bytes == 0 {
+        return Ok(WimInfo {
+            header,
+            images: Vec::new(),
+        });
+    }
+    if header.xml_bytes > MAX_XML_BYTES {
+        return Err(invalid(format!(
+            "WIM metadata of {} bytes is too large",
+            header.xml_bytes
+        )));
+    }
+    let xml = read_at(header.xml_offset, header.xml_bytes)?;
+    if xml.len() as u64 != header.xml_bytes {
+        return Err(invalid("WIM metadata is truncated"));
+    }
+    let images = parse_xml(&xml)?;
+    // Image indexes count from 1 up to the number of images in the header
+    if let Some(image) = images
+        .iter()
+        .find(|i| i.index == 0 || i.index > header.image_count)
+    {
+        return Err(invalid(format!(
+            "WIM metadata describes image {} of {}",
+            image.index, header.image_count
+        )));
+    }
+    Ok(WimInfo { header, images })
+}
+
+/// Read the header and images of the WIM or ESD file at `path`
+pub fn read_wim_file(path: &Path) -> Result<WimInfo, HyperVError> {
+    let mut file = File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => {
+            HyperVError::invalid_input(format!("WIM not found: {}", path.display()))
+        }
+        _ => HyperVError::internal(format!("Failed to open {}: {}", path.display(), e)),
+    })?;
+    read_wim(|offset, len| {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buffer = Vec::new();
+        (&mut file).take(len).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    })
+    .map_err(|e| {
+        HyperVError::invalid_input(format!(
+            "{} is not a readable WIM file: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Header and images of a WIM or ESD on this machine
+#[tauri::command]
+pub async fn inspect_wim(wim_path: String) -> Result<WimInfo, HyperVError> {
+    tokio::task::spawn_blocking(move || read_wim_file(Path::new(&wim_path)))
+        .await
+        .map_err(HyperVError::task_failed)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = include_str!("testdata/wim/install.xml");
+    /// Where the builder stores the metadata, past a gap standing in for resources
+    const XML_OFFSET: usize = 4096;
+
+    /// Metadata as WIMs store it: UTF-16LE with a byte order mark
+    fn utf16(xml: &str) -> Vec<u8> {
+        std::iter::once(0xFEFF)
+            .chain(xml.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    /// XPRESS-compressed WIM with `image_count` images described by `xml`
+    fn wim(image_count: u32, xml: &str) -> Vec<u8> {
+        let xml = utf16(xml);
+        let mut bytes = vec![0; XML_OFFSET];
+        bytes[..8].copy_from_slice(WIM_MAGIC);
+        bytes[8..12].copy_from_slice(&(WIM_HEADER_BYTES as u32).to_le_bytes());
+        bytes[12..16].copy_from_slice(&0x10d00u32.to_le_bytes());
+        bytes[16..20].copy_from_slice(&0x0002_0002u32.to_le_bytes());
+        bytes[40..42].copy_from_slice(&1u16.to_le_bytes());
+        bytes[42..44].copy_from_slice(&1u16.to_le_bytes());
+        bytes[44..48].copy_from_slice(&image_count.to_le_bytes());
+        // XML resource: stored size, flags (none), offset, original size
+        bytes[72..79].copy_from_slice(&(xml.len() as u64).to_le_bytes()[..7]);
+        bytes[80..88].copy_from_slice(&(XML_OFFSET as u64).to_le_bytes());
+        bytes[88..96].copy_from_slice(&(xml.len() as u64).to_le_bytes());
+        bytes[120..124].copy_from_slice(&0u32.to_le_bytes());
+        bytes.extend(xml);
+        bytes
+    }
+
+    fn read(bytes: &[u8]) -> io::Result<WimInfo> {
+        read_wim(|offset, len| {
+            let start = (offset as usize).min(bytes.len());
+            let end = start.saturating_add(len as usize).min(bytes.len());
+            Ok(bytes[start..end].to_vec())
+        })
+    }
+
+    #[test]
+    fn reads_header_and_images() {
+        let info = read(&wim(2, METADATA)).unwrap();
+        assert_eq!(info.header.version, 0x10d00);
+        assert_eq!(info.header.compression, "xpress");
+        assert_eq!((info.header.part_number, info.header.total_parts), (1, 1));
+        assert_eq!(info.header.image_count, 2);
+        assert_eq!(info.header.boot_index, 0);
+
+        let home = &info.images[0];
+        assert_eq!(home.index, 1);
+        assert_eq!(home.name, "Windows 11 Home");
+        assert_eq!(home.edition_id.as_deref(), Some("Core"));
+        assert_eq!(home.build, Some(22631));
+        assert_eq!(home.architecture.as_deref(), Some("x64"));
+        assert_eq!(home.language.as_deref(), Some("en-US"));
+        assert_eq!(home.total_bytes, Some(16_491_066_020));
+
+        // No NAME or DEFAULT language: fall back to DISPLAYNAME and the first LANGUAGE
+        let pro = &info.images[1];
+        assert_eq!(pro.index, 2);
+        assert_eq!(pro.name, "Windows 11 Pro");
+        assert_eq!(pro.edition_id.as_deref(), Some("Professional"));
+        assert_eq!(pro.language.as_deref(), Some("de-DE"));
+        assert_eq!(pro.total_bytes, Some(16_649_613_212));
+    }
+
+    #[test]
+    fn reads_wim_files() {
+        let path = std::env::temp_dir().join(format!("install-{}.wim", std::process::id()));
+        std::fs::write(&path, wim(2, METADATA)).unwrap();
+        let info = read_wim_file(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(info.unwrap().images.len(), 2);
+
+        let missing = read_wim_file(Path::new("/nonexistent/install.wim")).unwrap_err();
+        assert!(missing.message().starts_with("WIM not found"));
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = wim(2, METADATA);
+        bytes[..8].copy_from_slice(b"MSWIM\0\0\x01");
+        assert_eq!(read(&bytes).unwrap_err().to_string(), "Not a WIM file");
+        assert_eq!(read(b"").unwrap_err().to_string(), "Not a WIM file");
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = wim(2, METADATA);
+        assert_eq!(
+            read(&bytes[..WIM_HEADER_BYTES - 1])
+                .unwrap_err()
+                .to_string(),
+            "Truncated WIM header"
+        );
+        assert_eq!(
+            read(&bytes[..bytes.len() - 2]).unwrap_err().to_string(),
+            "WIM metadata is truncated"
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_image_index() {
+        assert_eq!(
+            read(&wim(1, METADATA)).unwrap_err().to_string(),
+            "WIM metadata describes image 2 of 1"
+        );
+        let zero = METADATA.replace("INDEX=\"1\"", "INDEX=\"0\"");
+        assert_eq!(
+            read(&wim(2, &zero)).unwrap_err().to_string(),
+            "WIM metadata describes image 0 of 2"
+        );
+    }
+
+    #[test]
+    fn rejects_compressed_or_malformed_metadata() {
+        let mut compressed = wim(2, METADATA);
+        compressed[79] = RESOURCE_COMPRESSED;
+        assert!(read(&compressed).is_err());
+
+        let malformed = wim(2, "<WIM><IMAGE INDEX=\"1\"></WIM>");
+        assert!(read(&malformed)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid WIM metadata"));
+    }
+}
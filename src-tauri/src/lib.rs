@@ -1,27 +1,51 @@
-mod commands;
+pub mod commands;
 
+use commands::audit::AuditLog;
+use commands::host::HostStore;
+use commands::jobs::JobRegistry;
+use commands::lock::VmLocks;
 use commands::{
-    cancel_create_vm, check_system, connect_vm_rdp, connect_vm_rdp_native, create_vm, delete_vm,
-    get_default_vhd_path, get_host_drives, get_network_switches, get_vm_ip, is_admin, list_vms,
-    load_vm_settings, restart_as_admin, save_vm_settings, start_vm, stop_vm, test_gpu_partitioning,
-    update_vm, update_vm_config, validate_vm_config, ProvisioningState,
+    cancel_call, cancel_create_vm, check_system, connect_vm_rdp, connect_vm_rdp_native, create_vm,
+    delete_host, delete_vm, discard_provisioning_run, get_active_host, get_default_vhd_path,
+    get_host_drives, get_network_switches, get_provisioning_checkpoint, get_provisioning_log,
+    get_vm_ip, get_vm_run_log, inspect_iso, inspect_wim, is_admin, list_hosts, list_iso_editions,
+    list_provisioning_jobs, list_running_calls, list_vm_operations, list_vm_runs, list_vms,
+    load_vm_settings, query_audit_log, restart_as_admin, save_host, save_vm_settings,
+    set_active_host, start_vm, stop_vm, test_gpu_partitioning, test_host_connection, update_vm,
+    update_vm_config, validate_vm_config, ExecutorState,
 };
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
-        .setup(|_app| {
+        .setup(|app| {
+            let executor = app.state::<ExecutorState>();
+            if let Ok(data_dir) = app.path().app_data_dir() {
+                executor.set_audit_log(AuditLog::new(data_dir.join("audit")));
+            }
+            let hosts = HostStore::new(app.handle());
+            let _ = hosts.protect_saved_passwords(
+                executor
+                    .local_executor(commands::audit::AuditContext::new("protect_host_passwords"))
+                    .as_ref(),
+            );
+            executor.set_host(hosts.active());
+
             #[cfg(not(debug_assertions))]
             {
-                if !commands::utils::is_admin_sync() {
-                    let _ = commands::utils::restart_as_admin_sync();
+                let ps = executor.local_executor(commands::audit::AuditContext::new("startup"));
+                if !commands::utils::is_admin_sync(ps.as_ref()) {
+                    let _ = commands::utils::restart_as_admin_sync(ps.as_ref());
                 }
             }
             Ok(())
         })
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
-        .manage(ProvisioningState::default())
+        .manage(ExecutorState::default())
+        .manage(JobRegistry::default())
+        .manage(VmLocks::default())
         .invoke_handler(tauri::generate_handler![
             check_system,
             get_network_switches,
@@ -43,7 +67,26 @@ pub fn run() {
             save_vm_settings,
             is_admin,
             restart_as_admin,
-            get_host_drives
+            get_host_drives,
+            query_audit_log,
+            list_hosts,
+            save_host,
+            delete_host,
+            get_active_host,
+            set_active_host,
+            test_host_connection,
+            list_vm_operations,
+            list_provisioning_jobs,
+            get_provisioning_log,
+            list_running_calls,
+            cancel_call,
+            get_provisioning_checkpoint,
+            discard_provisioning_run,
+            list_iso_editions,
+            inspect_iso,
+            inspect_wim,
+            list_vm_runs,
+            get_vm_run_log
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -12,6 +12,7 @@ import {
 import { Button, Badge, Separator } from "./components/ui";
 import { ThemeProvider, useTheme } from "./contexts/ThemeContext";
 import { LogProvider } from "./contexts/LogContext";
+import { HostProvider, useHost } from "./contexts/HostContext";
 import {
   LayoutDashboard,
   Plus,
@@ -24,6 +25,7 @@ import {
   PanelLeftClose,
   PanelLeft,
   FileText,
+  Server,
 } from "lucide-react";
 import "./App.css";
 
@@ -39,6 +41,9 @@ import { Globe, Github } from "lucide-react";
 
 type Tab = "dashboard" | "create" | "list" | "gpu" | "logs" | "settings";
 
+/** Select value standing for the local machine in the host switcher */
+const LOCAL_HOST = "__local__";
+
 interface VMInfo {
   name: string;
   state: string;
@@ -50,6 +55,7 @@ function AppContent() {
   const [runningVMs, setRunningVMs] = useState(0);
   const [sidebarOpen, setSidebarOpen] = useState(true);
   const { theme, setTheme } = useTheme();
+  const { hosts, activeHost, setActiveHost } = useHost();
 
   const navItems = [
     { id: "dashboard" as Tab, label: t("Dashboard"), icon: LayoutDashboard },
@@ -74,12 +80,20 @@ function AppContent() {
     loadVMCount();
     const interval = setInterval(loadVMCount, 10000);
     return () => clearInterval(interval);
-  }, []);
+  }, [activeHost]);
 
   const changeLanguage = (value: string) => {
     i18n.changeLanguage(value);
   };
 
+  const changeHost = async (value: string) => {
+    try {
+      await setActiveHost(value === LOCAL_HOST ? null : value);
+    } catch {
+      // ignore, keep the current host
+    }
+  };
+
   return (
     <div className="flex h-screen bg-background text-foreground">
       {/* Sidebar */}
@@ -223,10 +237,36 @@ function AppContent() {
           <h1 className="text-lg font-semibold">
             {navItems.find((item) => item.id === activeTab)?.label}
           </h1>
+          {hosts.length > 0 && (
+            <div className="ml-auto flex items-center gap-2">
+              <Server className="h-4 w-4 text-muted-foreground" />
+              <Select
+                value={activeHost ?? LOCAL_HOST}
+                onValueChange={changeHost}
+              >
+                <SelectTrigger className="w-48 h-8 text-xs">
+                  <SelectValue placeholder={t("Hyper-V Host")} />
+                </SelectTrigger>
+                <SelectContent>
+                  <SelectItem value={LOCAL_HOST}>
+                    {t("This Computer")}
+                  </SelectItem>
+                  {hosts.map((host) => (
+                    <SelectItem key={host.name} value={host.name}>
+                      {host.name}
+                    </SelectItem>
+                  ))}
+                </SelectContent>
+              </Select>
+            </div>
+          )}
         </header>
 
-        {/* Content */}
-        <main className="flex-1 overflow-y-auto p-6">
+        {/* Content (remounted on host switch so every view reloads) */}
+        <main
+          key={activeHost ?? LOCAL_HOST}
+          className="flex-1 overflow-y-auto p-6"
+        >
           {activeTab === "dashboard" && <Dashboard />}
           {activeTab === "create" && (
             <VMForm onSuccess={() => setActiveTab("list")} />
@@ -245,9 +285,11 @@ function App() {
   return (
     <ThemeProvider>
       <LogProvider>
-        <ErrorBoundary>
-          <AppContent />
-        </ErrorBoundary>
+        <HostProvider>
+          <ErrorBoundary>
+            <AppContent />
+          </ErrorBoundary>
+        </HostProvider>
       </LogProvider>
     </ThemeProvider>
   );
@@ -12,6 +12,8 @@ import {
   Square,
 } from "lucide-react";
 import { useTranslation } from "react-i18next";
+import { VMBusyBadge } from "./VMBusyBadge";
+import { useVmOperations } from "@/hooks/use-vm-operations";
 
 interface SystemInfo {
   os_version: string;
@@ -32,6 +34,7 @@ export function Dashboard() {
   const { t } = useTranslation();
   const [systemInfo, setSystemInfo] = useState<SystemInfo | null>(null);
   const [vms, setVms] = useState<VMInfo[]>([]);
+  const { busy } = useVmOperations();
   const [loading, setLoading] = useState(true);
 
   useEffect(() => {
@@ -198,15 +201,18 @@ export function Dashboard() {
                         </p>
                       </div>
                     </div>
-                    <span
-                      className={`text-xs px-2 py-1 rounded-full ${
-                        vm.state.toLowerCase() === "running"
-                          ? "bg-green-100 text-green-800 dark:bg-green-900 dark:text-green-200"
-                          : "bg-gray-100 text-gray-600 dark:bg-gray-800 dark:text-gray-400"
-                      }`}
-                    >
-                      {vm.state}
-                    </span>
+                    <div className="flex items-center gap-2">
+                      <VMBusyBadge {...busy(vm.name)} />
+                      <span
+                        className={`text-xs px-2 py-1 rounded-full ${
+                          vm.state.toLowerCase() === "running"
+                            ? "bg-green-100 text-green-800 dark:bg-green-900 dark:text-green-200"
+                            : "bg-gray-100 text-gray-600 dark:bg-gray-800 dark:text-gray-400"
+                        }`}
+                      >
+                        {vm.state}
+                      </span>
+                    </div>
                   </div>
                 ))}
               </div>
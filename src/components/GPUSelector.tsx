@@ -1,5 +1,6 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { formatError } from "@/lib/utils";
 import {
   Card,
   CardContent,
@@ -53,7 +54,7 @@ export function GPUSelector() {
         setGpuList(systemInfo.gpu_list);
         setVms(vmList);
       } catch (err) {
-        setError(err as string);
+        setError(formatError(err));
       } finally {
         setLoading(false);
       }
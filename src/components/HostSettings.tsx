@@ -0,0 +1,229 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { useTranslation } from "react-i18next";
+import { formatError } from "@/lib/utils";
+import { HostProfile, HostTransport, useHost } from "@/contexts/HostContext";
+import {
+  Button,
+  Card,
+  CardContent,
+  CardHeader,
+  CardTitle,
+  Input,
+  Label,
+  Select,
+  SelectContent,
+  SelectItem,
+  SelectTrigger,
+  SelectValue,
+} from "@/components/ui";
+import { Server, Trash2, Plus, Plug, Loader2 } from "lucide-react";
+
+const EMPTY_HOST: HostProfile = {
+  name: "",
+  hostname: "",
+  username: "",
+  password: "",
+  transport: "wsman",
+  port: null,
+};
+
+export function HostSettings() {
+  const { t } = useTranslation();
+  const { hosts, activeHost, refreshHosts } = useHost();
+  const [draft, setDraft] = useState<HostProfile>(EMPTY_HOST);
+  const [busy, setBusy] = useState(false);
+  const [status, setStatus] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  // Empty password means "keep the stored one" when editing a profile
+  const payload = (): HostProfile => ({
+    ...draft,
+    username: draft.username || null,
+    password: draft.password || null,
+  });
+
+  const run = async (action: () => Promise<void>) => {
+    setBusy(true);
+    setError(null);
+    setStatus(null);
+    try {
+      await action();
+    } catch (err) {
+      setError(formatError(err));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleTest = () =>
+    run(async () => {
+      const computer = await invoke<string>("test_host_connection", {
+        profile: payload(),
+      });
+      setStatus(t("Connected to {{name}}", { name: computer }));
+    });
+
+  const handleSave = () =>
+    run(async () => {
+      await invoke("save_host", { profile: payload() });
+      await refreshHosts();
+      setDraft(EMPTY_HOST);
+    });
+
+  const handleDelete = (name: string) =>
+    run(async () => {
+      await invoke("delete_host", { name });
+      await refreshHosts();
+    });
+
+  return (
+    <Card>
+      <CardHeader>
+        <CardTitle className="text-lg flex items-center gap-2">
+          <Server className="h-5 w-5" />
+          {t("Hyper-V Hosts")}
+        </CardTitle>
+      </CardHeader>
+      <CardContent className="space-y-4">
+        {hosts.length === 0 ? (
+          <p className="text-sm text-muted-foreground">
+            {t("No remote hosts. Commands run on this computer.")}
+          </p>
+        ) : (
+          <ul className="space-y-2">
+            {hosts.map((host) => (
+              <li
+                key={host.name}
+                className="flex items-center justify-between rounded-md border px-3 py-2"
+              >
+                <button
+                  className="text-left"
+                  onClick={() => setDraft({ ...host, password: "" })}
+                >
+                  <div className="text-sm font-medium">
+                    {host.name}
+                    {activeHost === host.name && (
+                      <span className="ml-2 text-xs text-primary">
+                        ({t("Active")})
+                      </span>
+                    )}
+                  </div>
+                  <div className="text-xs text-muted-foreground">
+                    {host.username ? `${host.username}@` : ""}
+                    {host.hostname}
+                    {host.transport === "wsman_https" ? " (HTTPS)" : ""}
+                  </div>
+                </button>
+                <Button
+                  variant="ghost"
+                  size="icon"
+                  disabled={busy}
+                  onClick={() => handleDelete(host.name)}
+                  title={t("Remove")}
+                >
+                  <Trash2 className="h-4 w-4" />
+                </Button>
+              </li>
+            ))}
+          </ul>
+        )}
+
+        <div className="grid grid-cols-2 gap-3">
+          <div className="space-y-1">
+            <Label>{t("Profile Name")}</Label>
+            <Input
+              value={draft.name}
+              onChange={(e) => setDraft({ ...draft, name: e.target.value })}
+            />
+          </div>
+          <div className="space-y-1">
+            <Label>{t("Hostname")}</Label>
+            <Input
+              value={draft.hostname}
+              placeholder="hyperv01.example.local"
+              onChange={(e) =>
+                setDraft({ ...draft, hostname: e.target.value })
+              }
+            />
+          </div>
+          <div className="space-y-1">
+            <Label>{t("Username")}</Label>
+            <Input
+              value={draft.username ?? ""}
+              placeholder="DOMAIN\\Administrator"
+              onChange={(e) =>
+                setDraft({ ...draft, username: e.target.value })
+              }
+            />
+          </div>
+          <div className="space-y-1">
+            <Label>{t("Password")}</Label>
+            <Input
+              type="password"
+              value={draft.password ?? ""}
+              onChange={(e) =>
+                setDraft({ ...draft, password: e.target.value })
+              }
+            />
+          </div>
+          <div className="space-y-1">
+            <Label>{t("Transport")}</Label>
+            <Select
+              value={draft.transport}
+              onValueChange={(value) =>
+                setDraft({ ...draft, transport: value as HostTransport })
+              }
+            >
+              <SelectTrigger>
+                <SelectValue />
+              </SelectTrigger>
+              <SelectContent>
+                <SelectItem value="wsman">WinRM (HTTP)</SelectItem>
+                <SelectItem value="wsman_https">WinRM (HTTPS)</SelectItem>
+              </SelectContent>
+            </Select>
+          </div>
+          <div className="space-y-1">
+            <Label>{t("Port (Optional)")}</Label>
+            <Input
+              type="number"
+              value={draft.port ?? ""}
+              onChange={(e) =>
+                setDraft({
+                  ...draft,
+                  port: e.target.value ? Number(e.target.value) : null,
+                })
+              }
+            />
+          </div>
+        </div>
+
+        {error && <p className="text-sm text-destructive">{error}</p>}
+        {status && <p className="text-sm text-green-600">{status}</p>}
+
+        <div className="flex justify-end gap-2">
+          <Button
+            variant="outline"
+            disabled={busy || !draft.hostname}
+            onClick={handleTest}
+          >
+            {busy ? (
+              <Loader2 className="h-4 w-4 mr-2 animate-spin" />
+            ) : (
+              <Plug className="h-4 w-4 mr-2" />
+            )}
+            {t("Test Connection")}
+          </Button>
+          <Button
+            disabled={busy || !draft.name || !draft.hostname}
+            onClick={handleSave}
+          >
+            <Plus className="h-4 w-4 mr-2" />
+            {t("Save Host")}
+          </Button>
+        </div>
+      </CardContent>
+    </Card>
+  );
+}
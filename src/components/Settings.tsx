@@ -2,6 +2,7 @@ import { useTheme } from "@/contexts/ThemeContext";
 import { Card, CardContent, CardHeader, CardTitle } from "@/components/ui";
 import { Sun, Moon, Monitor, Info } from "lucide-react";
 import { useTranslation } from "react-i18next";
+import { HostSettings } from "./HostSettings";
 
 export function Settings() {
   const { t } = useTranslation();
@@ -60,6 +61,9 @@ export function Settings() {
         </CardContent>
       </Card>
 
+      {/* Remote Hyper-V Hosts */}
+      <HostSettings />
+
       {/* About */}
       <Card>
         <CardHeader>
@@ -1,5 +1,6 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { formatError } from "@/lib/utils";
 import {
   Button,
   Card,
@@ -38,7 +39,7 @@ export function SystemCheck() {
       const info = await invoke<SystemInfo>("check_system");
       setSystemInfo(info);
     } catch (err) {
-      setError(err as string);
+      setError(formatError(err));
     } finally {
       setLoading(false);
     }
@@ -0,0 +1,24 @@
+import { useTranslation } from "react-i18next";
+import { Loader2 } from "lucide-react";
+import { VmOperation } from "@/hooks/use-vm-operations";
+
+interface VMBusyBadgeProps {
+  running: VmOperation | null;
+  queued: number;
+}
+
+/** "Busy: Updating GPU" badge for a VM held by a backend operation */
+export function VMBusyBadge({ running, queued }: VMBusyBadgeProps) {
+  const { t } = useTranslation();
+  if (!running && queued === 0) return null;
+
+  return (
+    <span className="flex items-center gap-1 text-xs px-2 py-1 rounded-full font-medium bg-amber-100 text-amber-800 dark:bg-amber-900 dark:text-amber-200">
+      <Loader2 className="h-3 w-3 animate-spin" />
+      {running
+        ? t("Busy: {{operation}}", { operation: t(running.description) })
+        : t("Waiting")}
+      {queued > 0 && ` (${t("{{count}} queued", { count: queued })})`}
+    </span>
+  );
+}
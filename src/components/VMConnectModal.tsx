@@ -1,5 +1,6 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { formatError } from "@/lib/utils";
 import { useTranslation } from "react-i18next";
 import {
   Dialog,
@@ -117,7 +118,7 @@ export function VMConnectModal({
       await invoke("connect_vm_rdp", { name: vmName });
       onClose();
     } catch (e) {
-      alert(t("Connection Failed: {{error}}", { error: e }));
+      alert(t("Connection Failed: {{error}}", { error: formatError(e) }));
     } finally {
       setLoading(false);
     }
@@ -130,7 +131,7 @@ export function VMConnectModal({
       await invoke("connect_vm_rdp_native", { name: vmName, settings });
       onClose();
     } catch (e) {
-      alert(t("RDP Connection Failed: {{error}}", { error: e }));
+      alert(t("RDP Connection Failed: {{error}}", { error: formatError(e) }));
     } finally {
       setLoading(false);
     }
@@ -1,15 +1,45 @@
 import { useEffect, useRef } from "react";
 import { Button } from "@/components/ui/button";
+import { Progress } from "@/components/ui";
 import { Loader2, XCircle, CheckCircle, Terminal } from "lucide-react";
 import { useTranslation } from "react-i18next";
 
+/** Payload of the `vm-progress` event (see `ProgressEvent` in the Rust backend) */
+export interface ProvisioningProgress {
+  job_id: number;
+  vm: string;
+  phase: string;
+  phase_name: string;
+  step: number;
+  total_steps: number;
+  phase_percent: number | null;
+  overall_percent: number;
+  message: string;
+  elapsed_secs: number;
+  eta_secs: number | null;
+}
+
+function formatDuration(totalSecs: number) {
+  const hours = Math.floor(totalSecs / 3600);
+  const minutes = Math.floor((totalSecs % 3600) / 60);
+  const seconds = String(totalSecs % 60).padStart(2, "0");
+  return hours > 0
+    ? `${hours}:${String(minutes).padStart(2, "0")}:${seconds}`
+    : `${minutes}:${seconds}`;
+}
+
 interface VMCreationModalProps {
   open: boolean;
   vmName: string;
   logs: string[];
+  progress?: ProvisioningProgress | null;
   status: "idle" | "running" | "success" | "error";
   error?: string;
   onCancel: () => void;
+  /** Offered after a failure when the run can be resumed */
+  onResume?: () => void;
+  /** Offered along with resuming: removes what the failed run created */
+  onDiscard?: () => void;
   onClose: () => void;
   title?: string;
 }
@@ -18,9 +48,12 @@ export function VMCreationModal({
   open,
   vmName,
   logs,
+  progress,
   status,
   error,
   onCancel,
+  onResume,
+  onDiscard,
   onClose,
   title,
 }: VMCreationModalProps) {
@@ -54,6 +87,39 @@ export function VMCreationModal({
           </h2>
         </div>
 
+        {/* Progress */}
+        {progress && (
+          <div className="px-6 pt-4 space-y-2">
+            <div className="flex items-center justify-between text-sm">
+              <span className="font-medium">
+                {t("Step {{step}}/{{total}}: {{phase}}", {
+                  step: progress.step,
+                  total: progress.total_steps,
+                  phase: t(progress.phase_name),
+                })}
+                {progress.phase_percent !== null &&
+                  ` (${progress.phase_percent}%)`}
+              </span>
+              <span className="text-muted-foreground">
+                {t("Elapsed {{time}}", {
+                  time: formatDuration(progress.elapsed_secs),
+                })}
+                {status === "running" &&
+                  progress.eta_secs !== null &&
+                  ` · ${t("About {{time}} left", {
+                    time: formatDuration(progress.eta_secs),
+                  })}`}
+              </span>
+            </div>
+            <Progress value={progress.overall_percent} />
+            {progress.message && (
+              <p className="text-xs text-muted-foreground truncate">
+                {progress.message}
+              </p>
+            )}
+          </div>
+        )}
+
         {/* Content - Terminal */}
         <div className="flex-1 min-h-0 relative bg-zinc-950 p-4 font-mono text-sm text-green-400 overflow-hidden m-4 rounded-md border shadow-inner">
           <div className="absolute top-2 right-2 opacity-50 pointer-events-none">
@@ -96,13 +162,23 @@ export function VMCreationModal({
               {t("Cancel")}
             </Button>
           ) : (
-            <Button
-              variant={status === "success" ? "default" : "secondary"}
-              onClick={onClose}
-              className="px-8"
-            >
-              {t("Close")}
-            </Button>
+            <>
+              {status === "error" && onDiscard && (
+                <Button variant="destructive" onClick={onDiscard}>
+                  {t("Start over")}
+                </Button>
+              )}
+              {status === "error" && onResume && (
+                <Button onClick={onResume}>{t("Resume")}</Button>
+              )}
+              <Button
+                variant={status === "success" ? "default" : "secondary"}
+                onClick={onClose}
+                className="px-8"
+              >
+                {t("Close")}
+              </Button>
+            </>
           )}
         </div>
       </div>
@@ -1,5 +1,6 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { formatError } from "@/lib/utils";
 import { open } from "@tauri-apps/plugin-dialog";
 import { listen } from "@tauri-apps/api/event";
 import {
@@ -32,7 +33,20 @@ import {
 } from "lucide-react";
 import { useLog } from "@/contexts/LogContext";
 import { useTranslation } from "react-i18next";
-import { VMCreationModal } from "./VMCreationModal";
+import { ProvisioningProgress, VMCreationModal } from "./VMCreationModal";
+
+/** Payload of the `provisioning-log` event (see `JobLogLine` in the Rust backend) */
+interface ProvisioningLogLine {
+  job_id: number;
+  vm: string;
+  line: string;
+}
+
+/** Result of `get_provisioning_checkpoint` */
+interface ProvisioningCheckpoint {
+  verified: string[];
+  next: string | null;
+}
 
 interface GpuInfo {
   id: string;
@@ -58,6 +72,77 @@ interface VmConfig {
   };
 }
 
+/** Result of `list_iso_editions` */
+interface IsoEdition {
+  index: number;
+  name: string;
+  architecture: string;
+  build: number;
+  edition_id: string | null;
+  language: string | null;
+  size_bytes: number | null;
+}
+
+/** Result of `inspect_iso` */
+interface IsoInfo {
+  volume_label: string;
+  filesystem: string;
+  install_image: string | null;
+  install_image_bytes: number | null;
+  build: number | null;
+  architecture: string | null;
+  editions: IsoEdition[];
+  sha256: string | null;
+}
+
+interface SectorLayout {
+  logical: number;
+  physical: number;
+}
+
+/** Logical/physical sector sizes offered for new disks */
+const SECTOR_LAYOUTS: Record<string, SectorLayout> = {
+  "512n": { logical: 512, physical: 512 },
+  "512e": { logical: 512, physical: 4096 },
+  "4kn": { logical: 4096, physical: 4096 },
+};
+
+/** Dynamic disk block sizes in KB, per format */
+const BLOCK_SIZES_KB = {
+  vhdx: [1024, 32768, 262144],
+  vhd: [512, 2048],
+};
+
+/** A failed check of `validate_vm_config`, tied to a `VMConfig` field */
+interface FieldIssue {
+  field: string;
+  check: string;
+  message: string;
+}
+
+/** Result of `validate_vm_config` */
+interface ValidationReport {
+  valid: boolean;
+  errors: FieldIssue[];
+  warnings: FieldIssue[];
+}
+
+/** Form labels of the `VMConfig` fields validation reports on */
+const FIELD_LABELS: Record<string, string> = {
+  name: "VM Name",
+  memory_gb: "RAM",
+  disk_size_gb: "Disk Size",
+  cpu_cores: "CPU Cores",
+  vhd_path: "VHD Storage Path",
+  iso_path: "Windows ISO",
+  edition: "Windows Edition",
+  source: "Golden VHDX",
+  disk: "Disk Format",
+  network_switch: "Network Switch",
+  gpu_name: "GPU",
+  gpu_allocation_percent: "GPU Allocation",
+};
+
 interface NetworkSwitch {
   name: string;
   switch_type: string;
@@ -79,6 +164,11 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
     "idle" | "running" | "success" | "error"
   >("idle");
   const [creationError, setCreationError] = useState<string>();
+  const [creationProgress, setCreationProgress] =
+    useState<ProvisioningProgress | null>(null);
+  const [canResume, setCanResume] = useState(false);
+  const [canDiscard, setCanDiscard] = useState(false);
+  const [validation, setValidation] = useState<ValidationReport | null>(null);
 
   // Form State
   const [config, setConfig] = useState<VmConfig>({
@@ -94,6 +184,23 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
     auto_logon: true,
   });
 
+  // Install from the ISO, or start from a sysprepped VHDX
+  const [sourceKind, setSourceKind] = useState<"iso" | "golden_image">("iso");
+  const [goldenPath, setGoldenPath] = useState("");
+  const [differencing, setDifferencing] = useState(false);
+  const [editions, setEditions] = useState<IsoEdition[]>([]);
+  const [edition, setEdition] = useState<number | null>(null);
+  const [loadingEditions, setLoadingEditions] = useState(false);
+  const [isoInfo, setIsoInfo] = useState<IsoInfo | null>(null);
+
+  const [diskFormat, setDiskFormat] = useState<"vhdx" | "vhd">("vhdx");
+  const [diskAllocation, setDiskAllocation] = useState<"dynamic" | "fixed">(
+    "dynamic",
+  );
+  const [blockSizeKb, setBlockSizeKb] = useState("default");
+  const [sectorLayout, setSectorLayout] = useState("default");
+  const [dataDiskGb, setDataDiskGb] = useState(0);
+
   const [enableGpu, setEnableGpu] = useState(true);
   const [selectedGpu, setSelectedGpu] = useState<string>("AUTO");
   const [gpuAllocation, setGpuAllocation] = useState(50);
@@ -113,7 +220,7 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
         }
       } catch (err) {
         console.error("Failed to load system info:", err);
-        addLog("error", "System", `Failed to load info: ${err}`);
+        addLog("error", "System", `Failed to load info: ${formatError(err)}`);
       } finally {
         setFetchingSystemInfo(false);
       }
@@ -129,12 +236,68 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
       });
       if (selected && typeof selected === "string") {
         setConfig((prev) => ({ ...prev, iso_path: selected }));
+        // Editions are read by mounting the ISO, so skip other media
+        if (await inspectIso(selected)) {
+          loadEditions(selected);
+        }
       }
     } catch (err) {
       console.error("Failed to browse ISO:", err);
     }
   };
 
+  const inspectIso = async (isoPath: string) => {
+    setIsoInfo(null);
+    setEditions([]);
+    setEdition(null);
+    try {
+      const info = await invoke<IsoInfo>("inspect_iso", { isoPath });
+      setIsoInfo(info);
+      return info.install_image !== null;
+    } catch (err) {
+      addLog("error", "System", `Failed to inspect ISO: ${formatError(err)}`);
+      return false;
+    }
+  };
+
+  const loadEditions = async (isoPath: string) => {
+    setEditions([]);
+    setEdition(null);
+    setLoadingEditions(true);
+    try {
+      const list = await invoke<IsoEdition[]>("list_iso_editions", {
+        isoPath,
+      });
+      setEditions(list);
+      // Prefer Pro, which the provisioning script targeted before
+      const preferred =
+        list.find((e) => /\bPro$/i.test(e.name)) ?? list[0] ?? null;
+      setEdition(preferred ? preferred.index : null);
+    } catch (err) {
+      addLog(
+        "error",
+        "System",
+        `Failed to read ISO editions: ${formatError(err)}`,
+      );
+    } finally {
+      setLoadingEditions(false);
+    }
+  };
+
+  const handleBrowseGolden = async () => {
+    try {
+      const selected = await open({
+        multiple: false,
+        filters: [{ name: "Virtual Disk", extensions: ["vhdx"] }],
+      });
+      if (selected && typeof selected === "string") {
+        setGoldenPath(selected);
+      }
+    } catch (err) {
+      console.error("Failed to browse golden image:", err);
+    }
+  };
+
   const handleBrowseStorage = async () => {
     try {
       const selected = await open({
@@ -154,13 +317,90 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
   const validateForm = () => {
     const errors: string[] = [];
     if (!config.name) errors.push(t("VM Name"));
-    if (!config.iso_path) errors.push(t("Windows ISO"));
+    if (sourceKind === "iso" && !config.iso_path) {
+      errors.push(t("Windows ISO"));
+    }
+    if (sourceKind === "iso" && config.iso_path && edition === null) {
+      errors.push(t("Windows Edition"));
+    }
+    if (sourceKind === "golden_image" && !goldenPath) {
+      errors.push(t("Golden VHDX"));
+    }
     if (!config.storage_path) errors.push(t("VHD Storage Path"));
     if (!config.switch_name) errors.push(t("Network Switch"));
     return errors;
   };
 
-  const handleCreate = async () => {
+  /** `VMConfig` payload of `create_vm` and `validate_vm_config` */
+  const buildPayload = () => ({
+    name: config.name,
+    disk_size_gb: config.disk_size_gb,
+    memory_gb: config.ram_size_gb,
+    cpu_cores: config.cpu_count,
+    iso_path: config.iso_path,
+    edition: edition ?? undefined,
+    // VHD disks boot a Generation 1 VM, which has no vTPM
+    tpm_enabled: !(sourceKind === "iso" && diskFormat === "vhd"),
+    secure_boot: false, // Often requires false for GPU-PV with unsigned drivers
+    network_switch: config.switch_name,
+    gpu_name: enableGpu ? selectedGpu : "None",
+    vhd_path: config.storage_path,
+    gpu_allocation_percent: enableGpu ? gpuAllocation : 50,
+    username: config.username,
+    password: config.password || "",
+    auto_logon: config.auto_logon,
+    source:
+      sourceKind === "iso"
+        ? { kind: "iso" }
+        : { kind: "golden_image", path: goldenPath, differencing },
+    disk: {
+      // A golden image keeps its own format and layout
+      ...(sourceKind === "iso" && {
+        format: diskFormat,
+        allocation: diskAllocation,
+        block_size_kb:
+          diskAllocation === "dynamic" && blockSizeKb !== "default"
+            ? Number(blockSizeKb)
+            : undefined,
+        logical_sector_bytes: SECTOR_LAYOUTS[sectorLayout]?.logical,
+        physical_sector_bytes: SECTOR_LAYOUTS[sectorLayout]?.physical,
+      }),
+      data_disk_gb: dataDiskGb > 0 ? dataDiskGb : undefined,
+    },
+  });
+
+  /** Offer resuming or discarding an interrupted run of the VM, if there is one */
+  const offerInterruptedRun = () =>
+    invoke<ProvisioningCheckpoint | null>("get_provisioning_checkpoint", {
+      name: config.name,
+    })
+      .then((info) => {
+        // A run that got past its first checkpoint can pick up where it stopped
+        setCanResume(!!info?.next && info.verified.length > 0);
+        setCanDiscard(!!info);
+      })
+      .catch(() => {
+        setCanResume(false);
+        setCanDiscard(false);
+      });
+
+  /** Remove what the interrupted run created, then start a fresh one */
+  const handleDiscard = async () => {
+    setCanResume(false);
+    setCanDiscard(false);
+    try {
+      await invoke("discard_provisioning_run", { name: config.name });
+    } catch (err) {
+      const errorMsg = formatError(err);
+      setCreationError(errorMsg);
+      addLog("error", "VM", errorMsg);
+      offerInterruptedRun();
+      return;
+    }
+    await handleCreate(false);
+  };
+
+  const handleCreate = async (resume = false) => {
     const missingFields = validateForm();
     if (missingFields.length > 0) {
       setError(`${t("Vui lòng sửa các lỗi sau:")} ${missingFields.join(", ")}`);
@@ -170,41 +410,78 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
     setLoading(true);
     setError(null);
 
+    // A resumed run was validated when it first started
+    if (!resume) {
+      try {
+        const report = await invoke<ValidationReport>("validate_vm_config", {
+          config: buildPayload(),
+        });
+        setValidation(report);
+        // A fresh run is refused until the interrupted one is resumed or discarded
+        const interrupted = report.errors.find(
+          (issue) => issue.check === "interrupted_run",
+        );
+        if (interrupted) {
+          setCreationLogs([]);
+          setCreationProgress(null);
+          setCreationStatus("error");
+          setCreationError(interrupted.message);
+          setShowLogModal(true);
+          offerInterruptedRun();
+          setLoading(false);
+          return;
+        }
+        if (!report.valid) {
+          setError(t("Vui lòng sửa các lỗi sau:"));
+          setLoading(false);
+          return;
+        }
+      } catch (err) {
+        setError(formatError(err));
+        setLoading(false);
+        return;
+      }
+    }
+
     // Modal Setup
     setCreationLogs([]);
+    setCreationProgress(null);
     setCreationStatus("running");
     setCreationError(undefined);
+    setCanResume(false);
+    setCanDiscard(false);
     setShowLogModal(true);
 
     addLog("info", "VM", `${t("Đang tạo VM:")} ${config.name}...`);
     setCreationLogs([`${t("Initializing process...")}`]);
 
     let unlisten: (() => void) | undefined;
+    let unlistenProgress: (() => void) | undefined;
 
     try {
       // Listen for logs
-      unlisten = await listen<string>("vm-log", (event) => {
-        setCreationLogs((prev) => [...prev, event.payload]);
-      });
+      // Other VMs may be provisioning at the same time
+      unlisten = await listen<ProvisioningLogLine>(
+        "provisioning-log",
+        (event) => {
+          if (event.payload.vm.toLowerCase() !== config.name.toLowerCase()) {
+            return;
+          }
+          setCreationLogs((prev) => [...prev, event.payload.line]);
+        },
+      );
+      unlistenProgress = await listen<ProvisioningProgress>(
+        "vm-progress",
+        (event) => {
+          if (event.payload.vm.toLowerCase() === config.name.toLowerCase()) {
+            setCreationProgress(event.payload);
+          }
+        },
+      );
+
+      const payload = buildPayload();
 
-      const payload = {
-        name: config.name,
-        disk_size_gb: config.disk_size_gb,
-        memory_gb: config.ram_size_gb,
-        cpu_cores: config.cpu_count,
-        iso_path: config.iso_path,
-        tpm_enabled: true,
-        secure_boot: false, // Often requires false for GPU-PV with unsigned drivers
-        network_switch: config.switch_name,
-        gpu_name: enableGpu ? selectedGpu : "None",
-        vhd_path: config.storage_path,
-        gpu_allocation_percent: enableGpu ? gpuAllocation : 50,
-        username: config.username,
-        password: config.password || "",
-        auto_logon: config.auto_logon,
-      };
-
-      await invoke("create_vm", { config: payload });
+      await invoke("create_vm", { config: payload, resume });
 
       setCreationStatus("success");
       const successMsg = `VM "${config.name}" ${t(
@@ -219,14 +496,16 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
       addLog("success", "VM", successMsg);
       onSuccess();
     } catch (err: any) {
-      const errorMsg = err.toString();
+      const errorMsg = formatError(err);
       setError(errorMsg);
       setCreationStatus("error");
       setCreationError(errorMsg);
       setCreationLogs((prev) => [...prev, `\n[ERROR] ${errorMsg}`]);
       addLog("error", "VM", `${t("Lỗi tạo VM:")} ${errorMsg}`);
+      offerInterruptedRun();
     } finally {
       if (unlisten) unlisten();
+      if (unlistenProgress) unlistenProgress();
       setLoading(false);
     }
   };
@@ -293,24 +572,122 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
               </div>
 
               <div className="space-y-2">
-                <Label>{t("Windows ISO")}</Label>
-                <div className="flex gap-2">
-                  <Input
-                    value={config.iso_path}
-                    readOnly
-                    placeholder={t("C:\\Downloads\\Win11.iso")}
-                    className="bg-muted"
-                  />
-                  <Button
-                    variant="outline"
-                    size="icon"
-                    onClick={handleBrowseIso}
-                  >
-                    <Disc className="h-4 w-4" />
-                  </Button>
-                </div>
+                <Label>{t("Installation Source")}</Label>
+                <Select
+                  value={sourceKind}
+                  onValueChange={(value) =>
+                    setSourceKind(value as "iso" | "golden_image")
+                  }
+                >
+                  <SelectTrigger>
+                    <SelectValue />
+                  </SelectTrigger>
+                  <SelectContent>
+                    <SelectItem value="iso">{t("Windows ISO")}</SelectItem>
+                    <SelectItem value="golden_image">
+                      {t("Golden VHDX")}
+                    </SelectItem>
+                  </SelectContent>
+                </Select>
               </div>
 
+              {sourceKind === "iso" ? (
+                <div className="space-y-2">
+                  <Label>{t("Windows ISO")}</Label>
+                  <div className="flex gap-2">
+                    <Input
+                      value={config.iso_path}
+                      readOnly
+                      placeholder={t("C:\\Downloads\\Win11.iso")}
+                      className="bg-muted"
+                    />
+                    <Button
+                      variant="outline"
+                      size="icon"
+                      onClick={handleBrowseIso}
+                    >
+                      <Disc className="h-4 w-4" />
+                    </Button>
+                  </div>
+                  {isoInfo &&
+                    (isoInfo.install_image ? (
+                      <p className="text-xs text-muted-foreground">
+                        {[
+                          isoInfo.volume_label,
+                          isoInfo.architecture,
+                          isoInfo.build && `${t("Build")} ${isoInfo.build}`,
+                        ]
+                          .filter(Boolean)
+                          .join(" · ")}
+                      </p>
+                    ) : (
+                      <p className="text-xs text-destructive">
+                        {t("This ISO is not Windows installation media")}
+                      </p>
+                    ))}
+                  {loadingEditions && (
+                    <p className="text-xs text-muted-foreground">
+                      {t("Reading editions from ISO...")}
+                    </p>
+                  )}
+                  {editions.length > 0 && (
+                    <Select
+                      value={edition !== null ? String(edition) : undefined}
+                      onValueChange={(value) => setEdition(Number(value))}
+                    >
+                      <SelectTrigger>
+                        <SelectValue placeholder={t("Windows Edition")} />
+                      </SelectTrigger>
+                      <SelectContent>
+                        {editions.map((e) => (
+                          <SelectItem key={e.index} value={String(e.index)}>
+                            {e.index}. {e.name} (
+                            {[e.architecture, e.build, e.language]
+                              .filter(Boolean)
+                              .join(", ")}
+                            )
+                          </SelectItem>
+                        ))}
+                      </SelectContent>
+                    </Select>
+                  )}
+                </div>
+              ) : (
+                <div className="space-y-2">
+                  <Label>{t("Golden VHDX")}</Label>
+                  <div className="flex gap-2">
+                    <Input
+                      value={goldenPath}
+                      readOnly
+                      placeholder={t("Sysprepped .vhdx")}
+                      className="bg-muted"
+                    />
+                    <Button
+                      variant="outline"
+                      size="icon"
+                      onClick={handleBrowseGolden}
+                    >
+                      <HardDrive className="h-4 w-4" />
+                    </Button>
+                  </div>
+                  <div className="flex items-center gap-2">
+                    <Input
+                      id="differencing"
+                      type="checkbox"
+                      className="h-4 w-4 w-auto"
+                      checked={differencing}
+                      onChange={(e) => setDifferencing(e.target.checked)}
+                    />
+                    <Label
+                      htmlFor="differencing"
+                      className="text-sm font-normal"
+                    >
+                      {t("Differencing disk (keeps the golden image as parent)")}
+                    </Label>
+                  </div>
+                </div>
+              )}
+
               <div className="space-y-2">
                 <Label>{t("VHD Storage Path")}</Label>
                 <div className="flex gap-2">
@@ -361,6 +738,105 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
                 </div>
               </div>
 
+              {sourceKind === "iso" && (
+                <div className="grid grid-cols-2 gap-4">
+                  <div className="space-y-2">
+                    <Label>{t("Disk Format")}</Label>
+                    <Select
+                      value={diskFormat}
+                      onValueChange={(value) => {
+                        setDiskFormat(value as "vhdx" | "vhd");
+                        setBlockSizeKb("default");
+                        setSectorLayout("default");
+                      }}
+                    >
+                      <SelectTrigger>
+                        <SelectValue />
+                      </SelectTrigger>
+                      <SelectContent>
+                        <SelectItem value="vhdx">VHDX</SelectItem>
+                        <SelectItem value="vhd">
+                          {t("VHD (Generation 1, no TPM)")}
+                        </SelectItem>
+                      </SelectContent>
+                    </Select>
+                  </div>
+                  <div className="space-y-2">
+                    <Label>{t("Allocation")}</Label>
+                    <Select
+                      value={diskAllocation}
+                      onValueChange={(value) =>
+                        setDiskAllocation(value as "dynamic" | "fixed")
+                      }
+                    >
+                      <SelectTrigger>
+                        <SelectValue />
+                      </SelectTrigger>
+                      <SelectContent>
+                        <SelectItem value="dynamic">
+                          {t("Dynamically expanding")}
+                        </SelectItem>
+                        <SelectItem value="fixed">{t("Fixed size")}</SelectItem>
+                      </SelectContent>
+                    </Select>
+                  </div>
+                  {diskAllocation === "dynamic" && (
+                    <div className="space-y-2">
+                      <Label>{t("Block Size")}</Label>
+                      <Select
+                        value={blockSizeKb}
+                        onValueChange={setBlockSizeKb}
+                      >
+                        <SelectTrigger>
+                          <SelectValue />
+                        </SelectTrigger>
+                        <SelectContent>
+                          <SelectItem value="default">
+                            {t("Default")}
+                          </SelectItem>
+                          {BLOCK_SIZES_KB[diskFormat].map((kb) => (
+                            <SelectItem key={kb} value={String(kb)}>
+                              {kb >= 1024 ? `${kb / 1024} MB` : `${kb} KB`}
+                            </SelectItem>
+                          ))}
+                        </SelectContent>
+                      </Select>
+                    </div>
+                  )}
+                  <div className="space-y-2">
+                    <Label>{t("Sector Size")}</Label>
+                    <Select
+                      value={sectorLayout}
+                      onValueChange={setSectorLayout}
+                    >
+                      <SelectTrigger>
+                        <SelectValue />
+                      </SelectTrigger>
+                      <SelectContent>
+                        <SelectItem value="default">{t("Default")}</SelectItem>
+                        <SelectItem value="512n">512n</SelectItem>
+                        <SelectItem value="512e">512e</SelectItem>
+                        {diskFormat === "vhdx" && (
+                          <SelectItem value="4kn">4Kn</SelectItem>
+                        )}
+                      </SelectContent>
+                    </Select>
+                  </div>
+                </div>
+              )}
+
+              <div className="space-y-2">
+                <Label>{t("Data Disk (GB, 0 for none)")}</Label>
+                <Input
+                  type="number"
+                  min={0}
+                  value={dataDiskGb}
+                  onChange={(e) =>
+                    setDataDiskGb(Math.max(0, Number(e.target.value) || 0))
+                  }
+                />
+              </div>
+
               <div className="space-y-2">
                 <Label className="flex justify-between">
                   <span>{t("RAM")}</span>
@@ -546,8 +1022,24 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
         </div>
       )}
 
+      {validation &&
+        validation.errors.length + validation.warnings.length > 0 && (
+          <ul className="space-y-1 text-sm">
+            {validation.errors.map((issue) => (
+              <li key={issue.check} className="text-destructive">
+                {t(FIELD_LABELS[issue.field] ?? issue.field)}: {issue.message}
+              </li>
+            ))}
+            {validation.warnings.map((issue) => (
+              <li key={issue.check} className="text-amber-600">
+                {t(FIELD_LABELS[issue.field] ?? issue.field)}: {issue.message}
+              </li>
+            ))}
+          </ul>
+        )}
+
       <div className="flex justify-end pt-4">
-        <Button size="lg" onClick={handleCreate} disabled={loading}>
+        <Button size="lg" onClick={() => handleCreate()} disabled={loading}>
           {loading ? (
             <>
               <Loader2 className="mr-2 h-4 w-4 animate-spin" />
@@ -562,13 +1054,18 @@ export function VMForm({ onSuccess }: { onSuccess: () => void }) {
         open={showLogModal}
         vmName={config.name}
         logs={creationLogs}
+        progress={creationProgress}
         status={creationStatus}
         error={creationError}
         onCancel={() => {
           // Send cancel command to backend
-          invoke("cancel_create_vm", { name: config.name });
+          invoke("cancel_create_vm", { name: config.name }).catch((err) =>
+            addLog("error", "VM", formatError(err)),
+          );
           setShowLogModal(false);
         }}
+        onResume={canResume ? () => handleCreate(true) : undefined}
+        onDiscard={canDiscard ? handleDiscard : undefined}
         onClose={() => {
           setShowLogModal(false);
           // Only redirect if successful
@@ -1,7 +1,10 @@
 import { useState, useEffect } from "react";
 import { VMUpdateModal } from "./VMUpdateModal";
 import { VMConnectModal } from "./VMConnectModal";
+import { VMBusyBadge } from "./VMBusyBadge";
+import { useVmOperations } from "@/hooks/use-vm-operations";
 import { invoke } from "@tauri-apps/api/core";
+import { formatError } from "@/lib/utils";
 import { confirm } from "@tauri-apps/plugin-dialog";
 import { Button, Card, CardContent } from "@/components/ui";
 import { useTranslation } from "react-i18next";
@@ -33,6 +36,7 @@ interface VMInfo {
 export function VMList() {
   const { t } = useTranslation();
   const [vms, setVms] = useState<VMInfo[]>([]);
+  const { busy } = useVmOperations();
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
   const [actionLoading, setActionLoading] = useState<string | null>(null);
@@ -52,7 +56,7 @@ export function VMList() {
       const vmList = await invoke<VMInfo[]>("list_vms");
       setVms(vmList);
     } catch (err) {
-      setError(err as string);
+      setError(formatError(err));
     } finally {
       setLoading(false);
     }
@@ -71,7 +75,7 @@ export function VMList() {
       await invoke("start_vm", { name });
       await loadVMs();
     } catch (err) {
-      alert(`Failed to start VM: ${err}`);
+      alert(`Failed to start VM: ${formatError(err)}`);
     } finally {
       setActionLoading(null);
     }
@@ -83,7 +87,7 @@ export function VMList() {
       await invoke("stop_vm", { name, force });
       await loadVMs();
     } catch (err) {
-      alert(`Failed to stop VM: ${err}`);
+      alert(`Failed to stop VM: ${formatError(err)}`);
     } finally {
       setActionLoading(null);
     }
@@ -104,7 +108,7 @@ export function VMList() {
       await invoke("delete_vm", { name });
       await loadVMs();
     } catch (err) {
-      alert(`Failed to delete VM: ${err}`);
+      alert(`Failed to delete VM: ${formatError(err)}`);
     } finally {
       setActionLoading(null);
     }
@@ -200,6 +204,7 @@ export function VMList() {
                             {t("GPU")}
                           </span>
                         )}
+                        <VMBusyBadge {...busy(vm.name)} />
                       </div>
                       <div className="flex items-center gap-4 mt-1 text-sm text-gray-500 dark:text-gray-400">
                         <span className="flex items-center gap-1">
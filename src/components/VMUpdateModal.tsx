@@ -19,6 +19,7 @@ import {
 import { Slider } from "@/components/ui/slider";
 import { Card, CardContent } from "@/components/ui/card";
 import { invoke } from "@tauri-apps/api/core";
+import { formatError } from "@/lib/utils";
 import { listen } from "@tauri-apps/api/event";
 import { Loader2, AlertTriangle } from "lucide-react";
 import { useLog } from "@/contexts/LogContext";
@@ -179,7 +180,7 @@ export function VMUpdateModal({
       onSuccess();
       // Don't auto close modal, let user see logs
     } catch (e) {
-      const msg = `Lỗi cập nhật: ${e}`;
+      const msg = `Lỗi cập nhật: ${formatError(e)}`;
       setUpdateStatus("error");
       setUpdateError(msg);
       setUpdateLogs((prev) => [...prev, `\n❌ ERROR: ${msg}`]);
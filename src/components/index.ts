@@ -3,6 +3,7 @@ export * from "./VMForm";
 export * from "./VMList";
 export * from "./GPUSelector";
 export * from "./Settings";
+export * from "./HostSettings";
 export * from "./Dashboard";
 export * from "./Logs";
 export * from "./ErrorBoundary";
@@ -0,0 +1,73 @@
+import {
+  createContext,
+  useCallback,
+  useContext,
+  useEffect,
+  useState,
+  ReactNode,
+} from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+export type HostTransport = "wsman" | "wsman_https";
+
+/** Remote Hyper-V host profile (see `HostProfile` in the Rust backend) */
+export interface HostProfile {
+  name: string;
+  hostname: string;
+  username?: string | null;
+  /** Never returned by the backend; omit to keep the stored password */
+  password?: string | null;
+  transport: HostTransport;
+  port?: number | null;
+}
+
+interface HostContextType {
+  hosts: HostProfile[];
+  /** Name of the active profile; `null` for the local machine */
+  activeHost: string | null;
+  setActiveHost: (name: string | null) => Promise<void>;
+  refreshHosts: () => Promise<void>;
+}
+
+const HostContext = createContext<HostContextType | null>(null);
+
+export function HostProvider({ children }: { children: ReactNode }) {
+  const [hosts, setHosts] = useState<HostProfile[]>([]);
+  const [activeHost, setActive] = useState<string | null>(null);
+
+  const refreshHosts = useCallback(async () => {
+    const [list, active] = await Promise.all([
+      invoke<HostProfile[]>("list_hosts"),
+      invoke<string | null>("get_active_host"),
+    ]);
+    setHosts(list);
+    setActive(active);
+  }, []);
+
+  const setActiveHost = async (name: string | null) => {
+    await invoke("set_active_host", { name });
+    setActive(name);
+  };
+
+  useEffect(() => {
+    refreshHosts().catch(() => {
+      // ignore, stays on the local machine
+    });
+  }, [refreshHosts]);
+
+  return (
+    <HostContext.Provider
+      value={{ hosts, activeHost, setActiveHost, refreshHosts }}
+    >
+      {children}
+    </HostContext.Provider>
+  );
+}
+
+export function useHost() {
+  const context = useContext(HostContext);
+  if (!context) {
+    throw new Error("useHost must be used within a HostProvider");
+  }
+  return context;
+}
@@ -0,0 +1,50 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { useHost } from "@/contexts/HostContext";
+
+/** Operation holding or waiting for a VM (see `VmOperation` in the Rust backend) */
+export interface VmOperation {
+  id: number;
+  host: string | null;
+  vm: string;
+  operation: string;
+  description: string;
+  status: "queued" | "running";
+  queued_at: number;
+  started_at: number | null;
+}
+
+const POLL_INTERVAL_MS = 2000;
+
+/** Operations on VMs of the active host, polled while mounted */
+export function useVmOperations() {
+  const { activeHost } = useHost();
+  const [operations, setOperations] = useState<VmOperation[]>([]);
+
+  useEffect(() => {
+    const load = async () => {
+      try {
+        const ops = await invoke<VmOperation[]>("list_vm_operations");
+        setOperations(ops.filter((op) => op.host === activeHost));
+      } catch {
+        // ignore
+      }
+    };
+    load();
+    const interval = setInterval(load, POLL_INTERVAL_MS);
+    return () => clearInterval(interval);
+  }, [activeHost]);
+
+  /** Running operation on `vm` and how many more are queued behind it */
+  const busy = (vm: string) => {
+    const ops = operations.filter(
+      (op) => op.vm.toLowerCase() === vm.toLowerCase(),
+    );
+    return {
+      running: ops.find((op) => op.status === "running") ?? null,
+      queued: ops.filter((op) => op.status === "queued").length,
+    };
+  };
+
+  return { operations, busy };
+}
@@ -4,3 +4,28 @@ import { twMerge } from "tailwind-merge"
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
+
+/** Error shape returned by backend commands (see `HyperVError` in the Rust backend) */
+export interface HyperVError {
+  code: string
+  message: string
+  hint?: string | null
+  record?: {
+    category?: string | null
+    category_info?: string | null
+    fully_qualified_error_id?: string | null
+    raw: string
+  } | null
+}
+
+export function isHyperVError(err: unknown): err is HyperVError {
+  return typeof err === "object" && err !== null && "code" in err && "message" in err
+}
+
+/** Human readable text for an error thrown by `invoke` */
+export function formatError(err: unknown): string {
+  if (isHyperVError(err)) {
+    return err.hint ? `${err.message} (${err.hint})` : err.message
+  }
+  return String(err)
+}